@@ -33,8 +33,9 @@
     missing_copy_implementations
 )]
 
-use bs_system::{consts::*, Level, Name, SetSocketOption, SocketOption};
-use libc::socklen_t;
+use bs_system::{consts::*, Capacity, GetSocketOption, Level, Name, Result};
+use bs_system::{SetSocketOption, SocketOption, SystemError};
+use libc::{socklen_t, EINVAL};
 use std::hash::Hash;
 use std::mem::size_of;
 
@@ -57,6 +58,26 @@ impl SocketFilter {
         Self { code, jt, jf, k }
     }
 
+    /// The instruction's opcode
+    pub const fn code(&self) -> u16 {
+        self.code
+    }
+
+    /// The offset jumped by when a conditional jump's condition holds
+    pub const fn jt(&self) -> u8 {
+        self.jt
+    }
+
+    /// The offset jumped by when a conditional jump's condition doesn't hold
+    pub const fn jf(&self) -> u8 {
+        self.jf
+    }
+
+    /// The instruction's constant operand
+    pub const fn k(&self) -> u32 {
+        self.k
+    }
+
     /// Helper function, creates a new `SocketFilter` with given `code`
     /// other parameters (`jt`, `jf`, `k`) are set to 0
     pub const fn from_code(code: u16) -> Self {
@@ -84,6 +105,48 @@ impl SocketFilterProgram {
         let filter = v.into_boxed_slice();
         Self { len, filter }
     }
+
+    /// The program's instructions
+    pub fn instructions(&self) -> &[SocketFilter] {
+        &self.filter
+    }
+
+    /// Checks the program the way the kernel does before attaching it: it must hold between one
+    /// and `BPF_MAXINSNS` instructions, end with a `BPF_RET` and only jump forward within itself
+    /// # Errors
+    /// `SystemError::Validation` describing the first violation
+    pub fn validate(&self) -> Result<()> {
+        let len = self.filter.len();
+        if len == 0 || len > BPF_MAXINSNS {
+            return Err(SystemError::Validation(format!(
+                "program has {} instructions, expected 1 to {}",
+                len, BPF_MAXINSNS
+            )));
+        }
+        for (pc, instruction) in self.filter.iter().enumerate() {
+            let code = i32::from(instruction.code);
+            if code & 0x07 != BPF_JMP {
+                continue;
+            }
+            let offsets = if code & 0xf0 == BPF_JA {
+                vec![instruction.k as usize]
+            } else {
+                vec![instruction.jt.into(), instruction.jf.into()]
+            };
+            if let Some(offset) = offsets.into_iter().find(|&offset| offset >= len - 1 - pc) {
+                return Err(SystemError::Validation(format!(
+                    "instruction {} jumps by {} out of the program",
+                    pc, offset
+                )));
+            }
+        }
+        if i32::from(self.filter[len - 1].code) & 0x07 != BPF_RET {
+            return Err(SystemError::Validation(
+                "program doesn't end with a return instruction".into(),
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl SocketOption for SocketFilterProgram {
@@ -106,6 +169,35 @@ impl SocketOption for SocketFilterProgram {
 
 impl SetSocketOption for SocketFilterProgram {}
 
+/// `SO_GET_FILTER`, which shares its value with `SO_ATTACH_FILTER`, returns the attached
+/// classic program
+impl GetSocketOption for SocketFilterProgram {
+    fn capacity() -> Capacity {
+        Capacity::Queried {
+            unit: size_of::<SocketFilter>(),
+        }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let instructions = bytes.chunks_exact(size_of::<SocketFilter>());
+        if !instructions.remainder().is_empty() {
+            return Err(SystemError::from_raw_os_error(EINVAL));
+        }
+        Ok(Self::from_vector(
+            instructions
+                .map(|bytes| {
+                    SocketFilter::new(
+                        u16::from_ne_bytes([bytes[0], bytes[1]]),
+                        bytes[2],
+                        bytes[3],
+                        u32::from_ne_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+                    )
+                })
+                .collect(),
+        ))
+    }
+}
+
 /// Different kinds of comparisons to perform upon `BPF_JMP` instructions
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
@@ -163,6 +255,14 @@ pub fn return_sequence() -> (Vec<Instruction>, usize, usize) {
     (vec![DROP, RETURN_A, LOAD_LENGTH], 0, 2)
 }
 
+/// Generates a sequence of instructions that returns `value`.
+///
+/// Used by programs whose return value isn't a packet length, e.g. `PACKET_FANOUT_CBPF` steering
+/// programs returning the index of a socket.
+pub fn return_value(value: u32) -> Vec<Instruction> {
+    vec![Instruction::new((BPF_RET | BPF_K) as _, 0, 0, value)]
+}
+
 /// Generates a sequence of instructions that passes the entire packet.
 pub fn teotology() -> Vec<Instruction> {
     vec![RETURN_A, LOAD_LENGTH]
@@ -174,13 +274,43 @@ pub fn contradiction() -> Vec<Instruction> {
 }
 
 /// Generates a sequence of instructions that implements a conditional jump.
+///
+/// Targets beyond the reach of the 8 bit conditional jump offsets are reached through `JA`
+/// trampolines placed right after the jump.
 pub fn jump(comparison: Comparison, operand: u32, jt: usize, jf: usize) -> Vec<Instruction> {
-    vec![Instruction::new(
+    // each trampoline pushes the targets that are still jumped to directly one instruction away
+    let (mut far_jt, mut far_jf) = (false, false);
+    loop {
+        let trampolines = far_jt as usize + far_jf as usize;
+        let far = (
+            far_jt || jt + trampolines > u8::MAX as usize,
+            far_jf || jf + trampolines > u8::MAX as usize,
+        );
+        if far == (far_jt, far_jf) {
+            break;
+        }
+        (far_jt, far_jf) = far;
+    }
+    let trampolines = far_jt as usize + far_jf as usize;
+    let (mut near_jt, mut near_jf) = (jt + trampolines, jf + trampolines);
+
+    // in reverse order, the jump is followed by the `jt` trampoline and then the `jf` one
+    let mut sequence = Vec::new();
+    if far_jf {
+        sequence.push(jump_always(jf));
+        near_jf = trampolines - 1;
+    }
+    if far_jt {
+        sequence.push(jump_always(jt + far_jf as usize));
+        near_jt = 0;
+    }
+    sequence.push(Instruction::new(
         (BPF_JMP as u8 | comparison as u8 | BPF_K as u8) as _,
-        jt as _,
-        jf as _,
+        near_jt as _,
+        near_jf as _,
         operand,
-    )]
+    ));
+    sequence
 }
 
 /// Generates a sequence of instructions that loads one octet from a given offset in the packet.
@@ -212,3 +342,245 @@ pub fn load_u32_at(offset: u32) -> Vec<Instruction> {
         offset,
     )]
 }
+
+/// Generates a sequence of instructions that loads two octets from offset `offset` in the
+/// transport header following the IPv4 header found at `network_offset`, according to its IHL.
+pub fn load_u16_at_transport(network_offset: u32, offset: u32) -> Vec<Instruction> {
+    vec![
+        Instruction::new(
+            (BPF_IND | BPF_LD | BPF_H) as _,
+            0,
+            0,
+            network_offset.wrapping_add(offset),
+        ),
+        Instruction::new((BPF_MSH | BPF_LDX | BPF_B) as _, 0, 0, network_offset),
+    ]
+}
+
+/// Generates a sequence of instructions that loads a piece of ancillary data (one of the
+/// `SKF_AD_*` extensions) about the packet.
+pub fn load_ancillary(ancillary: i32) -> Vec<Instruction> {
+    vec![Instruction::new(
+        (BPF_ABS | BPF_LD | BPF_W) as _,
+        0,
+        0,
+        (SKF_AD_OFF + ancillary) as u32,
+    )]
+}
+
+/// Generates a sequence of instructions that bitwise-ands the accumulator with `mask`.
+pub fn mask(mask: u32) -> Vec<Instruction> {
+    vec![Instruction::new(
+        (BPF_ALU | BPF_AND | BPF_K) as _,
+        0,
+        0,
+        mask,
+    )]
+}
+
+/// Generates a sequence of instructions that jumps `jt` instructions past its end if the
+/// accumulator holds one of `values`, and `jf` instructions past its end otherwise.
+///
+/// `values` must be sorted and deduplicated. They are searched via a balanced binary search tree
+/// of `JGT` and `JEQ` comparisons, so only one load of the inspected value is needed, and at most
+/// `2 * log2(values.len())` comparisons are performed. Targets beyond the reach of the 8 bit
+/// conditional jump offsets are reached through `JA` trampolines.
+pub fn jump_in(values: &[u32], jt: usize, jf: usize) -> Vec<Instruction> {
+    if values.is_empty() {
+        return vec![jump_always(jf)];
+    }
+    let mut tree = SearchTree {
+        instructions: Vec::new(),
+        accept: -(jt as isize) - 1,
+        reject: -(jf as isize) - 1,
+    };
+    let _entry = tree.search(values);
+    tree.instructions
+}
+
+fn jump_always(offset: usize) -> Instruction {
+    Instruction::new((BPF_JMP | BPF_JA) as _, 0, 0, offset as u32)
+}
+
+/// Builds a binary search tree in reverse order, like every other sequence.
+///
+/// Jump targets are identified by their index in `instructions`, where -1 is the instruction
+/// right after the tree, -2 the one after it, and so on.
+struct SearchTree {
+    instructions: Vec<Instruction>,
+    // closest instructions leading to the accepting and rejecting targets
+    accept: isize,
+    reject: isize,
+}
+
+impl SearchTree {
+    /// offset of `target` relative to the next instruction to be generated
+    fn offset(&self, target: isize) -> usize {
+        (self.instructions.len() as isize - target - 1) as usize
+    }
+
+    fn trampoline(&mut self, target: isize) -> isize {
+        self.instructions.push(jump_always(self.offset(target)));
+        let trampoline = self.instructions.len() as isize - 1;
+        if target == self.accept {
+            self.accept = trampoline;
+        }
+        if target == self.reject {
+            self.reject = trampoline;
+        }
+        trampoline
+    }
+
+    fn conditional(&mut self, comparison: Comparison, value: u32, jt: isize, jf: isize) -> isize {
+        let (mut jt, mut jf) = (jt, jf);
+        loop {
+            if self.offset(jt) > u8::MAX as usize {
+                jt = self.trampoline(jt);
+            } else if self.offset(jf) > u8::MAX as usize {
+                jf = self.trampoline(jf);
+            } else {
+                break;
+            }
+        }
+        let (jt, jf) = (self.offset(jt), self.offset(jf));
+        self.instructions.extend(jump(comparison, value, jt, jf));
+        self.instructions.len() as isize - 1
+    }
+
+    /// generates the subtree searching `values`, returning its entry point
+    fn search(&mut self, values: &[u32]) -> isize {
+        if values.is_empty() {
+            return self.reject;
+        }
+        let middle = values.len() / 2;
+        let (lesser, value, greater) = (&values[..middle], values[middle], &values[middle + 1..]);
+
+        let greater_entry = self.search(greater);
+        let lesser_entry = self.search(lesser);
+        let equal = self.conditional(Comparison::Equal, value, self.accept, lesser_entry);
+        if greater.is_empty() {
+            equal
+        } else {
+            self.conditional(Comparison::GreaterThan, value, greater_entry, equal)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// interprets a sequence generated by `jump_in`, returning the offset past its end it jumps to
+    fn run(sequence: &[Instruction], accumulator: u32) -> usize {
+        let mut pc = 0;
+        while pc < sequence.len() {
+            let SocketFilter { code, jt, jf, k } = sequence[pc];
+            let taken = match Comparison::from(code as u8 & 0xf0) {
+                Comparison::Always => {
+                    pc += 1 + k as usize;
+                    continue;
+                }
+                Comparison::Equal => accumulator == k,
+                Comparison::GreaterThan => accumulator > k,
+                _ => unreachable!(),
+            };
+            pc += 1 + if taken { jt } else { jf } as usize;
+        }
+        pc - sequence.len()
+    }
+
+    #[test]
+    fn jump_in_searches() {
+        let values: Vec<u32> = (0..1000).map(|v| v * 3).collect();
+        let mut sequence = jump_in(&values, 7, 300);
+        sequence.reverse();
+        for accumulator in 0..3010 {
+            let expected = if accumulator % 3 == 0 && accumulator < 3000 {
+                7
+            } else {
+                300
+            };
+            assert_eq!(run(&sequence, accumulator), expected);
+        }
+    }
+
+    #[test]
+    fn jump_far() {
+        for &(jt, jf) in &[
+            (3, 7),
+            (300, 7),
+            (3, 700),
+            (300, 700),
+            (254, 255),
+            (255, 256),
+        ] {
+            let mut sequence = jump(Comparison::Equal, 5, jt, jf);
+            sequence.reverse();
+            assert_eq!(run(&sequence, 5), jt);
+            assert_eq!(run(&sequence, 6), jf);
+        }
+    }
+
+    #[test]
+    fn get_socket_filter_program() {
+        let fd = bs_system::cvt(unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) });
+        let fd = fd.unwrap();
+        // no attached program
+        let program = SocketFilterProgram::get(fd).unwrap();
+        assert!(program.instructions().is_empty());
+
+        let instructions = vec![
+            SocketFilter::new((BPF_LD | BPF_B | BPF_ABS) as u16, 0, 0, 9),
+            SocketFilter::new((BPF_JMP | BPF_JEQ | BPF_K) as u16, 0, 1, 17),
+            SocketFilter::new((BPF_RET | BPF_K) as u16, 0, 0, u32::MAX),
+            SocketFilter::new((BPF_RET | BPF_K) as u16, 0, 0, 0),
+        ];
+        let _ = SocketFilterProgram::from_vector(instructions.clone())
+            .set(fd)
+            .unwrap();
+        let program = SocketFilterProgram::get(fd).unwrap();
+        assert_eq!(program.instructions(), &instructions[..]);
+        let _ = unsafe { libc::close(fd) };
+    }
+
+    #[test]
+    fn socket_filter_program_from_bytes_invalid() {
+        assert_eq!(
+            SocketFilterProgram::from_bytes(&[0; 12]).map(|p| p.len),
+            Err(SystemError::from_raw_os_error(EINVAL))
+        );
+    }
+
+    #[test]
+    fn validate_socket_filter_program() {
+        let program = |instructions| SocketFilterProgram::from_vector(instructions).validate();
+        let ret = SocketFilter::new((BPF_RET | BPF_K) as u16, 0, 0, 0);
+        assert_eq!(program(vec![ret]), Ok(()));
+        assert!(matches!(program(vec![]), Err(SystemError::Validation(_))));
+        assert!(matches!(
+            program(vec![ret; BPF_MAXINSNS + 1]),
+            Err(SystemError::Validation(_))
+        ));
+        let load = SocketFilter::new((BPF_LD | BPF_LEN | BPF_W) as u16, 0, 0, 0);
+        assert!(matches!(
+            program(vec![ret, load]),
+            Err(SystemError::Validation(_))
+        ));
+        let jump = SocketFilter::new((BPF_JMP | BPF_JEQ | BPF_K) as u16, 0, 1, 17);
+        assert_eq!(program(vec![jump, ret, ret]), Ok(()));
+        assert!(matches!(
+            program(vec![jump, ret]),
+            Err(SystemError::Validation(_))
+        ));
+        let always = SocketFilter::new((BPF_JMP | BPF_JA) as u16, 0, 0, 1);
+        assert!(matches!(
+            program(vec![always, ret]),
+            Err(SystemError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn jump_in_empty() {
+        assert_eq!(run(&jump_in(&[], 1, 2), 0), 2);
+    }
+}
@@ -33,12 +33,13 @@
     missing_copy_implementations
 )]
 
-use bs_system::{consts::*, Level, Name, Result, SetSocketOption, SocketOption, SystemError};
+use bs_system::SystemError;
+use bs_system::{consts::*, Level, Name, Operation, Result, SetSocketOption, SocketOption};
 use libc::socklen_t;
 use log::debug;
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::FromPrimitive as FromVal;
-use std::mem::size_of_val;
+use std::mem::{size_of, size_of_val};
 use std::os::unix::io::RawFd;
 
 /// `bpf_insn`
@@ -231,23 +232,102 @@ impl SocketFilterBpfAttribute {
 
         let fd = unsafe { syscall!(BPF, 5, ptr, size_of_val(&attr)) as i32 };
 
-        if cfg!(debug_assertions) {
-            unsafe {
+        let log = if cfg!(debug_assertions) {
+            let log = unsafe {
                 forget(self.log_buffer);
-                let log =
-                    Vec::from_raw_parts(log_ptr, self.log_size as usize, self.log_size as usize);
-                debug!("BPF_PROG_LOAD log: {:}", String::from_utf8(log).unwrap());
-            }
-        }
+                Vec::from_raw_parts(log_ptr, self.log_size as usize, self.log_size as usize)
+            };
+            // the verifier NUL-terminates its log
+            let end = log.iter().position(|&byte| byte == 0).unwrap_or(log.len());
+            let log = String::from_utf8_lossy(&log[..end]).into_owned();
+            debug!("BPF_PROG_LOAD log: {:}", log);
+            log
+        } else {
+            String::new()
+        };
 
         if fd > 0 {
             Ok(SocketFilterFd { fd })
         } else {
-            Err(SystemError(-fd))
+            Err(SystemError::Verifier { errno: -fd, log })
         }
     }
 }
 
+/// File descriptor referring to an eBPF hash map, used as a set of `u32` keys by [`jump_in`]
+///
+/// The map only needs to be kept open until programs referring to it are loaded, after which the
+/// kernel keeps it alive for them.
+///
+/// [`jump_in`]: fn.jump_in.html
+#[derive(Debug)]
+pub struct SetMapFd {
+    fd: RawFd,
+}
+
+impl SetMapFd {
+    /// Calls the `bpf(2)` syscall to create a hash map whose keys are `values`
+    pub fn new(values: &[u32]) -> Result<Self> {
+        #[repr(C)]
+        struct CreateAttr {
+            map_type: u32,
+            key_size: u32,
+            value_size: u32,
+            max_entries: u32,
+            map_flags: u32,
+        }
+        #[repr(C)]
+        struct UpdateAttr {
+            map_fd: u32,
+            key: u64,
+            value: u64,
+            flags: u64,
+        }
+
+        let mut create = CreateAttr {
+            map_type: BPF_MAP_TYPE_HASH,
+            key_size: size_of::<u32>() as u32,
+            value_size: size_of::<u8>() as u32,
+            max_entries: values.len() as u32,
+            map_flags: 0,
+        };
+        let ptr: *mut CreateAttr = &mut create;
+        let fd = unsafe { syscall!(BPF, BPF_MAP_CREATE, ptr, size_of_val(&create)) as i32 };
+        if fd <= 0 {
+            return Err(
+                SystemError::from_raw_os_error(-fd).with_operation(Operation::Syscall("bpf"))
+            );
+        }
+        let set = Self { fd };
+
+        let present: *const u8 = &1;
+        for value in values {
+            let key: *const u32 = value;
+            let mut update = UpdateAttr {
+                map_fd: set.fd as u32,
+                key: key as u64,
+                value: present as u64,
+                flags: BPF_ANY,
+            };
+            let ptr: *mut UpdateAttr = &mut update;
+            let res =
+                unsafe { syscall!(BPF, BPF_MAP_UPDATE_ELEM, ptr, size_of_val(&update)) as i32 };
+            if res != 0 {
+                return Err(
+                    SystemError::from_raw_os_error(-res).with_operation(Operation::Syscall("bpf"))
+                );
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl Drop for SetMapFd {
+    fn drop(&mut self) {
+        let _ = unsafe { libc::close(self.fd) };
+    }
+}
+
 /// Different kinds of comparisons to perform upon `BPF_JMP` instructions
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd, FromPrimitive, ToPrimitive)]
@@ -264,22 +344,22 @@ pub enum Comparison {
     AndMask = 0x40,
     /// true if the operands differ
     NotEqual = 0x50,
-    /// true if the first operand is greater then the second (operands are treated as signed 64-bit
+    /// true if the first operand is greater then the second (operands are treated as signed
     /// integers)
     SignedGreaterThan = 0x60,
-    /// true if the first operand is greater or equal to the second (operands are treated as signed 64-bit
+    /// true if the first operand is greater or equal to the second (operands are treated as signed
     /// integers)
     SignedGreaterEqual = 0x70,
     /// true if the first operand is lesser then the second
     LesserThan = 0xa0,
     /// true if the first operand is lesser or equal to the second
     LesserEqual = 0xb0,
-    /// true if the first operand is lesser then the second (operands are treated as signed 64-bit
+    /// true if the first operand is lesser then the second (operands are treated as signed
     /// integers)
     SignedLesserThan = 0xc0,
-    /// true if the first operand is lesser or equal to the second (operands are treated as signed 64-bit
+    /// true if the first operand is lesser or equal to the second (operands are treated as signed
+    /// integers)
     SignedLesserEqual = 0xd0,
-    // TODO - 32 bit comparisons
     #[doc(hidden)]
     Unknown,
 }
@@ -310,8 +390,6 @@ impl From<u32> for Operand {
     }
 }
 
-const OFFSET_SK_BUFF_LEN: i16 = 0;
-
 const EXIT: Instruction = Instruction::from_code((BPF_JMP | BPF_EXIT) as u8);
 
 const fn load_packet_length(dst: Register) -> Instruction {
@@ -354,6 +432,14 @@ pub fn return_sequence() -> (Vec<Instruction>, usize, usize) {
     (res, 0, 2)
 }
 
+/// Generates a sequence of instructions that returns `value`.
+///
+/// Used by programs whose return value isn't a packet length, e.g. `PACKET_FANOUT_EBPF` steering
+/// programs returning the index of a socket.
+pub fn return_value(value: u32) -> Vec<Instruction> {
+    vec![EXIT, copy_imm(Register::Ret, value as i32)]
+}
+
 /// Generates a sequence of instructions that passes the entire packet.
 pub fn teotology() -> Vec<Instruction> {
     vec![EXIT, load_packet_length(Register::Ret)]
@@ -376,7 +462,7 @@ const fn jump_always(offset: i16) -> Instruction {
 
 const fn jump_imm(comp: Comparison, reg: Register, imm: i32, offset: i16) -> Instruction {
     Instruction::new(
-        (BPF_JMP as u8) | comp as u8 | (BPF_K as u8),
+        (BPF_JMP32 as u8) | comp as u8 | (BPF_K as u8),
         reg,
         Register::None,
         offset,
@@ -395,6 +481,9 @@ const fn jump_reg(comp: Comparison, dst: Register, src: Register, offset: i16) -
 }
 
 /// Generates a sequence of instructions that implements a conditional jump.
+///
+/// Comparisons against an immediate value are 32 bits wide (`BPF_JMP32`), matching the width of
+/// values loaded from the packet, so immediates above `i32::max_value()` aren't sign extended.
 pub fn jump(comparison: Comparison, operand: Operand, jt: usize, jf: usize) -> Vec<Instruction> {
     let distance_to_true_label: i16 = jt as i16 + 1;
     match operand {
@@ -442,6 +531,134 @@ pub fn load_u32_at(offset: i32) -> Vec<Instruction> {
     )]
 }
 
+/// Generates a sequence of instructions that loads a 32-bit field of the program's `__sk_buff`
+/// context, found at `offset`.
+pub fn load_context_u32(offset: i16) -> Vec<Instruction> {
+    vec![Instruction::new(
+        (BPF_LDX | BPF_W | BPF_MEM) as u8,
+        Register::SocketBuffer,
+        Register::Ret,
+        offset,
+        0,
+    )]
+}
+
+/// Generates a sequence of instructions that converts the lower 16 bits of R0 from network byte
+/// order to host byte order.
+pub fn network_to_host_u16() -> Vec<Instruction> {
+    vec![Instruction::new(
+        (BPF_ALU | BPF_END | BPF_FROM_BE) as u8,
+        Register::None,
+        Register::Ret,
+        0,
+        16,
+    )]
+}
+
+/// Generates a sequence of instructions that calls the kernel helper function identified by
+/// `helper`, leaving its return value in R0.
+pub fn call(helper: i32) -> Vec<Instruction> {
+    vec![Instruction::new(
+        (BPF_JMP | BPF_CALL) as u8,
+        Register::None,
+        Register::None,
+        0,
+        helper,
+    )]
+}
+
+/// Generates a sequence of instructions that bitwise-ands the lower 32 bits of R0 with `mask`.
+pub fn mask(mask: u32) -> Vec<Instruction> {
+    vec![Instruction::new(
+        (BPF_ALU | BPF_AND | BPF_K) as u8,
+        Register::None,
+        Register::Ret,
+        0,
+        mask as i32,
+    )]
+}
+
+/// Generates a sequence of instructions that loads two octets from offset `offset` in the
+/// transport header following the IPv4 header found at `network_offset`, according to its IHL.
+pub fn load_u16_at_transport(network_offset: i32, offset: i32) -> Vec<Instruction> {
+    vec![
+        Instruction::new(
+            (BPF_IND | BPF_LD | BPF_H) as u8,
+            Register::Gen1,
+            Register::None,
+            0,
+            network_offset.wrapping_add(offset),
+        ),
+        // R7 = R0
+        copy(Register::Ret, Register::Gen1),
+        Instruction::new(
+            (BPF_ALU | BPF_LSH | BPF_K) as u8,
+            Register::None,
+            Register::Ret,
+            0,
+            2,
+        ),
+        Instruction::new(
+            (BPF_ALU | BPF_AND | BPF_K) as u8,
+            Register::None,
+            Register::Ret,
+            0,
+            0xf,
+        ),
+    ]
+    .into_iter()
+    .chain(load_u8_at(network_offset))
+    .collect()
+}
+
+/// Generates a sequence of instructions that jumps `jt` instructions past its end if the lower 32
+/// bits of R0 are a key of `set`, and `jf` instructions past its end otherwise.
+pub fn jump_in(set: &SetMapFd, jt: usize, jf: usize) -> Vec<Instruction> {
+    let key_offset = -(size_of::<u32>() as i16);
+    vec![
+        jump_always(jf as i16),
+        // 64 bit comparison, as R0 now holds a pointer
+        Instruction::new(
+            (BPF_JMP | BPF_JNE | BPF_K) as u8,
+            Register::None,
+            Register::Ret,
+            jt as i16 + 1,
+            0,
+        ),
+        Instruction::new(
+            (BPF_JMP | BPF_CALL) as u8,
+            Register::None,
+            Register::None,
+            0,
+            BPF_FUNC_MAP_LOOKUP_ELEM,
+        ),
+        Instruction::new(
+            (BPF_ALU64 | BPF_ADD | BPF_K) as u8,
+            Register::None,
+            Register::Arg1,
+            0,
+            key_offset as i32,
+        ),
+        // R2 = R10
+        copy(Register::FramePointer, Register::Arg1),
+        // second half of the 16 octet `BPF_LD | BPF_DW | BPF_IMM` instruction
+        Instruction::from_code(0),
+        Instruction {
+            code: (BPF_LD | BPF_DW | BPF_IMM) as u8,
+            regs: (BPF_PSEUDO_MAP_FD << 4) | Register::Context as u8,
+            off: 0,
+            imm: set.fd,
+        },
+        Instruction::new(
+            (BPF_STX | BPF_W | BPF_MEM) as u8,
+            Register::Ret,
+            Register::FramePointer,
+            key_offset,
+            0,
+        ),
+    ]
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
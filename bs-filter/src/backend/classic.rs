@@ -1,7 +1,7 @@
-use crate::backend::{private::FilterBackend, Backend};
+use crate::backend::{private::FilterBackend, Ancillary, Backend, Relation};
 use bs_cbpf as cbpf;
 use bs_system::{Result, SystemError};
-use libc::EOVERFLOW;
+use std::convert::Infallible;
 
 /// Phantom struct to represent Classic BPF related
 /// functionalities.
@@ -10,6 +10,7 @@ pub struct Classic {}
 
 impl FilterBackend for Classic {
     type SocketOption = cbpf::SocketFilterProgram;
+    type Set = Infallible;
 }
 
 impl Backend for Classic {
@@ -25,6 +26,10 @@ impl Backend for Classic {
         cbpf::return_sequence()
     }
 
+    fn return_value(value: u32) -> Vec<Self::Instruction> {
+        cbpf::return_value(value)
+    }
+
     fn teotology() -> Vec<Self::Instruction> {
         cbpf::teotology()
     }
@@ -36,9 +41,25 @@ impl Backend for Classic {
     fn into_socket_option(instructions: Vec<Self::Instruction>) -> Result<Self::SocketOption> {
         let len = instructions.len();
         if len > u16::max_value() as usize {
-            return Err(SystemError(EOVERFLOW));
+            return Err(SystemError::Compilation(format!(
+                "program of {} instructions is too long",
+                len
+            )));
+        }
+        let program = Self::SocketOption::from_vector(instructions);
+        program.validate()?;
+        Ok(program)
+    }
+
+    fn comparison(relation: Relation) -> Option<Self::Comparison> {
+        match relation {
+            Relation::Equal => Some(cbpf::Comparison::Equal),
+            Relation::GreaterThan => Some(cbpf::Comparison::GreaterThan),
+            Relation::GreaterEqual => Some(cbpf::Comparison::GreaterEqual),
+            Relation::AndMask => Some(cbpf::Comparison::AndMask),
+            // implemented via the negation of their counterparts
+            Relation::NotEqual | Relation::LesserThan | Relation::LesserEqual => None,
         }
-        Ok(Self::SocketOption::from_vector(instructions))
     }
 
     fn jump(
@@ -61,4 +82,144 @@ impl Backend for Classic {
     fn load_u32_at(offset: u32) -> Vec<Self::Instruction> {
         cbpf::load_u32_at(offset)
     }
+
+    fn load_u16_at_transport(network_offset: u32, offset: u32) -> Vec<Self::Instruction> {
+        cbpf::load_u16_at_transport(network_offset, offset)
+    }
+
+    fn load_ancillary(ancillary: Ancillary) -> Vec<Self::Instruction> {
+        cbpf::load_ancillary(ancillary as i32)
+    }
+
+    fn mask(mask: u32) -> Vec<Self::Instruction> {
+        cbpf::mask(mask)
+    }
+
+    fn jump_in(
+        values: &[u32],
+        jt: usize,
+        jf: usize,
+        _sets: &mut Vec<Self::Set>,
+    ) -> Result<Vec<Self::Instruction>> {
+        Ok(cbpf::jump_in(values, jt, jf))
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idiom::ethernet::ether_type_arp;
+    use crate::idiom::ip::ip4_src_in;
+    use crate::idiom::transport::port_in;
+    use crate::layer::Ethernet;
+    use crate::Predicate;
+    use bs_system::consts::*;
+    use libc::{ETH_P_ARP, ETH_P_IP, ETH_P_IPV6};
+    use std::net::Ipv4Addr;
+
+    /// interprets `program` on `packet` the way the kernel does, returning the length to which
+    /// the packet is truncated
+    fn run(program: &[cbpf::Instruction], packet: &[u8]) -> u32 {
+        let load = |offset: u32, size: usize| {
+            let bytes = packet.get(offset as usize..offset as usize + size)?;
+            Some(
+                bytes
+                    .iter()
+                    .fold(0, |value, &byte| value << 8 | u32::from(byte)),
+            )
+        };
+        let (mut a, mut x, mut pc) = (0, 0, 0);
+        loop {
+            let instruction = program[pc];
+            let (code, k) = (i32::from(instruction.code()), instruction.k());
+            pc += 1;
+            match code & 0x07 {
+                BPF_LD => {
+                    let size = match code & 0x18 {
+                        BPF_W => 4,
+                        BPF_H => 2,
+                        _ => 1,
+                    };
+                    let value = match code & 0xe0 {
+                        BPF_ABS => load(k, size),
+                        BPF_IND => load(x + k, size),
+                        BPF_LEN => Some(packet.len() as u32),
+                        _ => unimplemented!(),
+                    };
+                    match value {
+                        Some(value) => a = value,
+                        None => return 0,
+                    }
+                }
+                BPF_LDX => match load(k, 1) {
+                    Some(value) => x = (value & 0xf) * 4,
+                    None => return 0,
+                },
+                BPF_ALU => a &= k,
+                BPF_JMP => {
+                    let taken = match code & 0xf0 {
+                        BPF_JA => {
+                            pc += k as usize;
+                            continue;
+                        }
+                        BPF_JEQ => a == k,
+                        BPF_JGT => a > k,
+                        BPF_JGE => a >= k,
+                        BPF_JSET => a & k != 0,
+                        _ => unimplemented!(),
+                    };
+                    let offset = if taken {
+                        instruction.jt()
+                    } else {
+                        instruction.jf()
+                    };
+                    pc += usize::from(offset);
+                }
+                BPF_RET if code & 0x18 == BPF_A => return a,
+                BPF_RET => return k,
+                _ => unimplemented!(),
+            }
+        }
+    }
+
+    /// an ethernet frame of `ether_type` carrying an IPv4 UDP datagram from `source`
+    fn frame(ether_type: i32, source: &str, port: u16) -> Vec<u8> {
+        let source: Ipv4Addr = source.parse().unwrap();
+        let mut frame = vec![0; 12];
+        frame.extend((ether_type as u16).to_be_bytes());
+        frame.extend([0x45, 0, 0, 30, 0, 0, 0, 0, 64, 17, 0, 0]);
+        frame.extend(source.octets());
+        frame.extend([127, 0, 0, 1]);
+        frame.extend(port.to_be_bytes());
+        frame.extend(port.to_be_bytes());
+        frame.extend([0, 10, 0, 0, b'b', b's']);
+        frame
+    }
+
+    fn compile(p: Predicate<Classic, Ethernet>) -> Vec<cbpf::Instruction> {
+        let program: Vec<_> = p.compile().unwrap().into_iter().collect();
+        assert!(program.len() > u8::MAX as usize);
+        program
+    }
+
+    #[test]
+    fn far_jumps() {
+        let ips: Vec<_> = (0..500_u32).map(|i| (0x0a00_0000 + i).into()).collect();
+        let accepted = frame(ETH_P_IP, "10.0.1.7", 53).len() as u32;
+
+        // the EtherType check jumps over the whole search tree
+        let program = compile(ip4_src_in(&ips) & port_in(&[53, 123, 443]));
+        assert_eq!(run(&program, &frame(ETH_P_IP, "10.0.1.7", 53)), accepted);
+        assert_eq!(run(&program, &frame(ETH_P_IP, "10.0.1.7", 80)), 0);
+        assert_eq!(run(&program, &frame(ETH_P_IP, "10.0.2.0", 53)), 0);
+        assert_eq!(run(&program, &frame(ETH_P_IPV6, "10.0.1.7", 53)), 0);
+        assert_eq!(run(&program, &frame(ETH_P_ARP, "10.0.1.7", 53)), 0);
+
+        let program = compile(ip4_src_in(&ips) | ether_type_arp());
+        assert_eq!(run(&program, &frame(ETH_P_IP, "10.0.1.7", 53)), accepted);
+        assert_eq!(run(&program, &frame(ETH_P_IP, "10.0.2.0", 53)), 0);
+        assert_eq!(run(&program, &frame(ETH_P_IPV6, "10.0.1.7", 53)), 0);
+        assert_eq!(run(&program, &frame(ETH_P_ARP, "10.0.1.7", 53)), accepted);
+    }
 }
@@ -1,7 +1,7 @@
-use crate::backend::{private::FilterBackend, Backend};
+use crate::backend::{private::FilterBackend, Ancillary, Backend, Relation};
 use bs_ebpf as ebpf;
+use bs_system::consts::*;
 use bs_system::{Result, SystemError};
-use libc::EOVERFLOW;
 
 /// Phantom struct to represent Extended BPF related
 /// functionalities.
@@ -10,6 +10,7 @@ pub struct Extended {}
 
 impl FilterBackend for Extended {
     type SocketOption = ebpf::SocketFilterFd;
+    type Set = ebpf::SetMapFd;
 }
 
 impl Backend for Extended {
@@ -23,6 +24,9 @@ impl Backend for Extended {
     fn return_sequence() -> (Vec<Self::Instruction>, usize, usize) {
         ebpf::return_sequence()
     }
+    fn return_value(value: u32) -> Vec<Self::Instruction> {
+        ebpf::return_value(value)
+    }
     fn teotology() -> Vec<Self::Instruction> {
         ebpf::teotology()
     }
@@ -34,11 +38,26 @@ impl Backend for Extended {
     fn into_socket_option(instructions: Vec<Self::Instruction>) -> Result<Self::SocketOption> {
         let len = instructions.len();
         if len > u16::max_value() as usize {
-            return Err(SystemError(EOVERFLOW));
+            return Err(SystemError::Compilation(format!(
+                "program of {} instructions is too long",
+                len
+            )));
         }
         Ok(ebpf::SocketFilterBpfAttribute::new(instructions).load()?)
     }
 
+    fn comparison(relation: Relation) -> Option<Self::Comparison> {
+        Some(match relation {
+            Relation::Equal => ebpf::Comparison::Equal,
+            Relation::NotEqual => ebpf::Comparison::NotEqual,
+            Relation::GreaterThan => ebpf::Comparison::GreaterThan,
+            Relation::GreaterEqual => ebpf::Comparison::GreaterEqual,
+            Relation::LesserThan => ebpf::Comparison::LesserThan,
+            Relation::LesserEqual => ebpf::Comparison::LesserEqual,
+            Relation::AndMask => ebpf::Comparison::AndMask,
+        })
+    }
+
     fn jump(
         comparison: Self::Comparison,
         operand: Self::Value,
@@ -59,4 +78,52 @@ impl Backend for Extended {
     fn load_u32_at(offset: u32) -> Vec<Self::Instruction> {
         ebpf::load_u32_at(offset as i32)
     }
+
+    fn load_u16_at_transport(network_offset: u32, offset: u32) -> Vec<Self::Instruction> {
+        ebpf::load_u16_at_transport(network_offset as i32, offset as i32)
+    }
+
+    fn load_ancillary(ancillary: Ancillary) -> Vec<Self::Instruction> {
+        match ancillary {
+            Ancillary::Protocol => {
+                // `__sk_buff.protocol` holds the EtherType in network byte order, while
+                // `SKF_AD_PROTOCOL` yields it in host byte order
+                let mut res = ebpf::network_to_host_u16();
+                res.extend(ebpf::load_context_u32(OFFSET_SK_BUFF_PROTOCOL));
+                res
+            }
+            Ancillary::PacketType => ebpf::load_context_u32(OFFSET_SK_BUFF_PKT_TYPE),
+            Ancillary::InterfaceIndex => ebpf::load_context_u32(OFFSET_SK_BUFF_IFINDEX),
+            Ancillary::Mark => ebpf::load_context_u32(OFFSET_SK_BUFF_MARK),
+            Ancillary::Queue => ebpf::load_context_u32(OFFSET_SK_BUFF_QUEUE_MAPPING),
+            Ancillary::RxHash => ebpf::load_context_u32(OFFSET_SK_BUFF_HASH),
+            Ancillary::Cpu => ebpf::call(BPF_FUNC_GET_SMP_PROCESSOR_ID),
+            Ancillary::Random => ebpf::call(BPF_FUNC_GET_PRANDOM_U32),
+        }
+    }
+
+    fn mask(mask: u32) -> Vec<Self::Instruction> {
+        ebpf::mask(mask)
+    }
+
+    fn jump_in(
+        values: &[u32],
+        jt: usize,
+        jf: usize,
+        sets: &mut Vec<Self::Set>,
+    ) -> Result<Vec<Self::Instruction>> {
+        if values.is_empty() {
+            // hash maps can't be empty, but nothing is a member of an empty set anyway
+            return Ok(ebpf::jump(
+                ebpf::Comparison::AndMask,
+                Self::Value::from(0),
+                jt,
+                jf,
+            ));
+        }
+        let set = ebpf::SetMapFd::new(values)?;
+        let res = ebpf::jump_in(&set, jt, jf);
+        sets.push(set);
+        Ok(res)
+    }
 }
@@ -1,8 +1,7 @@
 //! This module contains phantom structs that represent different implementations of BPF operations
 
-use crate::filter::AttachFilter;
+use bs_system::consts::*;
 use bs_system::Result;
-use cfg_if::cfg_if;
 use std::fmt::Debug;
 use std::hash::Hash;
 
@@ -16,21 +15,8 @@ mod extended;
 #[cfg(feature = "bs-ebpf")]
 pub use extended::Extended;
 
-cfg_if! {
-    if #[cfg(target_os = "linux")] {
-        use bs_system::SetSocketOption;
-        use std::os::unix::io::RawFd;
-
-        impl<T: SetSocketOption> AttachFilter for T {
-            fn attach(&self, socket: RawFd) -> Result<i32> {
-                self.set(socket)
-            }
-        }
-    }
-}
-
 mod private {
-    use super::AttachFilter;
+    use bs_system::SetSocketOption;
 
     pub trait FilterBackend {
         // TODO:
@@ -39,7 +25,75 @@ mod private {
         // change SocketOption to something more cross-compatible, e.g. Attachable.
         // Also, make the into_socket_option a generic `Filter` method (with a more suitable name)
         // and get rid of `Program` entirely.
-        type SocketOption: AttachFilter;
+        type SocketOption: SetSocketOption;
+
+        /// Kernel resources backing set membership checks, which must be kept alive until the
+        /// program referring to them is loaded
+        type Set: std::fmt::Debug;
+    }
+}
+
+/// Ancillary data about a packet which is not part of the packet's content, such as the interface
+/// it arrived on or its `SO_MARK`.
+///
+/// Classic BPF exposes these values via the negative offsets `SKF_AD_*` extensions, while Extended
+/// BPF reads them from the program's `__sk_buff` context or obtains them through kernel helpers.
+#[repr(i32)]
+#[derive(Copy, Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
+pub enum Ancillary {
+    /// The packet's EtherType protocol, in host byte order (`SKF_AD_PROTOCOL`)
+    Protocol = SKF_AD_PROTOCOL,
+    /// The packet's type, one of the `PACKET_*` constants of `packet(7)` (`SKF_AD_PKTTYPE`)
+    PacketType = SKF_AD_PKTTYPE,
+    /// The index of the interface the packet is going through (`SKF_AD_IFINDEX`)
+    InterfaceIndex = SKF_AD_IFINDEX,
+    /// The packet's mark, as set by `SO_MARK` or netfilter (`SKF_AD_MARK`)
+    Mark = SKF_AD_MARK,
+    /// The packet's queue mapping (`SKF_AD_QUEUE`)
+    Queue = SKF_AD_QUEUE,
+    /// The packet's flow hash (`SKF_AD_RXHASH`)
+    RxHash = SKF_AD_RXHASH,
+    /// The id of the CPU processing the packet (`SKF_AD_CPU`)
+    Cpu = SKF_AD_CPU,
+    /// A pseudo-random 32-bit number, generated anew for every packet (`SKF_AD_RANDOM`)
+    Random = SKF_AD_RANDOM,
+}
+
+/// A relation between a value loaded from the packet and an operand, checked by a conditional
+/// jump.
+///
+/// Backends don't necessarily support every `Relation` natively, see
+/// [`Backend::comparison`](trait.Backend.html#tymethod.comparison).
+#[derive(Copy, Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
+pub enum Relation {
+    /// value equals the operand
+    Equal,
+    /// value differs from the operand
+    NotEqual,
+    /// value is greater than the operand
+    GreaterThan,
+    /// value is greater than or equal to the operand
+    GreaterEqual,
+    /// value is lesser than the operand
+    LesserThan,
+    /// value is lesser than or equal to the operand
+    LesserEqual,
+    /// value shares at least one set bit with the operand
+    AndMask,
+}
+
+impl Relation {
+    /// The `Relation` that holds exactly when `self` doesn't, if any
+    pub fn negation(self) -> Option<Self> {
+        match self {
+            Relation::Equal => Some(Relation::NotEqual),
+            Relation::NotEqual => Some(Relation::Equal),
+            Relation::GreaterThan => Some(Relation::LesserEqual),
+            Relation::GreaterEqual => Some(Relation::LesserThan),
+            Relation::LesserThan => Some(Relation::GreaterEqual),
+            Relation::LesserEqual => Some(Relation::GreaterThan),
+            Relation::AndMask => None,
+        }
     }
 }
 
@@ -77,12 +131,23 @@ pub trait Backend: Sized + Clone + Ord + Debug + Hash + private::FilterBackend {
     /// to the DROP entry point.
     fn return_sequence() -> (Vec<Self::Instruction>, usize, usize);
 
+    /// Generates a sequence of instructions that returns `value`, for programs whose return value
+    /// isn't interpreted as a length, e.g. [`Steering`](../struct.Steering.html) programs.
+    fn return_value(value: u32) -> Vec<Self::Instruction>;
+
     /// Generates a sequence of instructions that passes the entire packet.
     fn teotology() -> Vec<Self::Instruction>;
 
     /// Generates a sequence of instructions that drops the packet.
     fn contradiction() -> Vec<Self::Instruction>;
 
+    /// The `Comparison` implementing `relation`, or `None` if the backend has no native support
+    /// for it.
+    ///
+    /// Every backend must support at least one of each `Relation` and its
+    /// [`negation`](enum.Relation.html#method.negation), and must support `Relation::AndMask`.
+    fn comparison(relation: Relation) -> Option<Self::Comparison>;
+
     /// Generates a sequence of instructions that implements a conditional jump.
     fn jump(
         comparison: Self::Comparison,
@@ -100,6 +165,28 @@ pub trait Backend: Sized + Clone + Ord + Debug + Hash + private::FilterBackend {
     /// Generates a sequence of instructions that loads four octets from a given offset in the packet.
     fn load_u32_at(offset: u32) -> Vec<Self::Instruction>;
 
+    /// Generates a sequence of instructions that loads two octets from offset `offset` in the
+    /// transport header following the IPv4 header found at `network_offset`.
+    fn load_u16_at_transport(network_offset: u32, offset: u32) -> Vec<Self::Instruction>;
+
+    /// Generates a sequence of instructions that loads a piece of ancillary data about the packet.
+    fn load_ancillary(ancillary: Ancillary) -> Vec<Self::Instruction>;
+
+    /// Generates a sequence of instructions that bitwise-ands the last loaded value with `mask`.
+    fn mask(mask: u32) -> Vec<Self::Instruction>;
+
+    /// Generates a sequence of instructions that jumps to `jt` if the last loaded value is one of
+    /// `values`, and to `jf` otherwise.
+    ///
+    /// `values` are sorted and deduplicated. Kernel resources the sequence refers to are pushed to
+    /// `sets`.
+    fn jump_in(
+        values: &[u32],
+        jt: usize,
+        jf: usize,
+        sets: &mut Vec<Self::Set>,
+    ) -> Result<Vec<Self::Instruction>>;
+
     #[doc(hidden)]
     fn into_socket_option(instructions: Vec<Self::Instruction>) -> Result<Self::SocketOption>;
 }
@@ -1,32 +1,49 @@
 use crate::backend::Backend;
+use crate::layer::Layer;
 use crate::program::Program;
 use bs_system::Result;
 use std::iter::FromIterator;
+use std::marker::PhantomData;
 use std::os::unix::io::RawFd;
 
-/// A concrete appicable socket filter
+/// A concrete appicable socket filter, for sockets whose packets start at `Layer` `L`
 #[derive(Debug)]
-pub struct Filter<K: Backend> {
+pub struct Filter<K: Backend, L: Layer> {
     inner: Vec<K::Instruction>,
+    sets: Vec<K::Set>,
+    layer: PhantomData<L>,
 }
 
-impl<K: Backend> Filter<K> {
-    /// Transform the `Filter` into a `SocketOption` settable on a `Socket`
-    pub fn build(self) -> Result<K::SocketOption> {
-        let prog: Program<K> = self.into();
-        prog.build()
+impl<K: Backend, L: Layer> Filter<K, L> {
+    pub(crate) fn new(inner: Vec<K::Instruction>, sets: Vec<K::Set>) -> Self {
+        Self {
+            inner,
+            sets,
+            layer: PhantomData,
+        }
+    }
+
+    /// Transform the `Filter` into an [`Attachable`](struct.Attachable.html) settable on a `Socket`
+    pub fn build(self) -> Result<Attachable<K, L>> {
+        let Self { inner, sets, .. } = self;
+        let prog = Program::<K>::new(inner);
+        let option = prog.build()?;
+        // the loaded program holds its own references to the sets from now on
+        drop(sets);
+        Ok(Attachable {
+            option,
+            layer: PhantomData,
+        })
     }
 }
 
-impl<K: Backend> FromIterator<K::Instruction> for Filter<K> {
+impl<K: Backend, L: Layer> FromIterator<K::Instruction> for Filter<K, L> {
     fn from_iter<I: IntoIterator<Item = K::Instruction>>(iter: I) -> Self {
-        Self {
-            inner: Vec::from_iter(iter),
-        }
+        Self::new(Vec::from_iter(iter), Vec::new())
     }
 }
 
-impl<K: Backend> IntoIterator for Filter<K> {
+impl<K: Backend, L: Layer> IntoIterator for Filter<K, L> {
     type Item = K::Instruction;
     type IntoIter = std::vec::IntoIter<Self::Item>;
 
@@ -35,13 +52,30 @@ impl<K: Backend> IntoIterator for Filter<K> {
     }
 }
 
-impl<K: Backend> Into<Program<K>> for Filter<K> {
+impl<K: Backend, L: Layer> Into<Program<K>> for Filter<K, L> {
     fn into(self) -> Program<K> {
         Program::from_iter(self.into_iter())
     }
 }
 
+/// A built [`Filter`](struct.Filter.html), ready to be attached to sockets whose packets start at
+/// `Layer` `L`
+#[derive(Debug)]
+pub struct Attachable<K: Backend, L: Layer> {
+    option: K::SocketOption,
+    layer: PhantomData<L>,
+}
+
 #[doc(hidden)]
-pub trait AttachFilter {
+pub trait AttachFilter<L: Layer> {
     fn attach(&self, socket: RawFd) -> Result<i32>;
 }
+
+#[cfg(target_os = "linux")]
+impl<K: Backend, L: Layer> AttachFilter<L> for Attachable<K, L> {
+    fn attach(&self, socket: RawFd) -> Result<i32> {
+        use bs_system::SetSocketOption;
+
+        self.option.set(socket)
+    }
+}
@@ -1,8 +1,7 @@
 use crate::backend::Backend;
-use crate::idiom::offset_equals_u16;
-use crate::predicate::{Expr::*, Predicate};
-use crate::Condition;
-use bs_system::consts::BPF_JEQ;
+use crate::idiom::{offset_equals_u16, offset_equals_u32};
+use crate::layer::Ethernet;
+use crate::predicate::Predicate;
 use bs_system::consts::{ETH_P_ARP, ETH_P_IP, ETH_P_IPV6};
 use bs_system::consts::{OFFSET_ETHER_DST, OFFSET_ETHER_SRC, OFFSET_ETHER_TYPE};
 use eui48::MacAddress;
@@ -20,54 +19,40 @@ fn mac_to_u32_and_u16(mac: MacAddress) -> (u32, u16) {
 }
 
 /// true iff packet's ethernet type is `ether_type`
-pub fn ether_type<K: Backend>(ether_type: u16) -> Predicate<K> {
+pub fn ether_type<K: Backend>(ether_type: u16) -> Predicate<K, Ethernet> {
     offset_equals_u16(OFFSET_ETHER_TYPE, ether_type)
 }
 
 /// true iff ethernet source is `mac`
-pub fn ether_src<K: Backend>(mac: MacAddress) -> Predicate<K> {
+pub fn ether_src<K: Backend>(mac: MacAddress) -> Predicate<K, Ethernet> {
     let (foursome, twosome) = mac_to_u32_and_u16(mac);
-    Predicate::from_inner(Terminal(Condition::new(
-        K::load_u32_at(OFFSET_ETHER_SRC),
-        K::Comparison::from(BPF_JEQ as u8),
-        K::Value::from(foursome),
-    ))) & Predicate::from_inner(Terminal(Condition::new(
-        K::load_u16_at(OFFSET_ETHER_SRC + size_of::<u32>() as u32),
-        K::Comparison::from(BPF_JEQ as u8),
-        K::Value::from(twosome as u32),
-    )))
+    offset_equals_u32(OFFSET_ETHER_SRC, foursome)
+        & offset_equals_u16(OFFSET_ETHER_SRC + size_of::<u32>() as u32, twosome)
 }
 
 /// true iff ethernet destination is `mac`
-pub fn ether_dst<K: Backend>(mac: MacAddress) -> Predicate<K> {
+pub fn ether_dst<K: Backend>(mac: MacAddress) -> Predicate<K, Ethernet> {
     let (foursome, twosome) = mac_to_u32_and_u16(mac);
-    Predicate::from_inner(Terminal(Condition::new(
-        K::load_u32_at(OFFSET_ETHER_DST),
-        K::Comparison::from(BPF_JEQ as u8),
-        K::Value::from(foursome),
-    ))) & Predicate::from_inner(Terminal(Condition::new(
-        K::load_u16_at(OFFSET_ETHER_DST + size_of::<u32>() as u32),
-        K::Comparison::from(BPF_JEQ as u8),
-        K::Value::from(twosome as u32),
-    )))
+    offset_equals_u32(OFFSET_ETHER_DST, foursome)
+        & offset_equals_u16(OFFSET_ETHER_DST + size_of::<u32>() as u32, twosome)
 }
 
 /// true iff `mac` is either the ethernet source or destination of the packet
-pub fn ether_host<K: Backend>(mac: MacAddress) -> Predicate<K> {
+pub fn ether_host<K: Backend>(mac: MacAddress) -> Predicate<K, Ethernet> {
     ether_dst(mac) | ether_src(mac)
 }
 
 /// accepts only ARP packets
-pub fn ether_type_arp<K: Backend>() -> Predicate<K> {
+pub fn ether_type_arp<K: Backend>() -> Predicate<K, Ethernet> {
     ether_type(ETH_P_ARP as u16)
 }
 
 /// accepts only IPv4 packets
-pub fn ether_type_ip4<K: Backend>() -> Predicate<K> {
+pub fn ether_type_ip4<K: Backend>() -> Predicate<K, Ethernet> {
     ether_type(ETH_P_IP as u16)
 }
 
 /// accepts only IPv6 packets
-pub fn ether_type_ip6<K: Backend>() -> Predicate<K> {
+pub fn ether_type_ip6<K: Backend>() -> Predicate<K, Ethernet> {
     ether_type(ETH_P_IPV6 as u16)
 }
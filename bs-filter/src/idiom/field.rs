@@ -0,0 +1,168 @@
+use crate::backend::{Ancillary, Backend, Relation};
+use crate::layer::Layer;
+use crate::predicate::Predicate;
+use crate::{Computation, Condition};
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+
+/// A value found in the packet, or about it, that `Predicate`s can be generated upon
+///
+/// # Example
+/// ```
+/// # use bs_filter::backend::Classic;
+/// # use bs_filter::idiom::field::{field_u8, field_u16};
+/// # use bs_filter::layer::Network;
+/// type Predicate = bs_filter::Predicate<Classic, Network>;
+///
+/// // IPv4 TTL of at most 64
+/// let low_ttl: Predicate = field_u8(8).lesser_equal(64);
+///
+/// // IPv4 "Don't Fragment" flag is set
+/// let dont_fragment: Predicate = field_u16(6).mask(0x4000).equals(0x4000);
+///
+/// // IPv4 protocol is either TCP or UDP
+/// let tcp_or_udp: Predicate = field_u8(9).any_of(&[6, 17]);
+///
+/// # assert!((low_ttl & dont_fragment & tcp_or_udp).satisfiable());
+/// ```
+#[derive(Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
+pub struct Field<K: Backend, L: Layer> {
+    computation: Computation<K>,
+    layer: PhantomData<L>,
+}
+
+/// The octet at offset `offset`
+pub fn field_u8<K: Backend, L: Layer>(offset: u32) -> Field<K, L> {
+    Field::from_computation(Computation::load_u8_at(offset))
+}
+
+/// The octet at offset `offset + shift`
+pub fn shift_field_u8<K: Backend, L: Layer>(offset: u32, shift: u32) -> Field<K, L> {
+    field_u8(offset.wrapping_add(shift))
+}
+
+/// The u16 at offset `offset`
+pub fn field_u16<K: Backend, L: Layer>(offset: u32) -> Field<K, L> {
+    Field::from_computation(Computation::load_u16_at(offset))
+}
+
+/// The u16 at offset `offset + shift`
+pub fn shift_field_u16<K: Backend, L: Layer>(offset: u32, shift: u32) -> Field<K, L> {
+    field_u16(offset.wrapping_add(shift))
+}
+
+/// The u32 at offset `offset`
+pub fn field_u32<K: Backend, L: Layer>(offset: u32) -> Field<K, L> {
+    Field::from_computation(Computation::load_u32_at(offset))
+}
+
+/// The u32 at offset `offset + shift`
+pub fn shift_field_u32<K: Backend, L: Layer>(offset: u32, shift: u32) -> Field<K, L> {
+    field_u32(offset.wrapping_add(shift))
+}
+
+/// The ancillary data `ancillary`
+pub fn ancillary<K: Backend, L: Layer>(ancillary: Ancillary) -> Field<K, L> {
+    Field::from_computation(Computation::load_ancillary(ancillary))
+}
+
+impl<K: Backend, L: Layer> Field<K, L> {
+    /// A `Field` holding the result of `computation`
+    pub fn from_computation(computation: Computation<K>) -> Self {
+        Self {
+            computation,
+            layer: PhantomData,
+        }
+    }
+
+    /// The field bitwise-anded with `mask`
+    pub fn mask(self, mask: u32) -> Self {
+        Self::from_computation(self.computation.mask(mask))
+    }
+
+    /// true iff `relation` holds between the field and `value`
+    pub fn relation(self, relation: Relation, value: u32) -> Predicate<K, L> {
+        Condition::new(self.computation, relation, value).into()
+    }
+
+    /// true iff the field equals `value`
+    pub fn equals(self, value: u32) -> Predicate<K, L> {
+        self.relation(Relation::Equal, value)
+    }
+
+    /// true iff the field differs from `value`
+    pub fn not_equals(self, value: u32) -> Predicate<K, L> {
+        self.relation(Relation::NotEqual, value)
+    }
+
+    /// true iff the field is greater than `value`
+    pub fn greater_than(self, value: u32) -> Predicate<K, L> {
+        self.relation(Relation::GreaterThan, value)
+    }
+
+    /// true iff the field is greater than or equal to `value`
+    pub fn greater_equal(self, value: u32) -> Predicate<K, L> {
+        self.relation(Relation::GreaterEqual, value)
+    }
+
+    /// true iff the field is lesser than `value`
+    pub fn lesser_than(self, value: u32) -> Predicate<K, L> {
+        self.relation(Relation::LesserThan, value)
+    }
+
+    /// true iff the field is lesser than or equal to `value`
+    pub fn lesser_equal(self, value: u32) -> Predicate<K, L> {
+        self.relation(Relation::LesserEqual, value)
+    }
+
+    /// true iff the field shares at least one set bit with `mask`
+    pub fn any_bit(self, mask: u32) -> Predicate<K, L> {
+        self.relation(Relation::AndMask, mask)
+    }
+
+    /// true iff the field falls within `range`
+    pub fn in_range(self, range: impl RangeBounds<u32>) -> Predicate<K, L> {
+        let lower = match range.start_bound() {
+            Bound::Included(&start) => Some(self.clone().greater_equal(start)),
+            Bound::Excluded(&start) => Some(self.clone().greater_than(start)),
+            Bound::Unbounded => None,
+        };
+        let upper = match range.end_bound() {
+            Bound::Included(&end) => Some(self.lesser_equal(end)),
+            Bound::Excluded(&end) => Some(self.lesser_than(end)),
+            Bound::Unbounded => None,
+        };
+        match (lower, upper) {
+            (Some(lower), Some(upper)) => lower & upper,
+            (Some(bound), None) | (None, Some(bound)) => bound,
+            (None, None) => Predicate::const_true(),
+        }
+    }
+
+    /// true iff the field equals any of `values`
+    ///
+    /// the field is loaded once and then looked up in `values`, see
+    /// [`Condition::any_of`](../../struct.Condition.html#method.any_of)
+    pub fn any_of(self, values: &[u32]) -> Predicate<K, L> {
+        Condition::any_of(self.computation, values).into()
+    }
+}
+
+/// true iff `field` equals any of `values`
+///
+/// # Example
+/// ```
+/// # use bs_filter::backend::Classic;
+/// # use bs_filter::idiom::field::{field_in, field_u16};
+/// # use bs_filter::layer::Transport;
+/// let ports: Vec<u16> = (8000..8500).collect();
+/// let p = field_in(field_u16::<Classic, Transport>(2), &ports);
+/// # assert!(p.compile().is_ok());
+/// ```
+pub fn field_in<K: Backend, L: Layer, T: Copy + Into<u32>>(
+    field: Field<K, L>,
+    values: &[T],
+) -> Predicate<K, L> {
+    let values: Vec<u32> = values.iter().map(|&value| value.into()).collect();
+    field.any_of(&values)
+}
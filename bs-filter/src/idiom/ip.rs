@@ -1,9 +1,10 @@
 use crate::backend::Backend;
-use crate::idiom::ethernet::{ether_type_ip4, ether_type_ip6};
+use crate::idiom::field::{field_in, shift_field_u32};
 use crate::idiom::shift_offset_equals_u32;
 use crate::idiom::shift_offset_equals_u8;
+use crate::layer::{IpLayer, Layer};
 use crate::predicate::Predicate;
-use bs_system::consts::SIZE_ETHER_HEADER;
+use bs_system::consts::{ETH_P_IP, ETH_P_IPV6};
 use bs_system::consts::{OFFSET_IP4_DST, OFFSET_IP4_PROTO, OFFSET_IP4_SRC, OFFSET_IP4_TTL};
 use bs_system::consts::{
     OFFSET_IP6_DST, OFFSET_IP6_HOP_LIMIT, OFFSET_IP6_NEXT_HEADER, OFFSET_IP6_SRC,
@@ -11,73 +12,96 @@ use bs_system::consts::{
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 /// true iff packet's IP TTL field is `ttl`, assuming IP layer starts at offset `shift`
-pub fn shift_ip4_ttl<K: Backend>(ttl: u8, shift: u32) -> Predicate<K> {
+pub fn shift_ip4_ttl<K: Backend, L: Layer>(ttl: u8, shift: u32) -> Predicate<K, L> {
     shift_offset_equals_u8(OFFSET_IP4_TTL, ttl, shift)
 }
 
 /// true iff packet's IP TTL field is `ttl`
-pub fn ip4_ttl<K: Backend>(ttl: u8) -> Predicate<K> {
-    shift_ip4_ttl(ttl, SIZE_ETHER_HEADER)
+pub fn ip4_ttl<K: Backend, L: IpLayer>(ttl: u8) -> Predicate<K, L> {
+    shift_ip4_ttl(ttl, L::network_offset())
 }
 
 /// true iff packet's IP protocol field is `proto`, assuming IP layer starts at offset `shift`
-pub fn shift_ip4_proto<K: Backend>(proto: u8, shift: u32) -> Predicate<K> {
+pub fn shift_ip4_proto<K: Backend, L: Layer>(proto: u8, shift: u32) -> Predicate<K, L> {
     shift_offset_equals_u8(OFFSET_IP4_PROTO, proto, shift)
 }
 
 /// true iff packet's IP protocol field is `proto`
-pub fn ip4_proto<K: Backend>(proto: u8) -> Predicate<K> {
-    shift_ip4_proto(proto, SIZE_ETHER_HEADER)
+pub fn ip4_proto<K: Backend, L: IpLayer>(proto: u8) -> Predicate<K, L> {
+    shift_ip4_proto(proto, L::network_offset())
 }
 
 /// true iff IP source is `ip`, assuming IP layer starts at offset `shift`
-pub fn shift_ip4_src<K: Backend>(ip: Ipv4Addr, shift: u32) -> Predicate<K> {
+pub fn shift_ip4_src<K: Backend, L: Layer>(ip: Ipv4Addr, shift: u32) -> Predicate<K, L> {
     shift_offset_equals_u32(OFFSET_IP4_SRC, ip.into(), shift)
 }
 
 /// true iff IP source is `ip`
-pub fn ip4_src<K: Backend>(ip: Ipv4Addr) -> Predicate<K> {
-    ether_type_ip4() & shift_ip4_src(ip, SIZE_ETHER_HEADER)
+pub fn ip4_src<K: Backend, L: IpLayer>(ip: Ipv4Addr) -> Predicate<K, L> {
+    L::ether_type(ETH_P_IP as u16) & shift_ip4_src(ip, L::network_offset())
 }
 
 /// true iff IP destination is `ip`, assuming IP layer starts at offset `shift`
-pub fn shift_ip4_dst<K: Backend>(ip: Ipv4Addr, shift: u32) -> Predicate<K> {
+pub fn shift_ip4_dst<K: Backend, L: Layer>(ip: Ipv4Addr, shift: u32) -> Predicate<K, L> {
     shift_offset_equals_u32(OFFSET_IP4_DST, ip.into(), shift)
 }
 
 /// true iff IP destination is `ip`
-pub fn ip4_dst<K: Backend>(ip: Ipv4Addr) -> Predicate<K> {
-    ether_type_ip4() & shift_ip4_dst(ip, SIZE_ETHER_HEADER)
+pub fn ip4_dst<K: Backend, L: IpLayer>(ip: Ipv4Addr) -> Predicate<K, L> {
+    L::ether_type(ETH_P_IP as u16) & shift_ip4_dst(ip, L::network_offset())
 }
 
 /// true iff either IP destination or source is `ip`, assuming IP layer starts at offset `shift`
-pub fn shift_ip4_host<K: Backend>(ip: Ipv4Addr, shift: u32) -> Predicate<K> {
+pub fn shift_ip4_host<K: Backend, L: Layer>(ip: Ipv4Addr, shift: u32) -> Predicate<K, L> {
     shift_ip4_src(ip, shift) | shift_ip4_dst(ip, shift)
 }
 
 /// true iff `ip` is either IP source or destination
-pub fn ip4_host<K: Backend>(ip: Ipv4Addr) -> Predicate<K> {
-    shift_ip4_host(ip, SIZE_ETHER_HEADER)
+pub fn ip4_host<K: Backend, L: IpLayer>(ip: Ipv4Addr) -> Predicate<K, L> {
+    shift_ip4_host(ip, L::network_offset())
+}
+
+fn ip4_in<K: Backend, L: IpLayer>(offset: u32, ips: &[Ipv4Addr]) -> Predicate<K, L> {
+    let ips: Vec<u32> = ips.iter().map(|&ip| ip.into()).collect();
+    L::ether_type(ETH_P_IP as u16) & field_in(shift_field_u32(offset, L::network_offset()), &ips)
+}
+
+/// true iff IP source is any of `ips`
+///
+/// the source address is loaded once and looked up in `ips`, so this scales to large lists of
+/// addresses, unlike a disjunction of [`ip4_src`](fn.ip4_src.html)s
+pub fn ip4_src_in<K: Backend, L: IpLayer>(ips: &[Ipv4Addr]) -> Predicate<K, L> {
+    ip4_in(OFFSET_IP4_SRC, ips)
+}
+
+/// true iff IP destination is any of `ips`
+pub fn ip4_dst_in<K: Backend, L: IpLayer>(ips: &[Ipv4Addr]) -> Predicate<K, L> {
+    ip4_in(OFFSET_IP4_DST, ips)
+}
+
+/// true iff either IP source or destination is any of `ips`
+pub fn ip4_host_in<K: Backend, L: IpLayer>(ips: &[Ipv4Addr]) -> Predicate<K, L> {
+    ip4_src_in(ips) | ip4_dst_in(ips)
 }
 
 /// true iff packet's IPv6 Hop Limit field is `ttl`, assuming IPv6 layer starts at offset `shift`
-pub fn shift_ip6_hop_limit<K: Backend>(ttl: u8, shift: u32) -> Predicate<K> {
+pub fn shift_ip6_hop_limit<K: Backend, L: Layer>(ttl: u8, shift: u32) -> Predicate<K, L> {
     shift_offset_equals_u8(OFFSET_IP6_HOP_LIMIT, ttl, shift)
 }
 
 /// true iff packet's IPv6 Hop Limit field is `ttl`
-pub fn ip6_hop_limit<K: Backend>(ttl: u8) -> Predicate<K> {
-    shift_ip6_hop_limit(ttl, SIZE_ETHER_HEADER)
+pub fn ip6_hop_limit<K: Backend, L: IpLayer>(ttl: u8) -> Predicate<K, L> {
+    shift_ip6_hop_limit(ttl, L::network_offset())
 }
 
 /// true iff packet's IPv6 Next Header field is `proto`, assuming IPv6 layer starts at offset `shift`
-pub fn shift_ip6_next_header<K: Backend>(proto: u8, shift: u32) -> Predicate<K> {
+pub fn shift_ip6_next_header<K: Backend, L: Layer>(proto: u8, shift: u32) -> Predicate<K, L> {
     shift_offset_equals_u8(OFFSET_IP6_NEXT_HEADER, proto, shift)
 }
 
 /// true iff packet's IPv6 protocol field is `proto`
-pub fn ip6_next_header<K: Backend>(proto: u8) -> Predicate<K> {
-    shift_ip6_next_header(proto, SIZE_ETHER_HEADER)
+pub fn ip6_next_header<K: Backend, L: IpLayer>(proto: u8) -> Predicate<K, L> {
+    shift_ip6_next_header(proto, L::network_offset())
 }
 
 use byteorder::{BigEndian, ReadBytesExt};
@@ -95,7 +119,7 @@ fn ip6_address_to_u32_array(ip: Ipv6Addr) -> [u32; 4] {
 }
 
 /// true iff IP source is `ip`, assuming IP layer starts at offset `shift`
-pub fn shift_ip6_src<K: Backend>(ip: Ipv6Addr, shift: u32) -> Predicate<K> {
+pub fn shift_ip6_src<K: Backend, L: Layer>(ip: Ipv6Addr, shift: u32) -> Predicate<K, L> {
     let words = ip6_address_to_u32_array(ip);
 
     shift_offset_equals_u32(OFFSET_IP6_SRC, words[0], shift)
@@ -113,12 +137,12 @@ pub fn shift_ip6_src<K: Backend>(ip: Ipv6Addr, shift: u32) -> Predicate<K> {
 }
 
 /// true iff IP source is `ip`
-pub fn ip6_src<K: Backend>(ip: Ipv6Addr) -> Predicate<K> {
-    ether_type_ip6() & shift_ip6_src(ip, SIZE_ETHER_HEADER)
+pub fn ip6_src<K: Backend, L: IpLayer>(ip: Ipv6Addr) -> Predicate<K, L> {
+    L::ether_type(ETH_P_IPV6 as u16) & shift_ip6_src(ip, L::network_offset())
 }
 
 /// true iff IP destination is `ip`, assuming IP layer starts at offset `shift`
-pub fn shift_ip6_dst<K: Backend>(ip: Ipv6Addr, shift: u32) -> Predicate<K> {
+pub fn shift_ip6_dst<K: Backend, L: Layer>(ip: Ipv6Addr, shift: u32) -> Predicate<K, L> {
     let words = ip6_address_to_u32_array(ip);
 
     shift_offset_equals_u32(OFFSET_IP6_DST, words[0], shift)
@@ -136,43 +160,43 @@ pub fn shift_ip6_dst<K: Backend>(ip: Ipv6Addr, shift: u32) -> Predicate<K> {
 }
 
 /// true iff IP destination is `ip`
-pub fn ip6_dst<K: Backend>(ip: Ipv6Addr) -> Predicate<K> {
-    ether_type_ip6() & shift_ip6_dst(ip, SIZE_ETHER_HEADER)
+pub fn ip6_dst<K: Backend, L: IpLayer>(ip: Ipv6Addr) -> Predicate<K, L> {
+    L::ether_type(ETH_P_IPV6 as u16) & shift_ip6_dst(ip, L::network_offset())
 }
 
 /// true iff either IP destination or source is `ip`, assuming IP layer starts at offset `shift`
-pub fn shift_ip6_host<K: Backend>(ip: Ipv6Addr, shift: u32) -> Predicate<K> {
+pub fn shift_ip6_host<K: Backend, L: Layer>(ip: Ipv6Addr, shift: u32) -> Predicate<K, L> {
     shift_ip6_src(ip, shift) | shift_ip6_dst(ip, shift)
 }
 
 /// true iff `ip` is either IP source or destination
-pub fn ip6_host<K: Backend>(ip: Ipv6Addr) -> Predicate<K> {
-    shift_ip6_host(ip, SIZE_ETHER_HEADER)
+pub fn ip6_host<K: Backend, L: IpLayer>(ip: Ipv6Addr) -> Predicate<K, L> {
+    shift_ip6_host(ip, L::network_offset())
 }
 
 /// true iff packet's Hop Limit field (TTL for IPv4) is `ttl`, assuming IP layer starts at offset `shift`
-pub fn shift_ip_hop_limit<K: Backend>(ttl: u8, shift: u32) -> Predicate<K> {
+pub fn shift_ip_hop_limit<K: Backend, L: Layer>(ttl: u8, shift: u32) -> Predicate<K, L> {
     shift_ip4_ttl(ttl, shift) | shift_ip6_hop_limit(ttl, shift)
 }
 
 /// true iff packet's Hop Limit is `ttl`
-pub fn ip_hop_limit<K: Backend>(ttl: u8) -> Predicate<K> {
-    shift_ip_hop_limit(ttl, SIZE_ETHER_HEADER)
+pub fn ip_hop_limit<K: Backend, L: IpLayer>(ttl: u8) -> Predicate<K, L> {
+    shift_ip_hop_limit(ttl, L::network_offset())
 }
 
 /// true iff packet's the next layer after IP is identified by IP protocol `proto`, assuming IP
 /// layer starts at offset `shift`
-pub fn shift_ip_next_header<K: Backend>(proto: u8, shift: u32) -> Predicate<K> {
+pub fn shift_ip_next_header<K: Backend, L: Layer>(proto: u8, shift: u32) -> Predicate<K, L> {
     shift_ip4_proto(proto, shift) | shift_ip6_next_header(proto, shift)
 }
 
 /// true iff packet's the next layer after IP is identified by IP protocol `proto`
-pub fn ip_next_header<K: Backend>(proto: u8) -> Predicate<K> {
-    shift_ip_next_header(proto, SIZE_ETHER_HEADER)
+pub fn ip_next_header<K: Backend, L: IpLayer>(proto: u8) -> Predicate<K, L> {
+    shift_ip_next_header(proto, L::network_offset())
 }
 
 /// true iff IP source is `ip`, assuming IP layer starts at offset `shift`
-pub fn shift_ip_src<K: Backend>(ip: IpAddr, shift: u32) -> Predicate<K> {
+pub fn shift_ip_src<K: Backend, L: Layer>(ip: IpAddr, shift: u32) -> Predicate<K, L> {
     match ip {
         IpAddr::V4(ip4) => shift_ip4_src(ip4, shift),
         IpAddr::V6(ip6) => shift_ip6_src(ip6, shift),
@@ -180,12 +204,12 @@ pub fn shift_ip_src<K: Backend>(ip: IpAddr, shift: u32) -> Predicate<K> {
 }
 
 /// true iff IP source is `ip`
-pub fn ip_src<K: Backend>(ip: IpAddr) -> Predicate<K> {
-    shift_ip_src(ip, SIZE_ETHER_HEADER)
+pub fn ip_src<K: Backend, L: IpLayer>(ip: IpAddr) -> Predicate<K, L> {
+    shift_ip_src(ip, L::network_offset())
 }
 
 /// true iff IP destination is `ip`, assuming IP layer starts at offset `shift`
-pub fn shift_ip_dst<K: Backend>(ip: IpAddr, shift: u32) -> Predicate<K> {
+pub fn shift_ip_dst<K: Backend, L: Layer>(ip: IpAddr, shift: u32) -> Predicate<K, L> {
     match ip {
         IpAddr::V4(ip4) => shift_ip4_dst(ip4, shift),
         IpAddr::V6(ip6) => shift_ip6_dst(ip6, shift),
@@ -193,16 +217,16 @@ pub fn shift_ip_dst<K: Backend>(ip: IpAddr, shift: u32) -> Predicate<K> {
 }
 
 /// true iff IP destination is `ip`
-pub fn ip_dst<K: Backend>(ip: IpAddr) -> Predicate<K> {
-    shift_ip_dst(ip, SIZE_ETHER_HEADER)
+pub fn ip_dst<K: Backend, L: IpLayer>(ip: IpAddr) -> Predicate<K, L> {
+    shift_ip_dst(ip, L::network_offset())
 }
 
 /// true iff either IP destination or source is `ip`, assuming IP layer starts at offset `shift`
-pub fn shift_ip_host<K: Backend>(ip: IpAddr, shift: u32) -> Predicate<K> {
+pub fn shift_ip_host<K: Backend, L: Layer>(ip: IpAddr, shift: u32) -> Predicate<K, L> {
     shift_ip_src(ip, shift) | shift_ip_dst(ip, shift)
 }
 
 /// true iff `ip` is either IP source or destination
-pub fn ip_host<K: Backend>(ip: IpAddr) -> Predicate<K> {
-    shift_ip_host(ip, SIZE_ETHER_HEADER)
+pub fn ip_host<K: Backend, L: IpLayer>(ip: IpAddr) -> Predicate<K, L> {
+    shift_ip_host(ip, L::network_offset())
 }
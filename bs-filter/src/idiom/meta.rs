@@ -0,0 +1,99 @@
+use crate::backend::{Ancillary, Backend};
+use crate::idiom::field::ancillary;
+use crate::layer::Layer;
+use crate::predicate::Predicate;
+use bs_system::consts::{
+    PACKET_BROADCAST, PACKET_HOST, PACKET_MULTICAST, PACKET_OTHERHOST, PACKET_OUTGOING,
+};
+
+/// true iff the ancillary data `ancillary` equals `value`
+pub fn ancillary_equals<K: Backend, L: Layer>(data: Ancillary, value: u32) -> Predicate<K, L> {
+    ancillary(data).equals(value)
+}
+
+/// true iff the packet's EtherType protocol, as determined by the kernel, is `ether_type`
+///
+/// unlike [`ether_type`](../ethernet/fn.ether_type.html), this doesn't depend on the packet
+/// actually containing an ethernet header
+pub fn protocol<K: Backend, L: Layer>(ether_type: u16) -> Predicate<K, L> {
+    ancillary_equals(Ancillary::Protocol, ether_type as u32)
+}
+
+/// true iff the packet is going through the interface with index `ifindex`
+pub fn ifindex<K: Backend, L: Layer>(ifindex: u32) -> Predicate<K, L> {
+    ancillary_equals(Ancillary::InterfaceIndex, ifindex)
+}
+
+/// true iff the packet's mark is `mark`
+pub fn mark<K: Backend, L: Layer>(mark: u32) -> Predicate<K, L> {
+    ancillary_equals(Ancillary::Mark, mark)
+}
+
+/// true iff the packet's queue mapping is `queue`
+pub fn queue<K: Backend, L: Layer>(queue: u32) -> Predicate<K, L> {
+    ancillary_equals(Ancillary::Queue, queue)
+}
+
+/// true iff the packet's flow hash is `hash`
+pub fn rxhash<K: Backend, L: Layer>(hash: u32) -> Predicate<K, L> {
+    ancillary_equals(Ancillary::RxHash, hash)
+}
+
+/// true iff the packet is being processed by CPU number `cpu`
+pub fn cpu<K: Backend, L: Layer>(cpu: u32) -> Predicate<K, L> {
+    ancillary_equals(Ancillary::Cpu, cpu)
+}
+
+/// true iff the packet's type is `pkt_type`, see the `PACKET_*` constants in `packet(7)`
+pub fn pkt_type<K: Backend, L: Layer>(pkt_type: u8) -> Predicate<K, L> {
+    ancillary_equals(Ancillary::PacketType, pkt_type as u32)
+}
+
+/// accepts only packets addressed to the local host
+pub fn pkt_type_host<K: Backend, L: Layer>() -> Predicate<K, L> {
+    pkt_type(PACKET_HOST)
+}
+
+/// accepts only link layer broadcast packets
+pub fn pkt_type_broadcast<K: Backend, L: Layer>() -> Predicate<K, L> {
+    pkt_type(PACKET_BROADCAST)
+}
+
+/// accepts only link layer multicast packets
+pub fn pkt_type_multicast<K: Backend, L: Layer>() -> Predicate<K, L> {
+    pkt_type(PACKET_MULTICAST)
+}
+
+/// accepts only packets addressed to other hosts, captured in promiscuous mode
+pub fn pkt_type_otherhost<K: Backend, L: Layer>() -> Predicate<K, L> {
+    pkt_type(PACKET_OTHERHOST)
+}
+
+/// accepts only packets sent by the local host
+pub fn pkt_type_outgoing<K: Backend, L: Layer>() -> Predicate<K, L> {
+    pkt_type(PACKET_OUTGOING)
+}
+
+/// randomly accepts `n` out of every `of` packets on average
+///
+/// # Example
+/// ```
+/// # use bs_filter::backend::Classic;
+/// # use bs_filter::idiom::meta::sample;
+/// # use bs_filter::layer::Transport;
+/// // roughly one percent of the traffic
+/// let one_percent = sample::<Classic, Transport>(1, 100);
+/// # assert!(one_percent.satisfiable());
+/// ```
+pub fn sample<K: Backend, L: Layer>(n: u32, of: u32) -> Predicate<K, L> {
+    if n >= of {
+        return Predicate::const_true();
+    }
+    if n == 0 {
+        return Predicate::const_false();
+    }
+
+    // accept iff the packet's random number falls in the lowest `n / of` of the u32 range
+    let threshold = ((n as u64) << 32) / of as u64;
+    ancillary(Ancillary::Random).lesser_than(threshold as u32)
+}
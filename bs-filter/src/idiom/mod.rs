@@ -1,65 +1,69 @@
 use crate::backend::Backend;
-use crate::predicate::{Expr::*, Predicate};
-use crate::Condition;
-use bs_system::consts::BPF_JEQ;
+use crate::layer::Layer;
+use crate::predicate::Predicate;
 
 /// true iff the octet at offset `offset` equals `value`
-pub fn offset_equals_u8<K: Backend>(offset: u32, value: u8) -> Predicate<K> {
-    Predicate::from_inner(Terminal(Condition::new(
-        K::load_u8_at(offset),
-        K::Comparison::from(BPF_JEQ as u8),
-        K::Value::from(value as u32),
-    )))
+pub fn offset_equals_u8<K: Backend, L: Layer>(offset: u32, value: u8) -> Predicate<K, L> {
+    field::field_u8(offset).equals(value as u32)
 }
 
 /// true iff the octet at offset `offset + shift` equals `value`
-// TODO - should `shift` be i32?
-pub fn shift_offset_equals_u8<K: Backend>(offset: u32, value: u8, shift: u32) -> Predicate<K> {
-    Predicate::from_inner(Terminal(Condition::new(
-        K::load_u8_at(offset + shift),
-        K::Comparison::from(BPF_JEQ as u8),
-        K::Value::from(value as u32),
-    )))
+///
+/// the addition wraps around, so `shift` may also point into one of the kernel's negative
+/// `SKF_*_OFF` areas
+pub fn shift_offset_equals_u8<K: Backend, L: Layer>(
+    offset: u32,
+    value: u8,
+    shift: u32,
+) -> Predicate<K, L> {
+    field::shift_field_u8(offset, shift).equals(value as u32)
 }
 
 /// true iff the u16 at offset `offset` equals `value`
-pub fn offset_equals_u16<K: Backend>(offset: u32, value: u16) -> Predicate<K> {
-    Predicate::from_inner(Terminal(Condition::new(
-        K::load_u16_at(offset),
-        K::Comparison::from(BPF_JEQ as u8),
-        K::Value::from(value as u32),
-    )))
+pub fn offset_equals_u16<K: Backend, L: Layer>(offset: u32, value: u16) -> Predicate<K, L> {
+    field::field_u16(offset).equals(value as u32)
 }
 
 /// true iff the u16 at offset `offset + shift` equals `value`
-pub fn shift_offset_equals_u16<K: Backend>(offset: u32, value: u16, shift: u32) -> Predicate<K> {
-    Predicate::from_inner(Terminal(Condition::new(
-        K::load_u16_at(offset + shift),
-        K::Comparison::from(BPF_JEQ as u8),
-        K::Value::from(value as u32),
-    )))
+pub fn shift_offset_equals_u16<K: Backend, L: Layer>(
+    offset: u32,
+    value: u16,
+    shift: u32,
+) -> Predicate<K, L> {
+    field::shift_field_u16(offset, shift).equals(value as u32)
 }
 
 /// true iff the u32 at offset `offset` equals `value`
-pub fn offset_equals_u32<K: Backend>(offset: u32, value: u32) -> Predicate<K> {
-    Predicate::from_inner(Terminal(Condition::new(
-        K::load_u32_at(offset),
-        K::Comparison::from(BPF_JEQ as u8),
-        K::Value::from(value),
-    )))
+pub fn offset_equals_u32<K: Backend, L: Layer>(offset: u32, value: u32) -> Predicate<K, L> {
+    field::field_u32(offset).equals(value)
 }
 
 /// true iff the u32 at offset `offset + shift` equals `value`
-pub fn shift_offset_equals_u32<K: Backend>(offset: u32, value: u32, shift: u32) -> Predicate<K> {
-    Predicate::from_inner(Terminal(Condition::new(
-        K::load_u32_at(offset + shift),
-        K::Comparison::from(BPF_JEQ as u8),
-        K::Value::from(value),
-    )))
+pub fn shift_offset_equals_u32<K: Backend, L: Layer>(
+    offset: u32,
+    value: u32,
+    shift: u32,
+) -> Predicate<K, L> {
+    field::shift_field_u32(offset, shift).equals(value)
 }
 
+/// Generic predicates over fields of the packet, e.g. comparisons, ranges and bitmasks
+pub mod field;
+
 /// Ethernet layer filtering idioms
 pub mod ethernet;
 
 /// IP layer filtering idioms
+///
+/// the location of the IP header is determined by the [`IpLayer`](../layer/trait.IpLayer.html) the
+/// idioms are generated for, the `shift_*` variants allow specifying it explicitly instead
 pub mod ip;
+
+/// Transport layer filtering idioms, e.g. matching TCP, UDP and SCTP ports
+pub mod transport;
+
+/// Filtering idioms based on ancillary data about packets, e.g. their incoming interface or mark
+pub mod meta;
+
+/// Netlink message filtering idioms
+pub mod netlink;
@@ -0,0 +1,11 @@
+use crate::backend::Backend;
+use crate::idiom::offset_equals_u16;
+use crate::layer::Netlink;
+use crate::predicate::Predicate;
+use bs_system::consts::OFFSET_NLMSG_TYPE;
+
+/// true iff the message's type (`nlmsg_type`) is `type_`, e.g. `RTM_NEWLINK`
+pub fn message_type<K: Backend>(type_: u16) -> Predicate<K, Netlink> {
+    // filters load fields in network byte order, while netlink uses the host's
+    offset_equals_u16(OFFSET_NLMSG_TYPE, u16::from_be_bytes(type_.to_ne_bytes()))
+}
@@ -0,0 +1,55 @@
+use crate::backend::Backend;
+use crate::idiom::field::{field_in, shift_field_u16, shift_field_u8, Field};
+use crate::layer::IpLayer;
+use crate::predicate::Predicate;
+use bs_system::consts::ETH_P_IP;
+use bs_system::consts::{IPPROTO_SCTP, IPPROTO_TCP, IPPROTO_UDP};
+use bs_system::consts::{OFFSET_IP4_FRAGMENT, OFFSET_IP4_PROTO};
+use bs_system::consts::{OFFSET_TRANSPORT_DST_PORT, OFFSET_TRANSPORT_SRC_PORT};
+
+const IP4_FRAGMENT_OFFSET_MASK: u32 = 0x1fff;
+
+/// true iff the packet is the first (or only) fragment of an IPv4 packet carrying a protocol with
+/// ports, i.e. TCP, UDP or SCTP
+fn ip4_ported<K: Backend, L: IpLayer>() -> Predicate<K, L> {
+    L::ether_type(ETH_P_IP as u16)
+        & shift_field_u8(OFFSET_IP4_PROTO, L::network_offset()).any_of(&[
+            IPPROTO_TCP as u32,
+            IPPROTO_UDP as u32,
+            IPPROTO_SCTP as u32,
+        ])
+        & shift_field_u16(OFFSET_IP4_FRAGMENT, L::network_offset())
+            .mask(IP4_FRAGMENT_OFFSET_MASK)
+            .equals(0)
+}
+
+fn port_field<K: Backend, L: IpLayer>(offset: u32) -> Field<K, L> {
+    Field::from_computation(L::transport_u16(offset))
+}
+
+/// true iff the source port of a TCP, UDP or SCTP over IPv4 packet is any of `ports`
+pub fn src_port_in<K: Backend, L: IpLayer>(ports: &[u16]) -> Predicate<K, L> {
+    ip4_ported() & field_in(port_field(OFFSET_TRANSPORT_SRC_PORT), ports)
+}
+
+/// true iff the destination port of a TCP, UDP or SCTP over IPv4 packet is any of `ports`
+pub fn dst_port_in<K: Backend, L: IpLayer>(ports: &[u16]) -> Predicate<K, L> {
+    ip4_ported() & field_in(port_field(OFFSET_TRANSPORT_DST_PORT), ports)
+}
+
+/// true iff either the source or destination port of a TCP, UDP or SCTP over IPv4 packet is any
+/// of `ports`
+///
+/// # Example
+/// ```
+/// # use bs_filter::backend::Classic;
+/// # use bs_filter::idiom::transport::port_in;
+/// # use bs_filter::layer::Ethernet;
+/// let web = port_in::<Classic, Ethernet>(&[80, 443, 8080, 8443]);
+/// # assert!(web.compile().is_ok());
+/// ```
+pub fn port_in<K: Backend, L: IpLayer>(ports: &[u16]) -> Predicate<K, L> {
+    ip4_ported()
+        & (field_in(port_field(OFFSET_TRANSPORT_SRC_PORT), ports)
+            | field_in(port_field(OFFSET_TRANSPORT_DST_PORT), ports))
+}
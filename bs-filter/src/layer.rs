@@ -0,0 +1,101 @@
+use crate::backend::Backend;
+use crate::idiom::meta::protocol;
+use crate::idiom::offset_equals_u16;
+use crate::predicate::Predicate;
+use crate::Computation;
+use bs_system::consts::{OFFSET_ETHER_TYPE, SIZE_ETHER_HEADER, SKF_NET_OFF};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// The layer at which the packets seen by a filter start.
+///
+/// Different kinds of sockets hand their filters packets starting at different layers, e.g.
+/// `packet(7)` sockets of type `SOCK_RAW` see the entire ethernet frame, while `udp(7)` sockets see
+/// the packet starting at the UDP header.
+/// `Predicate`s and `Filter`s are parametrized by their `Layer`, and sockets only accept filters
+/// compiled for the layer they operate at.
+pub trait Layer: Sized + Copy + Clone + Ord + Debug + Hash {}
+
+/// A `Layer` whose packets are carried over IP, providing access to the IP header.
+pub trait IpLayer: Layer {
+    /// Offset of the IP header, relative to the start of the packet as seen by the filter.
+    ///
+    /// The offset may be "negative" (i.e. wrap around), in which case it refers to one of the
+    /// kernel's special `SKF_*_OFF` areas.
+    fn network_offset() -> u32;
+
+    /// Generates a `Predicate` that is true iff the packet's EtherType is `ether_type`.
+    fn ether_type<K: Backend>(ether_type: u16) -> Predicate<K, Self>;
+
+    /// Loads the u16 at offset `offset` in the transport header following the packet's IPv4
+    /// header.
+    fn transport_u16<K: Backend>(offset: u32) -> Computation<K> {
+        Computation::load_u16_at_transport(Self::network_offset(), offset)
+    }
+}
+
+/// Packets starting with an ethernet header, e.g. for `packet(7)` sockets of type `SOCK_RAW`
+#[derive(Copy, Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
+pub struct Ethernet {}
+
+impl Layer for Ethernet {}
+
+impl IpLayer for Ethernet {
+    fn network_offset() -> u32 {
+        SIZE_ETHER_HEADER
+    }
+
+    fn ether_type<K: Backend>(ether_type: u16) -> Predicate<K, Self> {
+        offset_equals_u16(OFFSET_ETHER_TYPE, ether_type)
+    }
+}
+
+/// Packets starting with an IP header, e.g. for `raw(7)` sockets and `packet(7)` sockets of type
+/// `SOCK_DGRAM`
+#[derive(Copy, Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
+pub struct Network {}
+
+impl Layer for Network {}
+
+impl IpLayer for Network {
+    fn network_offset() -> u32 {
+        0
+    }
+
+    fn ether_type<K: Backend>(ether_type: u16) -> Predicate<K, Self> {
+        protocol(ether_type)
+    }
+}
+
+/// Packets starting with a transport layer header, e.g. for `udp(7)` and `tcp(7)` sockets
+///
+/// The IP header is still reachable through the kernel's `SKF_NET_OFF` area.
+#[derive(Copy, Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
+pub struct Transport {}
+
+impl Layer for Transport {}
+
+impl IpLayer for Transport {
+    fn network_offset() -> u32 {
+        SKF_NET_OFF as u32
+    }
+
+    fn ether_type<K: Backend>(ether_type: u16) -> Predicate<K, Self> {
+        protocol(ether_type)
+    }
+
+    fn transport_u16<K: Backend>(offset: u32) -> Computation<K> {
+        Computation::load_u16_at(offset)
+    }
+}
+
+/// Netlink messages starting with their `nlmsghdr`, for `netlink(7)` sockets
+///
+/// The fields of netlink messages are in host byte order, unlike those of the packets seen at the
+/// other layers.
+/// The kernel packs several messages in a single datagram, e.g. the end of a dump along with its
+/// last entries, and filters see the whole datagram through the first message's header.
+#[derive(Copy, Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
+pub struct Netlink {}
+
+impl Layer for Netlink {}
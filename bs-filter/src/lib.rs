@@ -31,10 +31,15 @@
 pub(crate) mod filter;
 pub(crate) mod predicate;
 pub(crate) mod program;
+pub(crate) mod steering;
 
 pub use filter::AttachFilter;
+pub use filter::Attachable;
 pub use filter::Filter;
 pub use predicate::Predicate;
+pub use steering::AttachSteering;
+pub use steering::Steering;
+pub use steering::SteeringProgram;
 
 /// Provides various filtering backends, namely cBPF [`Classic`](backend/struct.Classic.html)
 /// and eBPF [`Extended`](backend/struct.Extended.html)
@@ -48,45 +53,164 @@ pub mod backend;
 /// and/or specific filters
 pub mod idiom;
 
+/// Provides phantom structs that represent the different layers at which packets handed to socket
+/// filters may start, e.g. [`Ethernet`](layer/struct.Ethernet.html) for `packet(7)` sockets or
+/// [`Transport`](layer/struct.Transport.html) for `udp(7)` sockets
+pub mod layer;
+
+use bs_system::{Result, SystemError};
+use std::collections::BTreeSet;
+
+/// A backend-neutral sequence of operations producing a value to be checked by a
+/// [`Condition`](struct.Condition.html)
+///
+/// A `Computation` starts by loading a value, either from the packet or from ancillary data about
+/// it, and may then transform it, e.g. by [`mask`](#method.mask)ing it.
 #[derive(Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd, Default)]
-pub(crate) struct Computation<K: backend::Backend> {
+pub struct Computation<K: backend::Backend> {
+    // kept in reverse order, like the rest of the program until it's compiled
     instructions: Vec<K::Instruction>,
 }
 
 impl<K: backend::Backend> Computation<K> {
-    pub fn new(instructions: Vec<K::Instruction>) -> Self {
+    pub(crate) fn new(instructions: Vec<K::Instruction>) -> Self {
         Self { instructions }
     }
 
-    pub fn build(self) -> Vec<K::Instruction> {
+    pub(crate) fn build(self) -> Vec<K::Instruction> {
         self.instructions
     }
+
+    /// Loads the octet at offset `offset` in the packet
+    pub fn load_u8_at(offset: u32) -> Self {
+        Self::new(K::load_u8_at(offset))
+    }
+
+    /// Loads the u16 at offset `offset` in the packet
+    pub fn load_u16_at(offset: u32) -> Self {
+        Self::new(K::load_u16_at(offset))
+    }
+
+    /// Loads the u32 at offset `offset` in the packet
+    pub fn load_u32_at(offset: u32) -> Self {
+        Self::new(K::load_u32_at(offset))
+    }
+
+    /// Loads the u16 at offset `offset` in the transport header following the IPv4 header found
+    /// at offset `network_offset` in the packet, accounting for IPv4 options
+    pub fn load_u16_at_transport(network_offset: u32, offset: u32) -> Self {
+        Self::new(K::load_u16_at_transport(network_offset, offset))
+    }
+
+    /// Loads the ancillary data `ancillary` about the packet
+    pub fn load_ancillary(ancillary: backend::Ancillary) -> Self {
+        Self::new(K::load_ancillary(ancillary))
+    }
+
+    /// Bitwise-ands the computed value with `mask`
+    pub fn mask(self, mask: u32) -> Self {
+        let mut instructions = K::mask(mask);
+        instructions.extend(self.instructions);
+        Self::new(instructions)
+    }
 }
 
+/// A terminal condition of a [`Predicate`](struct.Predicate.html), checking a
+/// [`Relation`](backend/enum.Relation.html) between the result of a
+/// [`Computation`](struct.Computation.html) and an operand
+///
+/// `Condition`s are the building blocks of every idiom, and can be used to define new ones for
+/// protocols not covered by `bs-filter`.
+///
+/// # Example
+/// ```
+/// # use bs_filter::backend::{Backend, Classic, Relation};
+/// # use bs_filter::layer::Transport;
+/// # use bs_filter::{Computation, Condition, Predicate};
+/// // an encapsulation header following the UDP header, whose high nibble is its version
+/// fn encapsulation_version<K: Backend>(version: u8) -> Predicate<K, Transport> {
+///     Condition::new(
+///         Computation::load_u8_at(8).mask(0xf0),
+///         Relation::Equal,
+///         (version as u32) << 4,
+///     )
+///     .into()
+/// }
+///
+/// let p = encapsulation_version::<Classic>(2);
+/// # assert!(p.compile().is_ok());
+/// ```
 #[derive(Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
-pub(crate) struct Condition<K: backend::Backend> {
+pub struct Condition<K: backend::Backend> {
     computation: Computation<K>,
-    comparison: K::Comparison,
-    operand: K::Value,
+    check: Check<K>,
+    negated: bool,
+}
+
+#[derive(Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
+enum Check<K: backend::Backend> {
+    Compare(K::Comparison, K::Value),
+    // sorted and deduplicated
+    Membership(Vec<u32>),
+    // neither the relation nor its negation is supported by the backend
+    Unsupported(backend::Relation),
 }
 
 impl<K: backend::Backend> Condition<K> {
-    pub(crate) fn new(
-        computation: Vec<K::Instruction>,
-        comparison: K::Comparison,
-        operand: K::Value,
-    ) -> Self {
+    /// Creates a new `Condition` that holds iff `relation` holds between the result of
+    /// `computation` and `operand`
+    ///
+    /// relations the backend doesn't support natively are implemented via their negation, and
+    /// predicates holding a relation supported neither way fail to compile
+    pub fn new(computation: Computation<K>, relation: backend::Relation, operand: u32) -> Self {
+        let operand = K::Value::from(operand);
+        let (check, negated) = match K::comparison(relation) {
+            Some(comparison) => (Check::Compare(comparison, operand), false),
+            None => match relation.negation().and_then(K::comparison) {
+                Some(comparison) => (Check::Compare(comparison, operand), true),
+                None => (Check::Unsupported(relation), false),
+            },
+        };
+        Self {
+            computation,
+            check,
+            negated,
+        }
+    }
+
+    /// Creates a new `Condition` that holds iff the result of `computation` is one of `values`
+    ///
+    /// Unlike a disjunction of `Relation::Equal` conditions, the value is computed only once, and
+    /// is then looked up efficiently by the backend, e.g. via a binary search on `Classic` or a
+    /// hash map on `Extended`.
+    pub fn any_of(computation: Computation<K>, values: &[u32]) -> Self {
+        let values: BTreeSet<u32> = values.iter().cloned().collect();
         Self {
-            computation: Computation::new(computation),
-            comparison,
-            operand,
+            computation,
+            check: Check::Membership(values.into_iter().collect()),
+            negated: false,
         }
     }
 
-    pub(crate) fn build(self, jt: usize, jf: usize) -> Vec<K::Instruction> {
-        let mut res = K::jump(self.comparison, self.operand, jt, jf);
+    pub(crate) fn build(
+        self,
+        jt: usize,
+        jf: usize,
+        sets: &mut Vec<K::Set>,
+    ) -> Result<Vec<K::Instruction>> {
+        let (jt, jf) = if self.negated { (jf, jt) } else { (jt, jf) };
+        let mut res = match self.check {
+            Check::Compare(comparison, operand) => K::jump(comparison, operand, jt, jf),
+            Check::Membership(values) => K::jump_in(&values, jt, jf, sets)?,
+            Check::Unsupported(relation) => {
+                return Err(SystemError::Compilation(format!(
+                    "the backend supports neither {:?} nor its negation",
+                    relation
+                )))
+            }
+        };
         res.extend(self.computation.build());
-        res
+        Ok(res)
     }
 }
 
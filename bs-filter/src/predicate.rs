@@ -1,5 +1,6 @@
 use crate::backend::Backend;
 use crate::filter::Filter;
+use crate::layer::Layer;
 use crate::Condition;
 pub use boolean_expression::Expr;
 pub use boolean_expression::Expr::*;
@@ -8,19 +9,23 @@ use bs_system::Result;
 use std::cmp::Ord;
 use std::fmt::Debug;
 use std::hash::Hash;
-use std::iter::FromIterator;
+use std::marker::PhantomData;
 use std::ops::{BitAnd, BitOr, Not};
 
 /// A boolian logic construction of `Condition`s
 /// can be extended via bitwise operation syntax (e.g. `|`, `&`)
 ///
+/// `Predicate`s are bound to the [`Layer`](layer/trait.Layer.html) at which the packets they
+/// inspect start, so only `Predicate`s of the same `Layer` can be combined.
+///
 /// # Example
 /// ```
 /// # use std::ops::{BitAnd, BitOr};
 /// # use bs_filter::backend::Classic;
 /// # use bs_filter::backend::Backend;
+/// # use bs_filter::layer::Ethernet;
 ///
-/// type Predicate = bs_filter::Predicate<Classic>;
+/// type Predicate = bs_filter::Predicate<Classic, Ethernet>;
 ///
 /// fn show_or() {
 ///     // Predicates can be constructed with `&` to create a new And(...) Predicate
@@ -40,24 +45,25 @@ use std::ops::{BitAnd, BitOr, Not};
 /// }
 /// ```
 #[derive(Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
-pub struct Predicate<K: Backend> {
+pub struct Predicate<K: Backend, L: Layer> {
     expr: Expr<Condition<K>>,
+    layer: PhantomData<L>,
 }
 
-impl<K: Backend> Predicate<K> {
+impl<K: Backend, L: Layer> Predicate<K, L> {
     /// Generate a `Socket`-appropriate `Filter` implementing `self`'s logic
-    pub fn compile(mut self) -> Result<Filter<K>> {
-        self = Predicate::from_inner(self.into_inner().simplify_via_laws());
+    pub fn compile(self) -> Result<Filter<K, L>> {
         let (mut instructions, jt, jf) = K::return_sequence();
+        let mut sets = Vec::new();
 
-        instructions.extend(self.walk(jt, jf));
+        instructions.extend(self.simplify().walk(jt, jf, &mut sets)?);
 
         instructions.extend(K::initialization_sequence());
 
         instructions.reverse();
         // TODO - optimizations
 
-        Ok(Filter::from_iter(instructions))
+        Ok(Filter::new(instructions, sets))
     }
 
     /// always false
@@ -82,25 +88,46 @@ impl<K: Backend> Predicate<K> {
         bdd.sat(func)
     }
 
+    pub(crate) fn simplify(self) -> Self {
+        Self::from_inner(self.into_inner().simplify_via_laws())
+    }
+
+    /// The predicate's value, if it doesn't depend on the packet
+    pub(crate) fn constant(&self) -> Option<bool> {
+        match self.expr {
+            Const(boolean) => Some(boolean),
+            _ => None,
+        }
+    }
+
     fn into_inner(self) -> Expr<Condition<K>> {
         self.expr
     }
 
     pub(crate) fn from_inner(expr: Expr<Condition<K>>) -> Self {
-        Self { expr }
+        Self {
+            expr,
+            layer: PhantomData,
+        }
     }
-    fn walk(self, jt: usize, jf: usize) -> Vec<K::Instruction> {
-        match self.into_inner() {
-            Terminal(condition) => condition.build(jt, jf),
-            Not(e) => Predicate::from_inner(*e).walk(jf, jt),
+
+    pub(crate) fn walk(
+        self,
+        jt: usize,
+        jf: usize,
+        sets: &mut Vec<K::Set>,
+    ) -> Result<Vec<K::Instruction>> {
+        Ok(match self.into_inner() {
+            Terminal(condition) => condition.build(jt, jf, sets)?,
+            Not(e) => Self::from_inner(*e).walk(jf, jt, sets)?,
             And(a, b) => {
-                let mut res = Predicate::from_inner(*b).walk(jt, jf);
-                res.extend(Predicate::from_inner(*a).walk(0, jf + res.len()));
+                let mut res = Self::from_inner(*b).walk(jt, jf, sets)?;
+                res.extend(Self::from_inner(*a).walk(0, jf + res.len(), sets)?);
                 res
             }
             Or(a, b) => {
-                let mut res = Predicate::from_inner(*b).walk(jt, jf);
-                res.extend(Predicate::from_inner(*a).walk(jt + res.len(), 0));
+                let mut res = Self::from_inner(*b).walk(jt, jf, sets)?;
+                res.extend(Self::from_inner(*a).walk(jt + res.len(), 0, sets)?);
                 res
             }
             Const(boolean) => {
@@ -110,37 +137,37 @@ impl<K: Backend> Predicate<K> {
                     K::contradiction()
                 }
             }
-        }
+        })
     }
 }
 
-impl<K: Backend> BitAnd for Predicate<K> {
-    type Output = Predicate<K>;
+impl<K: Backend, L: Layer> From<Condition<K>> for Predicate<K, L> {
+    fn from(condition: Condition<K>) -> Self {
+        Self::from_inner(Terminal(condition))
+    }
+}
 
-    fn bitand(self, rhs: Predicate<K>) -> Self::Output {
-        Predicate {
-            expr: And(Box::new(self.into_inner()), Box::new(rhs.into_inner())),
-        }
+impl<K: Backend, L: Layer> BitAnd for Predicate<K, L> {
+    type Output = Predicate<K, L>;
+
+    fn bitand(self, rhs: Predicate<K, L>) -> Self::Output {
+        Predicate::from_inner(And(Box::new(self.into_inner()), Box::new(rhs.into_inner())))
     }
 }
 
-impl<K: Backend> BitOr for Predicate<K> {
-    type Output = Predicate<K>;
+impl<K: Backend, L: Layer> BitOr for Predicate<K, L> {
+    type Output = Predicate<K, L>;
 
-    fn bitor(self, rhs: Predicate<K>) -> Self::Output {
-        Predicate {
-            expr: Or(Box::new(self.into_inner()), Box::new(rhs.into_inner())),
-        }
+    fn bitor(self, rhs: Predicate<K, L>) -> Self::Output {
+        Predicate::from_inner(Or(Box::new(self.into_inner()), Box::new(rhs.into_inner())))
     }
 }
 
-impl<K: Backend> Not for Predicate<K> {
-    type Output = Predicate<K>;
+impl<K: Backend, L: Layer> Not for Predicate<K, L> {
+    type Output = Predicate<K, L>;
 
     fn not(self) -> Self::Output {
-        Predicate {
-            expr: Not(Box::new(self.into_inner())),
-        }
+        Predicate::from_inner(Not(Box::new(self.into_inner())))
     }
 }
 
@@ -150,8 +177,9 @@ impl<K: Backend> Not for Predicate<K> {
 mod tests {
 
     use crate::backend::Classic;
+    use crate::layer::Ethernet;
 
-    type Predicate = super::Predicate<Classic>;
+    type Predicate = super::Predicate<Classic, Ethernet>;
 
     #[test]
     fn simple_and() {
@@ -0,0 +1,115 @@
+use crate::backend::Backend;
+use crate::layer::Layer;
+use crate::predicate::Predicate;
+use crate::program::Program;
+use bs_system::Result;
+use std::marker::PhantomData;
+use std::os::unix::io::RawFd;
+
+/// A program steering packets between the sockets of a `PACKET_FANOUT` group
+///
+/// Unlike a [`Filter`](struct.Filter.html), whose return value is the length to which a packet is
+/// truncated, a steering program returns the index of the socket in the group the packet is
+/// handed to. Routes are checked in the order they were added, and packets matching none of them
+/// are handed to the default socket.
+///
+/// # Example
+/// ```
+/// # use bs_filter::backend::Classic;
+/// # use bs_filter::idiom::meta::protocol;
+/// # use bs_filter::layer::Network;
+/// # use bs_filter::Steering;
+/// // ARP to the second socket of the group, everything else to the first
+/// let steering = Steering::<Classic, Network>::new(0).route(protocol(0x0806), 1);
+/// # assert!(steering.compile().is_ok());
+/// ```
+#[derive(Clone, Debug, Ord, Eq, Hash, PartialEq, PartialOrd)]
+pub struct Steering<K: Backend, L: Layer> {
+    routes: Vec<(Predicate<K, L>, u32)>,
+    default: u32,
+}
+
+impl<K: Backend, L: Layer> Steering<K, L> {
+    /// Creates a new `Steering` handing every packet to the socket at index `default`
+    pub fn new(default: u32) -> Self {
+        Self {
+            routes: Vec::new(),
+            default,
+        }
+    }
+
+    /// Hands packets satisfying `predicate`, and no route added before it, to the socket at
+    /// index `index`
+    pub fn route(mut self, predicate: Predicate<K, L>, index: u32) -> Self {
+        self.routes.push((predicate, index));
+        self
+    }
+
+    /// Generates a `SteeringProgram` implementing `self`'s routes
+    pub fn compile(self) -> Result<SteeringProgram<K, L>> {
+        let mut instructions = K::return_value(self.default);
+        let mut sets = Vec::new();
+
+        // built backwards like the rest of the program, so every route can fall through to the
+        // ones after it
+        for (predicate, index) in self.routes.into_iter().rev() {
+            let predicate = predicate.simplify();
+            let mut route = K::return_value(index);
+            match predicate.constant() {
+                Some(true) => instructions = route,
+                Some(false) => {}
+                None => {
+                    let skip = route.len();
+                    instructions.append(&mut route);
+                    instructions.extend(predicate.walk(0, skip, &mut sets)?);
+                }
+            }
+        }
+
+        instructions.extend(K::initialization_sequence());
+        instructions.reverse();
+
+        let option = Program::<K>::new(instructions).build()?;
+        // the loaded program holds its own references to the sets from now on
+        drop(sets);
+        Ok(SteeringProgram {
+            option,
+            layer: PhantomData,
+        })
+    }
+}
+
+/// A compiled [`Steering`](struct.Steering.html), ready to be set on a `PACKET_FANOUT` group of
+/// sockets whose packets start at `Layer` `L`
+#[derive(Debug)]
+pub struct SteeringProgram<K: Backend, L: Layer> {
+    option: K::SocketOption,
+    layer: PhantomData<L>,
+}
+
+#[doc(hidden)]
+pub trait AttachSteering<L: Layer> {
+    fn attach(&self, socket: RawFd) -> Result<i32>;
+}
+
+#[cfg(target_os = "linux")]
+impl<K: Backend, L: Layer> AttachSteering<L> for SteeringProgram<K, L> {
+    fn attach(&self, socket: RawFd) -> Result<i32> {
+        use bs_system::consts::PACKET_FANOUT_DATA;
+        use bs_system::{cvt, SocketOption};
+        use libc::{c_void, setsockopt, SOL_PACKET};
+
+        // `PACKET_FANOUT_DATA` takes the same `sock_fprog` or program fd as the socket's own
+        // filter does
+        let ptr: *const K::SocketOption = &self.option;
+        unsafe {
+            cvt(setsockopt(
+                socket,
+                SOL_PACKET,
+                PACKET_FANOUT_DATA,
+                ptr as *const c_void,
+                self.option.optlen(),
+            ))
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Prints the length and ethertype of every ethernet frame seen by a nonblocking
+//! `PacketLayer2Socket`, driven by a `mio` poll loop
+//!
+//! `packet(7)` sockets require `CAP_NET_RAW`:
+//! `cargo run -p bs-socket --example mio_packet --features mio`
+
+#[cfg(target_os = "linux")]
+fn main() -> std::io::Result<()> {
+    use bs_socket::packet::PacketLayer2Socket;
+    use bs_socket::socket::{Receive, Socket};
+    use mio::{Events, Interest, Poll, Token};
+    use std::io::ErrorKind;
+
+    const PACKETS: Token = Token(0);
+    const ETHER_TYPE: usize = 12;
+
+    let mut poll = Poll::new()?;
+    let mut events = Events::with_capacity(16);
+
+    let mut socket: Socket<PacketLayer2Socket> = Socket::nonblocking()?;
+    poll.registry()
+        .register(&mut socket, PACKETS, Interest::READABLE)?;
+
+    let mut buffer = [0; 65536];
+    loop {
+        poll.poll(&mut events, None)?;
+        for event in &events {
+            if event.token() != PACKETS {
+                continue;
+            }
+            // readiness is only reported again once the socket would block
+            loop {
+                match socket.receive(&mut buffer, 0) {
+                    Ok(n) if n > ETHER_TYPE + 1 => println!(
+                        "{} bytes, ethertype {:#06x}",
+                        n,
+                        u16::from_be_bytes([buffer[ETHER_TYPE], buffer[ETHER_TYPE + 1]])
+                    ),
+                    Ok(n) => println!("{} bytes", n),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("packet(7) sockets are only available on linux");
+}
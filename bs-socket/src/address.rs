@@ -0,0 +1,420 @@
+use bs_system::{Result, SystemError};
+use libc::{sa_family_t, sockaddr_in, sockaddr_in6, sockaddr_storage, sockaddr_un, socklen_t};
+use libc::{AF_INET, AF_INET6, AF_UNIX, EAFNOSUPPORT, EINVAL, ENAMETOOLONG};
+use std::ffi::OsStr;
+use std::mem::{size_of, zeroed};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr::copy_nonoverlapping;
+
+/// An address of a specific socket domain, convertible to and from its `sockaddr_*` form
+///
+/// Each [`SocketKind`](../socket/trait.SocketKind.html) is tied to the `SocketAddress` of its
+/// domain, so e.g. `udp(7)` sockets can only be bound to IP socket addresses.
+pub trait SocketAddress: Sized {
+    /// The address family (`sa_family`) of the address, e.g. `AF_INET`
+    fn family() -> i32;
+
+    /// Converts the address to its `sockaddr_*` form, stored in a `sockaddr_storage`, along with
+    /// the length of the `sockaddr_*`
+    fn to_storage(&self) -> (sockaddr_storage, socklen_t);
+
+    /// Converts a `sockaddr_storage` holding a `sockaddr_*` of `length` octets back to an address
+    ///
+    /// # Errors
+    /// `EAFNOSUPPORT` if `storage` holds an address of a different family, `EINVAL` if `length`
+    /// is too short for the address
+    fn from_storage(storage: &sockaddr_storage, length: socklen_t) -> Result<Self>;
+}
+
+/// Reinterprets `storage` as a `T`, i.e. a specific `sockaddr_*`
+fn read_storage<T: Copy>(storage: &sockaddr_storage) -> T {
+    let ptr: *const sockaddr_storage = storage;
+    // sockaddr_storage is large and aligned enough for every sockaddr_*
+    unsafe { *(ptr as *const T) }
+}
+
+/// Stores the `sockaddr_*` `address` in a `sockaddr_storage`
+fn write_storage<T>(address: &T) -> (sockaddr_storage, socklen_t) {
+    let mut storage: sockaddr_storage = unsafe { zeroed() };
+    let dst: *mut sockaddr_storage = &mut storage;
+    let src: *const T = address;
+    unsafe { copy_nonoverlapping(src as *const u8, dst as *mut u8, size_of::<T>()) };
+    (storage, size_of::<T>() as socklen_t)
+}
+
+fn check_storage<A: SocketAddress>(
+    storage: &sockaddr_storage,
+    length: socklen_t,
+    minimal_length: usize,
+) -> Result<()> {
+    if storage.ss_family as i32 != A::family() {
+        Err(SystemError::from_raw_os_error(EAFNOSUPPORT))
+    } else if (length as usize) < minimal_length {
+        Err(SystemError::from_raw_os_error(EINVAL))
+    } else {
+        Ok(())
+    }
+}
+
+impl SocketAddress for SocketAddrV4 {
+    fn family() -> i32 {
+        AF_INET
+    }
+
+    fn to_storage(&self) -> (sockaddr_storage, socklen_t) {
+        let mut address: sockaddr_in = unsafe { zeroed() };
+        address.sin_family = AF_INET as sa_family_t;
+        address.sin_port = self.port().to_be();
+        address.sin_addr.s_addr = u32::from(*self.ip()).to_be();
+        write_storage(&address)
+    }
+
+    fn from_storage(storage: &sockaddr_storage, length: socklen_t) -> Result<Self> {
+        check_storage::<Self>(storage, length, size_of::<sockaddr_in>())?;
+        let address: sockaddr_in = read_storage(storage);
+        Ok(Self::new(
+            Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr)),
+            u16::from_be(address.sin_port),
+        ))
+    }
+}
+
+impl SocketAddress for SocketAddrV6 {
+    fn family() -> i32 {
+        AF_INET6
+    }
+
+    fn to_storage(&self) -> (sockaddr_storage, socklen_t) {
+        let mut address: sockaddr_in6 = unsafe { zeroed() };
+        address.sin6_family = AF_INET6 as sa_family_t;
+        address.sin6_port = self.port().to_be();
+        address.sin6_flowinfo = self.flowinfo().to_be();
+        address.sin6_addr.s6_addr = self.ip().octets();
+        address.sin6_scope_id = self.scope_id();
+        write_storage(&address)
+    }
+
+    fn from_storage(storage: &sockaddr_storage, length: socklen_t) -> Result<Self> {
+        check_storage::<Self>(storage, length, size_of::<sockaddr_in6>())?;
+        let address: sockaddr_in6 = read_storage(storage);
+        Ok(Self::new(
+            Ipv6Addr::from(address.sin6_addr.s6_addr),
+            u16::from_be(address.sin6_port),
+            u32::from_be(address.sin6_flowinfo),
+            address.sin6_scope_id,
+        ))
+    }
+}
+
+/// An IP address family, selecting between `ip(7)` and `ipv6(7)` for IP socket kinds
+pub trait IpFamily: Copy + std::fmt::Debug {
+    /// The socket addresses of the family
+    type Address: SocketAddress + Copy + std::fmt::Debug;
+}
+
+/// IPv4 (`AF_INET`)
+#[derive(Debug, Copy, Clone)]
+pub struct Inet;
+
+/// IPv6 (`AF_INET6`)
+#[derive(Debug, Copy, Clone)]
+pub struct Inet6;
+
+impl IpFamily for Inet {
+    type Address = SocketAddrV4;
+}
+
+impl IpFamily for Inet6 {
+    type Address = SocketAddrV6;
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use libc::{sockaddr_ll, sockaddr_nl, AF_NETLINK, AF_PACKET};
+
+        /// A `packet(7)` link layer address (`sockaddr_ll`)
+        ///
+        /// Binding a `packet(7)` socket to a `LinkAddress` restricts it to packets of the
+        /// address' protocol, going through the address' interface.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub struct LinkAddress {
+            interface_index: i32,
+            protocol: u16,
+            hardware_type: u16,
+            packet_type: u8,
+            hardware_address_length: u8,
+            hardware_address: [u8; 8],
+        }
+
+        impl LinkAddress {
+            /// Creates a new `LinkAddress` for the interface with index `interface_index` and the
+            /// EtherType `protocol` (e.g. `ETH_P_ALL`), in host byte order
+            pub fn new(interface_index: i32, protocol: u16) -> Self {
+                Self {
+                    interface_index,
+                    protocol,
+                    hardware_type: 0,
+                    packet_type: 0,
+                    hardware_address_length: 0,
+                    hardware_address: [0; 8],
+                }
+            }
+
+            /// Sets the physical layer address, e.g. the destination MAC address of frames sent by
+            /// a `PacketLayer3Socket`, truncated to 8 octets
+            pub fn with_hardware_address(mut self, address: &[u8]) -> Self {
+                let length = address.len().min(self.hardware_address.len());
+                self.hardware_address = [0; 8];
+                self.hardware_address[..length].copy_from_slice(&address[..length]);
+                self.hardware_address_length = length as u8;
+                self
+            }
+
+            /// Index of the address' interface, 0 matches any interface
+            pub fn interface_index(&self) -> i32 {
+                self.interface_index
+            }
+
+            /// EtherType of the address' protocol, in host byte order
+            pub fn protocol(&self) -> u16 {
+                self.protocol
+            }
+
+            /// ARP hardware type of the address' interface (`sll_hatype`)
+            pub fn hardware_type(&self) -> u16 {
+                self.hardware_type
+            }
+
+            /// Packet type (`sll_pkttype`), one of the `PACKET_*` constants of `packet(7)`
+            pub fn packet_type(&self) -> u8 {
+                self.packet_type
+            }
+
+            /// The physical layer address, e.g. a MAC address
+            pub fn hardware_address(&self) -> &[u8] {
+                &self.hardware_address[..self.hardware_address_length as usize]
+            }
+        }
+
+        impl SocketAddress for LinkAddress {
+            fn family() -> i32 {
+                AF_PACKET
+            }
+
+            fn to_storage(&self) -> (sockaddr_storage, socklen_t) {
+                let mut address: sockaddr_ll = unsafe { zeroed() };
+                address.sll_family = AF_PACKET as u16;
+                address.sll_protocol = self.protocol.to_be();
+                address.sll_ifindex = self.interface_index;
+                address.sll_hatype = self.hardware_type;
+                address.sll_pkttype = self.packet_type;
+                address.sll_halen = self.hardware_address_length;
+                address.sll_addr = self.hardware_address;
+                write_storage(&address)
+            }
+
+            fn from_storage(storage: &sockaddr_storage, length: socklen_t) -> Result<Self> {
+                // the kernel omits the unused part of sll_addr
+                let minimal_length = size_of::<sockaddr_ll>() - size_of::<[u8; 8]>();
+                check_storage::<Self>(storage, length, minimal_length)?;
+                let address: sockaddr_ll = read_storage(storage);
+                Ok(Self {
+                    interface_index: address.sll_ifindex,
+                    protocol: u16::from_be(address.sll_protocol),
+                    hardware_type: address.sll_hatype,
+                    packet_type: address.sll_pkttype,
+                    hardware_address_length: address.sll_halen.min(8),
+                    hardware_address: address.sll_addr,
+                })
+            }
+        }
+
+        /// A `netlink(7)` address (`sockaddr_nl`)
+        ///
+        /// The kernel's address has port 0, while sockets get a unique port when bound to port 0.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub struct NetlinkAddress {
+            port: u32,
+            groups: u32,
+        }
+
+        impl NetlinkAddress {
+            /// Creates a new `NetlinkAddress` for the socket with port `port` (`nl_pid`) and the
+            /// bitmask of the multicast groups `groups`, limited to the first 32 groups
+            pub fn new(port: u32, groups: u32) -> Self {
+                Self { port, groups }
+            }
+
+            /// The address of the kernel
+            pub fn kernel() -> Self {
+                Self::new(0, 0)
+            }
+
+            /// The port of the address (`nl_pid`)
+            pub fn port(&self) -> u32 {
+                self.port
+            }
+
+            /// The bitmask of the multicast groups of the address (`nl_groups`)
+            pub fn groups(&self) -> u32 {
+                self.groups
+            }
+        }
+
+        impl SocketAddress for NetlinkAddress {
+            fn family() -> i32 {
+                AF_NETLINK
+            }
+
+            fn to_storage(&self) -> (sockaddr_storage, socklen_t) {
+                let mut address: sockaddr_nl = unsafe { zeroed() };
+                address.nl_family = AF_NETLINK as sa_family_t;
+                address.nl_pid = self.port;
+                address.nl_groups = self.groups;
+                write_storage(&address)
+            }
+
+            fn from_storage(storage: &sockaddr_storage, length: socklen_t) -> Result<Self> {
+                check_storage::<Self>(storage, length, size_of::<sockaddr_nl>())?;
+                let address: sockaddr_nl = read_storage(storage);
+                Ok(Self::new(address.nl_pid, address.nl_groups))
+            }
+        }
+    }
+}
+
+/// A `unix(7)` socket address (`sockaddr_un`)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnixAddress {
+    kind: UnixAddressKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum UnixAddressKind {
+    Unnamed,
+    Pathname(Vec<u8>),
+    Abstract(Vec<u8>),
+}
+
+fn sun_path_offset() -> usize {
+    let address: sockaddr_un = unsafe { zeroed() };
+    let base: *const sockaddr_un = &address;
+    address.sun_path.as_ptr() as usize - base as usize
+}
+
+fn sun_path_capacity() -> usize {
+    size_of::<sockaddr_un>() - sun_path_offset()
+}
+
+impl UnixAddress {
+    /// An unnamed address, as held by sockets that aren't bound to any name
+    pub fn unnamed() -> Self {
+        Self {
+            kind: UnixAddressKind::Unnamed,
+        }
+    }
+
+    /// An address bound to the filesystem `path`
+    ///
+    /// # Errors
+    /// `EINVAL` if `path` is empty or contains a NUL octet, `ENAMETOOLONG` if it doesn't fit in a
+    /// `sockaddr_un`
+    pub fn pathname(path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = path.as_ref().as_os_str().as_bytes();
+        if bytes.is_empty() || bytes.contains(&0) {
+            return Err(SystemError::from_raw_os_error(EINVAL));
+        }
+        // leave room for the terminating NUL
+        if bytes.len() >= sun_path_capacity() {
+            return Err(SystemError::from_raw_os_error(ENAMETOOLONG));
+        }
+        Ok(Self {
+            kind: UnixAddressKind::Pathname(bytes.to_vec()),
+        })
+    }
+
+    /// A Linux abstract socket address, with the given `name` (excluding the leading NUL)
+    ///
+    /// # Errors
+    /// `ENAMETOOLONG` if `name` doesn't fit in a `sockaddr_un`
+    pub fn abstract_name(name: &[u8]) -> Result<Self> {
+        if name.len() >= sun_path_capacity() {
+            return Err(SystemError::from_raw_os_error(ENAMETOOLONG));
+        }
+        Ok(Self {
+            kind: UnixAddressKind::Abstract(name.to_vec()),
+        })
+    }
+
+    /// true iff the address is unnamed
+    pub fn is_unnamed(&self) -> bool {
+        self.kind == UnixAddressKind::Unnamed
+    }
+
+    /// The filesystem path of the address, if it's a pathname address
+    pub fn as_pathname(&self) -> Option<&Path> {
+        match &self.kind {
+            UnixAddressKind::Pathname(bytes) => Some(Path::new(OsStr::from_bytes(bytes))),
+            _ => None,
+        }
+    }
+
+    /// The name of the address, if it's an abstract address
+    pub fn as_abstract_name(&self) -> Option<&[u8]> {
+        match &self.kind {
+            UnixAddressKind::Abstract(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl SocketAddress for UnixAddress {
+    fn family() -> i32 {
+        AF_UNIX
+    }
+
+    fn to_storage(&self) -> (sockaddr_storage, socklen_t) {
+        let mut address: sockaddr_un = unsafe { zeroed() };
+        address.sun_family = AF_UNIX as sa_family_t;
+        let (skip, name): (usize, &[u8]) = match &self.kind {
+            UnixAddressKind::Unnamed => (0, &[]),
+            // NUL terminated by the zeroed remainder of sun_path
+            UnixAddressKind::Pathname(bytes) => (0, bytes),
+            UnixAddressKind::Abstract(name) => (1, name),
+        };
+        for (dst, src) in address.sun_path[skip..].iter_mut().zip(name) {
+            *dst = *src as libc::c_char;
+        }
+        let length = match &self.kind {
+            UnixAddressKind::Unnamed => size_of::<sa_family_t>(),
+            UnixAddressKind::Pathname(bytes) => sun_path_offset() + bytes.len() + 1,
+            UnixAddressKind::Abstract(name) => sun_path_offset() + name.len() + 1,
+        };
+        let (storage, _) = write_storage(&address);
+        (storage, length as socklen_t)
+    }
+
+    fn from_storage(storage: &sockaddr_storage, length: socklen_t) -> Result<Self> {
+        // messages from unbound sockets come with no address at all, not even a family
+        if length == 0 {
+            return Ok(Self::unnamed());
+        }
+        check_storage::<Self>(storage, length, size_of::<sa_family_t>())?;
+        let address: sockaddr_un = read_storage(storage);
+        let length = (length as usize).min(size_of::<sockaddr_un>());
+        if length <= sun_path_offset() {
+            return Ok(Self::unnamed());
+        }
+        let path: Vec<u8> = address.sun_path[..length - sun_path_offset()]
+            .iter()
+            .map(|&c| c as u8)
+            .collect();
+        if path[0] == 0 {
+            return Self::abstract_name(&path[1..]);
+        }
+        let end = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+        Self::pathname(OsStr::from_bytes(&path[..end]))
+    }
+}
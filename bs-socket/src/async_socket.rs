@@ -0,0 +1,299 @@
+use crate::address::IpFamily;
+use crate::option::OptionFor;
+use crate::socket::private::PrivateBasicSocket;
+use crate::socket::{BasicSocket, Bind, Connect, Receive, ReceiveFrom, SendTo, Socket, SocketKind};
+use crate::tcp::{Connected, Listening, TcpSocket};
+use crate::unix::UnixConnectionKind;
+use bs_system::{Result, SetSocketOption};
+use cfg_if::cfg_if;
+use futures_core::Stream;
+use std::future::poll_fn;
+use std::io;
+use std::io::ErrorKind;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use crate::packet::PacketSocketKind;
+        use crate::sctp::{OneToOne, SctpSocket};
+    }
+}
+
+#[cfg(feature = "bs-filter")]
+use {
+    crate::socket::SetFilter,
+    bs_filter::{backend, backend::Backend, AttachFilter, Filter},
+    std::iter::FromIterator,
+};
+
+/// A nonblocking `Socket` registered with the `tokio` runtime
+///
+/// Each async method waits until the socket is ready, then retries its operation until it no
+/// longer fails with `EAGAIN`. The runtime only reports readiness once per event, so the
+/// readiness is cleared whenever the operation would block, and only then.
+///
+/// The registered fd mustn't change, so the underlying `Socket` is only lent immutably, and
+/// should be bound and configured before it's wrapped, besides `set_option` and `set_filter`.
+/// ```no_run
+/// # use bs_socket::async_socket::AsyncSocket;
+/// # use bs_socket::socket::{Bind, Socket};
+/// # use bs_socket::udp::UdpSocket;
+/// # async fn f() -> bs_system::Result<()> {
+/// let mut s: Socket<UdpSocket> = Socket::new()?;
+/// let _ = s.bind(&"127.0.0.1:4242".parse().unwrap())?;
+/// let s = AsyncSocket::new(s)?;
+/// let mut buffer = [0; 1500];
+/// let (length, source) = s.receive_from(&mut buffer, 0).await?;
+/// let _ = s.send_to(&buffer[..length], &source, 0).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AsyncSocket<S: SocketKind> {
+    inner: AsyncFd<Socket<S>>,
+}
+
+/// Whether `result` failed as its operation would have blocked
+fn would_block<T>(result: &Result<T>) -> bool {
+    matches!(result, Err(e) if e.kind() == ErrorKind::WouldBlock)
+}
+
+impl<S: SocketKind> AsyncSocket<S> {
+    /// Sets the `O_NONBLOCK` flag of `socket` and registers it with the current runtime
+    /// # Panics
+    /// Outside of a `tokio` runtime
+    pub fn new(mut socket: Socket<S>) -> Result<Self> {
+        let _ = socket.set_nonblocking()?;
+        // the socket owns its fd, which is only closed on drop, and it's never replaced
+        let inner = unsafe { AsyncFd::register(socket) }.map_err(io::Error::from)?;
+        Ok(Self { inner })
+    }
+
+    /// The underlying `Socket`
+    pub fn get_ref(&self) -> &Socket<S> {
+        self.inner.get_ref()
+    }
+
+    /// Deregisters the socket from the runtime, it's left nonblocking
+    pub fn into_inner(self) -> Socket<S> {
+        self.inner.into_inner()
+    }
+
+    /// Sets `option` on the socket (`setsockopt(2)`)
+    pub fn set_option<O: SetSocketOption + OptionFor<S>>(
+        &mut self,
+        option: O,
+    ) -> Result<&mut Self> {
+        let _ = self.inner.get_mut().set_option(option)?;
+        Ok(self)
+    }
+
+    /// Waits until the socket is ready for `interest` and calls `operation`, until it doesn't
+    /// fail with `EAGAIN`
+    async fn io<T>(
+        &self,
+        interest: Interest,
+        mut operation: impl FnMut(&Socket<S>) -> Result<T>,
+    ) -> Result<T> {
+        loop {
+            let mut guard = self.inner.ready(interest).await?;
+            let result = operation(guard.get_inner());
+            if !would_block(&result) {
+                return result;
+            }
+            guard.clear_ready();
+        }
+    }
+
+    /// Flushes the socket's receive queue, see [`drain`](#method.drain)
+    async fn drain_queue(&mut self) -> Result<()> {
+        poll_fn(|cx| match self.inner.poll_read_ready_mut(cx) {
+            // readiness reported after the guard was taken is kept by `clear_ready`
+            Poll::Ready(Ok(mut guard)) => {
+                let _ = guard.get_inner_mut().drain_queue()?;
+                guard.clear_ready();
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e.into())),
+            Poll::Pending => Poll::Ready(self.inner.get_mut().drain_queue().map(|_| ())),
+        })
+        .await
+    }
+}
+
+impl<S: SocketKind> AsyncSocket<S>
+where
+    Socket<S>: Receive,
+{
+    /// Receives a packet on the socket, waiting until one arrives
+    pub async fn receive(&self, buf: &mut [u8], flags: i32) -> Result<usize> {
+        self.io(Interest::READABLE, |socket| socket.receive(buf, flags))
+            .await
+    }
+
+    /// Flushes the socket's receive queue
+    ///
+    /// Doesn't wait for packets, but clears the readiness the runtime last reported, which
+    /// flushed packets would leave stale.
+    pub async fn drain(&mut self) -> Result<&mut Self> {
+        self.drain_queue().await?;
+        Ok(self)
+    }
+}
+
+impl<S: SocketKind> AsyncSocket<S>
+where
+    Socket<S>: ReceiveFrom,
+{
+    /// Receives a packet into `buf`, waiting until one arrives, returns its length and its
+    /// source address
+    pub async fn receive_from(
+        &self,
+        buf: &mut [u8],
+        flags: i32,
+    ) -> Result<(usize, <Socket<S> as Bind>::Address)> {
+        self.io(Interest::READABLE, |socket| socket.receive_from(buf, flags))
+            .await
+    }
+}
+
+impl<S: SocketKind> AsyncSocket<S>
+where
+    Socket<S>: SendTo,
+{
+    /// Sends `buf` to `address`, waiting until the socket can send, returns the number of bytes
+    /// sent
+    pub async fn send_to(
+        &self,
+        buf: &[u8],
+        address: &<Socket<S> as Bind>::Address,
+        flags: i32,
+    ) -> Result<usize> {
+        self.io(Interest::WRITABLE, |socket| {
+            socket.send_to(buf, address, flags)
+        })
+        .await
+    }
+}
+
+impl<S: SocketKind> AsyncSocket<S>
+where
+    Socket<S>: Connect,
+{
+    /// Sends `buf` to the address the socket is connected to, waiting until the socket can
+    /// send, returns the number of bytes sent
+    pub async fn send(&self, buf: &[u8], flags: i32) -> Result<usize> {
+        self.io(Interest::WRITABLE, |socket| socket.send(buf, flags))
+            .await
+    }
+}
+
+impl<F: IpFamily> AsyncSocket<TcpSocket<Connected, F>> {
+    /// Sends `buf` to the socket's peer, waiting until the socket can send, returns the number
+    /// of bytes sent
+    pub async fn send(&self, buf: &[u8], flags: i32) -> Result<usize> {
+        self.io(Interest::WRITABLE, |socket| socket.send(buf, flags))
+            .await
+    }
+}
+
+impl<F: IpFamily> AsyncSocket<TcpSocket<Listening, F>> {
+    /// Accepts a pending connection, waiting until one arrives
+    pub async fn accept(&self) -> Result<AsyncSocket<TcpSocket<Connected, F>>> {
+        let socket = self
+            .io(Interest::READABLE, |socket| socket.accept())
+            .await?;
+        AsyncSocket::new(socket)
+    }
+}
+
+impl<S: UnixConnectionKind> AsyncSocket<S> {
+    /// Accepts a pending connection, waiting until one arrives
+    pub async fn accept(&self) -> Result<Self> {
+        let socket = self
+            .io(Interest::READABLE, |socket| socket.accept())
+            .await?;
+        Self::new(socket)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<F: IpFamily> AsyncSocket<SctpSocket<OneToOne, F>> {
+    /// Accepts a pending association, waiting until one arrives
+    pub async fn accept(&self) -> Result<Self> {
+        let socket = self
+            .io(Interest::READABLE, |socket| socket.accept())
+            .await?;
+        Self::new(socket)
+    }
+}
+
+#[cfg(feature = "bs-filter")]
+impl<S: SocketKind> AsyncSocket<S>
+where
+    Socket<S>: SetFilter,
+{
+    /// Flushes the socket's incoming stream and sets a new filter, see `SetFilter::set_filter`
+    pub async fn set_filter(
+        &mut self,
+        filter: impl AttachFilter<<Socket<S> as SetFilter>::Layer>,
+    ) -> Result<&mut Self> {
+        let f = Filter::<backend::Classic, <Socket<S> as SetFilter>::Layer>::from_iter(
+            backend::Classic::contradiction(),
+        );
+        let _ = self.inner.get_mut().attach_filter(f.build()?)?;
+        self.drain_queue().await?;
+        let _ = self.inner.get_mut().attach_filter(filter)?;
+        Ok(self)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<S: PacketSocketKind> AsyncSocket<S> {
+    /// A `Stream` of the packets received on the socket, each received into a buffer of
+    /// `capacity` bytes and truncated to its length
+    ///
+    /// The stream never ends, errors are yielded and receiving continues.
+    pub fn packets(&self, capacity: usize) -> Packets<'_, S> {
+        Packets {
+            socket: self,
+            capacity,
+        }
+    }
+}
+
+/// A `Stream` of the packets received on a `packet(7)` `AsyncSocket`, see `AsyncSocket::packets`
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct Packets<'a, S: PacketSocketKind> {
+    socket: &'a AsyncSocket<S>,
+    capacity: usize,
+}
+
+#[cfg(target_os = "linux")]
+impl<S: PacketSocketKind> Stream for Packets<'_, S> {
+    type Item = Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let mut guard = match self.socket.inner.poll_read_ready(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Pending => return Poll::Pending,
+            };
+            let mut packet = vec![0; self.capacity];
+            let result = guard.get_inner().receive(&mut packet, 0);
+            if would_block(&result) {
+                guard.clear_ready();
+                continue;
+            }
+            return Poll::Ready(Some(result.map(|length| {
+                packet.truncate(length);
+                packet
+            })));
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::option::OptionFor;
+use crate::socket::private::PrivateBasicSocket;
+use crate::socket::{Bind, NewSocket, Socket, SocketKind};
+use bs_system::{Result, SetSocketOption};
+use cfg_if::cfg_if;
+use std::fmt::Debug;
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use crate::option::BindToDevice;
+        use crate::socket::{SOCK_CLOEXEC, SOCK_NONBLOCK};
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+use crate::socket::BasicSocket;
+
+#[cfg(feature = "bs-filter")]
+use {
+    crate::socket::SetFilter,
+    bs_filter::{layer::Layer, AttachFilter},
+    bs_system::SystemError,
+    libc::ENOTCONN,
+    std::marker::PhantomData,
+};
+
+/// A configuration step applied to a newly created socket by `SocketBuilder::build`
+trait Pending<S: SocketKind>: Debug {
+    fn apply(&self, socket: &mut Socket<S>) -> Result<()>;
+}
+
+impl<S: SocketKind, O: SetSocketOption + OptionFor<S>> Pending<S> for O {
+    fn apply(&self, socket: &mut Socket<S>) -> Result<()> {
+        self.set(socket.os()).map(|_| ())
+    }
+}
+
+/// The address a `SocketBuilder` binds its socket to
+#[derive(Debug)]
+struct PendingBind<A>(A);
+
+impl<S: SocketKind, A: Debug> Pending<S> for PendingBind<A>
+where
+    Socket<S>: Bind<Address = A>,
+{
+    fn apply(&self, socket: &mut Socket<S>) -> Result<()> {
+        socket.bind(&self.0).map(|_| ())
+    }
+}
+
+/// The filter a `SocketBuilder` attaches to its socket, for packets starting at `Layer` `L`
+#[cfg(feature = "bs-filter")]
+#[derive(Debug)]
+struct PendingFilter<F, L>(F, PhantomData<L>);
+
+#[cfg(feature = "bs-filter")]
+impl<S: SocketKind, F: AttachFilter<L> + Debug, L: Layer> Pending<S> for PendingFilter<F, L>
+where
+    Socket<S>: SetFilter<Layer = L>,
+{
+    fn apply(&self, socket: &mut Socket<S>) -> Result<()> {
+        let _ = self.0.attach(socket.os())?;
+        // packets queued before the filter was attached never went through it
+        match socket.drain_queue() {
+            // stream sockets have no receive queue until they're connected
+            Ok(_)
+            | Err(SystemError::Os {
+                errno: ENOTCONN, ..
+            }) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Creates a `Socket` and configures it before it's ever bound
+///
+/// Whatever order the builder's methods are called in, `build` creates the socket with its
+/// creation flags, sets the options in the order they were given, attaches the filter and
+/// flushes anything queued before it, and only then binds the socket. Sockets that receive
+/// before they're bound, such as `packet(7)` sockets, never queue an unfiltered packet.
+///
+/// ```no_run
+/// # use bs_socket::option::ReuseAddress;
+/// # use bs_socket::socket::Socket;
+/// # use bs_socket::udp::UdpSocket;
+/// # fn main() -> bs_system::Result<()> {
+/// let s: Socket<UdpSocket> = Socket::builder()
+///     .nonblocking(true)
+///     .option(ReuseAddress::new(true))
+///     .bind("0.0.0.0:4242".parse().unwrap())
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SocketBuilder<S: NewSocket> {
+    cloexec: bool,
+    nonblocking: bool,
+    options: Vec<Box<dyn Pending<S>>>,
+    #[cfg(feature = "bs-filter")]
+    filter: Option<Box<dyn Pending<S>>>,
+    address: Option<Box<dyn Pending<S>>>,
+}
+
+impl<S: NewSocket> Default for SocketBuilder<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: NewSocket> SocketBuilder<S> {
+    /// A builder for a blocking `Socket` with the `O_CLOEXEC` flag set
+    pub fn new() -> Self {
+        Self {
+            cloexec: true,
+            nonblocking: false,
+            options: Vec::new(),
+            #[cfg(feature = "bs-filter")]
+            filter: None,
+            address: None,
+        }
+    }
+
+    /// Whether to set the `O_CLOEXEC` flag, set by default
+    pub fn cloexec(mut self, cloexec: bool) -> Self {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Whether to set the `O_NONBLOCK` flag, unset by default
+    pub fn nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// Sets `option` on the socket once it's created, options are set in the order they're given
+    pub fn option<O: SetSocketOption + OptionFor<S> + 'static>(mut self, option: O) -> Self {
+        self.options.push(Box::new(option));
+        self
+    }
+
+    /// Binds the socket to the interface named `name` (`SO_BINDTODEVICE`) before it's bound to
+    /// an address
+    #[cfg(target_os = "linux")]
+    pub fn device(self, name: impl Into<String>) -> Self {
+        self.option(BindToDevice::new(name))
+    }
+
+    /// Binds the socket to `address`, after every option and the filter are set
+    pub fn bind<A: Debug + 'static>(mut self, address: A) -> Self
+    where
+        Socket<S>: Bind<Address = A>,
+    {
+        self.address = Some(Box::new(PendingBind(address)));
+        self
+    }
+
+    /// Attaches `filter` to the socket before it's bound, replacing any filter given before
+    #[cfg(feature = "bs-filter")]
+    pub fn filter<L: Layer + 'static>(
+        mut self,
+        filter: impl AttachFilter<L> + Debug + 'static,
+    ) -> Self
+    where
+        Socket<S>: SetFilter<Layer = L>,
+    {
+        self.filter = Some(Box::new(PendingFilter(filter, PhantomData)));
+        self
+    }
+
+    /// Creates the socket and applies the builder's configuration, see
+    /// [`SocketBuilder`](struct.SocketBuilder.html) for the order it's applied in
+    pub fn build(self) -> Result<Socket<S>> {
+        let mut socket = self.create()?;
+        for option in &self.options {
+            option.apply(&mut socket)?;
+        }
+        #[cfg(feature = "bs-filter")]
+        if let Some(filter) = &self.filter {
+            filter.apply(&mut socket)?;
+        }
+        if let Some(address) = &self.address {
+            address.apply(&mut socket)?;
+        }
+        Ok(socket)
+    }
+
+    cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            fn create(&self) -> Result<Socket<S>> {
+                let mut flags = 0;
+                if self.cloexec {
+                    flags |= SOCK_CLOEXEC;
+                }
+                if self.nonblocking {
+                    flags |= SOCK_NONBLOCK;
+                }
+                Socket::with_flags(flags)
+            }
+        } else {
+            // the platform has no creation flags, so they're set right after the socket is created
+            fn create(&self) -> Result<Socket<S>> {
+                let mut socket = Socket::with_flags(0)?;
+                if self.cloexec {
+                    let _ = socket.set_cloexec()?;
+                }
+                if self.nonblocking {
+                    let _ = socket.set_nonblocking()?;
+                }
+                Ok(socket)
+            }
+        }
+    }
+}
+
+impl<S: NewSocket> Socket<S> {
+    /// A [`SocketBuilder`](../builder/struct.SocketBuilder.html) for a new `Socket`
+    pub fn builder() -> SocketBuilder<S> {
+        SocketBuilder::new()
+    }
+}
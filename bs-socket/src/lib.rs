@@ -16,30 +16,69 @@
 //!
 //! # Examples
 //!
-//! `listen` (XXX link) is provided for [`TcpSocket`](tcp/struct.TcpSocket.html)
-//! ```ignore
+//! [`listen`](socket/struct.Socket.html#method.listen) is provided for a bound
+//! [`TcpSocket`](tcp/struct.TcpSocket.html)
+//! ```no_run
+//! # use bs_socket::socket::Socket;
+//! # use bs_socket::tcp::TcpSocket;
+//! # const SOME_DATA: &[u8] = b"hello";
+//! # fn main() -> bs_system::Result<()> {
 //! // no need to memorize the arguments for socket(2) :)
 //! let server_socket: Socket<TcpSocket> = Socket::new()?;
 //!
 //! // server_socket is automatically closed when dropped
-//! let client_socket = server_socket.listen(5)?.accept()?;
+//! let client_socket = server_socket
+//!     .bind(&"0.0.0.0:8080".parse().unwrap())?
+//!     .listen(5)?
+//!     .accept()?;
 //!
 //! // client_socket is `Connected`, so we can use the send method to write data to the socket
-//! client_socket.send(SOME_DATA)
+//! let _ = client_socket.send(SOME_DATA, 0)?;
+//! # Ok(())
+//! # }
 //! ```
 //!
 //! The above example will not compile we try to use a socket kind that doesn't support these
 //! methods
-//! ```ignore
+//! ```compile_fail
+//! # use bs_socket::socket::Socket;
+//! # use bs_socket::udp::UdpSocket;
+//! # const SOME_DATA: &[u8] = b"hello";
+//! # fn main() -> bs_system::Result<()> {
 //! // that's gonna cause some problems
 //! let server_socket: Socket<UdpSocket> = Socket::new()?;
 //!
 //! // fortunately it fails at compilation :)
 //! let client_socket = server_socket.listen(5)?.accept()?;
 //!
-//! client_socket.send(SOME_DATA)
+//! client_socket.send(SOME_DATA, 0)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Neither will accepting on a `TcpSocket` that isn't listening
+//! ```compile_fail
+//! # use bs_socket::socket::Socket;
+//! # use bs_socket::tcp::TcpSocket;
+//! # fn main() -> bs_system::Result<()> {
+//! let server_socket: Socket<TcpSocket> = Socket::new()?;
+//! let client_socket = server_socket.bind(&"0.0.0.0:8080".parse().unwrap())?.accept()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Nor receiving on a `TcpSocket` that isn't connected
+//! ```compile_fail
+//! # use bs_socket::socket::{Receive, Socket};
+//! # use bs_socket::tcp::TcpSocket;
+//! # fn main() -> bs_system::Result<()> {
+//! let server_socket: Socket<TcpSocket> = Socket::new()?;
+//! let server_socket = server_socket.bind(&"0.0.0.0:8080".parse().unwrap())?.listen(5)?;
+//! let mut buffer = [0; 1500];
+//! let _ = server_socket.receive(&mut buffer, 0)?;
+//! # Ok(())
+//! # }
 //! ```
-
 #![deny(
     bad_style,
     const_err,
@@ -74,11 +113,40 @@ use cfg_if::cfg_if;
 #[cfg(unix)]
 pub mod socket;
 
+/// `SocketBuilder`, which creates and configures a `Socket` before it's bound
+#[cfg(unix)]
+pub mod builder;
+
+/// Typed socket addresses and their conversion to and from `sockaddr_storage`
+#[cfg(unix)]
+pub mod address;
+
+/// Messages and control messages for `sendmsg(2)` and `recvmsg(2)`
+#[cfg(unix)]
+pub mod message;
+
+/// Typed socket options, restricted to the socket kinds they apply to
+#[cfg(unix)]
+pub mod option;
+
+/// `AsyncSocket`, a `Socket` driven by the `tokio` runtime
+#[cfg(all(unix, feature = "tokio"))]
+pub mod async_socket;
+
 cfg_if! {
     if #[cfg(target_os = "linux")] {
+        /// `SocketKind` for `netlink(7)` sockets, and netlink message framing
+        pub mod netlink;
+
         /// `SocketKind` for `packet(7)` sockets
         pub mod packet;
 
+        /// Memory-mapped `PACKET_RX_RING` and `PACKET_TX_RING` rings for `packet(7)` sockets
+        pub mod ring;
+
+        /// `SocketKind` for `sctp(7)` sockets
+        pub mod sctp;
+
     } else {
         #[doc(hidden)]
         pub mod mock;
@@ -95,28 +163,43 @@ pub mod tcp;
 /// `SocketKind` for `udp(7)` sockets
 pub mod udp;
 
+/// `SocketKind` for `unix(7)` sockets
+pub mod unix;
+
 #[cfg(test)]
 mod tests {
+    use super::address::*;
+    use super::message::*;
+    use super::option::*;
     use super::raw::*;
     use super::socket::*;
     use super::tcp::*;
     use super::udp::*;
+    use super::unix::*;
     use bs_filter::backend::Classic;
-    use bs_filter::idiom::ethernet::ether_type_arp;
+    use bs_filter::idiom::ip::ip_host;
+    use bs_system::{Name, Operation, SystemError};
     use cfg_if::cfg_if;
-    use std::os::unix::io::AsRawFd;
+    use std::convert::TryFrom;
+    use std::net::{Shutdown, SocketAddrV4};
+    use std::os::unix::io::{AsFd, AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
 
     cfg_if! {
         if #[cfg(target_os = "linux")] {
+            use super::netlink::*;
             use super::packet::*;
-            use libc::SOCK_NONBLOCK;
+            use super::sctp::*;
+            use bs_filter::idiom::netlink::message_type;
+            use bs_system::consts::{MSG_NOTIFICATION, SCTP_ASSOC_CHANGE};
+            use bs_system::consts::{NLMSG_ERROR, NLM_F_DUMP, SOCK_DIAG_BY_FAMILY};
+            use libc::{EINVAL, EPROTONOSUPPORT, ESOCKTNOSUPPORT, SOCK_NONBLOCK};
 
             #[test]
             #[allow(unused_results)]
             fn set_classic_filter() {
                 // UDP is arbitrary here
                 let mut s: Socket<UdpSocket> = Socket::new().unwrap();
-                let p = ether_type_arp::<Classic>();
+                let p = ip_host::<Classic, _>("127.0.0.1".parse().unwrap());
                 let f = p.compile().unwrap().build().unwrap();
                 s.set_filter(f).unwrap();
             }
@@ -134,6 +217,574 @@ mod tests {
             }
             */
 
+            #[test]
+            #[allow(unused_results)]
+            fn packet_layer2_socket_bind() {
+                let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                let address = LinkAddress::new(1, libc::ETH_P_ALL as u16);
+                s.bind(&address).unwrap();
+                let local = s.local_address().unwrap();
+                assert_eq!(local.interface_index(), 1);
+                assert_eq!(local.protocol(), address.protocol());
+            }
+
+            #[test]
+            fn socket_set_option_invalid() {
+                let mut s: Socket<UdpSocket> = Socket::new().unwrap();
+                let error = s.set_option(IpTtl::new(0)).map(|_| ()).unwrap_err();
+                assert_eq!(error.raw_os_error(), Some(EINVAL));
+                assert_eq!(error.operation(), Some(Operation::SetOption(Name::IpTtl)));
+                assert_eq!(error.to_string(), "setsockopt(IP_TTL): Invalid argument (EINVAL)");
+            }
+
+            #[test]
+            fn socket_peer_address_unconnected() {
+                let s: Socket<UdpSocket> = Socket::new().unwrap();
+                let error = s.peer_address().unwrap_err();
+                assert_eq!(error.raw_os_error(), Some(libc::ENOTCONN));
+                assert_eq!(error.operation(), Some(Operation::Syscall("getpeername")));
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn socket_set_linux_options() {
+                use super::socket::private::PrivateBasicSocket;
+                let mut s: Socket<UdpSocket> = Socket::new().unwrap();
+                s.set_option(BindToDevice::new("lo"))
+                    .unwrap()
+                    .set_option(UdpSegment::new(1000))
+                    .unwrap()
+                    .set_option(TimestampNs::new(true))
+                    .unwrap();
+                assert_eq!(s.int_option(libc::SOL_UDP, bs_system::consts::UDP_SEGMENT), Ok(1000));
+                assert_eq!(s.int_option(libc::SOL_SOCKET, bs_system::consts::SO_TIMESTAMPNS), Ok(1));
+                s.set_option(BindToDevice::new("")).unwrap();
+
+                let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                s.set_option(PacketAuxData::new(true)).unwrap();
+                assert_eq!(s.int_option(libc::SOL_PACKET, bs_system::consts::PACKET_AUXDATA), Ok(1));
+                assert_eq!(s.option().map(PacketAuxData::value), Ok(true));
+                // reading the statistics resets them
+                let _ = s.option::<PacketStatistics>().unwrap();
+                assert_eq!(s.option::<PacketStatistics>().map(|o| o.drops()), Ok(0));
+            }
+
+            #[test]
+            fn packet_layer2_socket_send_to() {
+                let s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                // a broadcast frame of the local experimental EtherType
+                let mut frame = [0_u8; 60];
+                frame[..6].copy_from_slice(&[0xff; 6]);
+                frame[12..14].copy_from_slice(&0x88b5_u16.to_be_bytes());
+                let address = LinkAddress::new(1, 0x88b5);
+                assert_eq!(s.send_to(&frame, &address, 0).unwrap(), frame.len());
+            }
+
+            #[cfg(feature = "tokio")]
+            #[tokio::test]
+            async fn async_packet_layer2_socket_packets() {
+                use super::async_socket::AsyncSocket;
+                use futures_core::Stream;
+                use std::future::poll_fn;
+                use std::pin::Pin;
+
+                let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                let _ = s.bind(&LinkAddress::new(1, 0x88b5)).unwrap();
+                let s = AsyncSocket::new(s).unwrap();
+                let mut packets = s.packets(64);
+
+                let mut frame = [0_u8; 60];
+                frame[..6].copy_from_slice(&[0xff; 6]);
+                frame[12..14].copy_from_slice(&0x88b5_u16.to_be_bytes());
+                let _ = s.send_to(&frame, &LinkAddress::new(1, 0x88b5), 0).await.unwrap();
+
+                let packet = poll_fn(|cx| Pin::new(&mut packets).poll_next(cx)).await;
+                assert_eq!(packet.unwrap().unwrap(), &frame[..]);
+            }
+
+            fn enable_option(fd: std::os::unix::io::RawFd, level: i32, name: i32) {
+                use std::mem::size_of;
+                let value: i32 = 1;
+                let ptr: *const i32 = &value;
+                let size = size_of::<i32>() as libc::socklen_t;
+                assert_eq!(
+                    unsafe { libc::setsockopt(fd, level, name, ptr as *const libc::c_void, size) },
+                    0
+                );
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn packet_layer2_socket_receive_message() {
+                let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                s.bind(&LinkAddress::new(1, 0x88b5)).unwrap();
+                enable_option(s.as_raw_fd(), libc::SOL_PACKET, bs_system::consts::PACKET_AUXDATA);
+
+                let mut frame = [0_u8; 60];
+                frame[..6].copy_from_slice(&[0xff; 6]);
+                frame[12..14].copy_from_slice(&0x88b5_u16.to_be_bytes());
+                s.send_to(&frame, &LinkAddress::new(1, 0x88b5), 0).unwrap();
+
+                let mut buffer = [0; 64];
+                let received = s
+                    .receive_message(&mut [std::io::IoSliceMut::new(&mut buffer)], 0)
+                    .unwrap();
+                assert_eq!(received.length(), frame.len());
+                assert_eq!(&buffer[..frame.len()], &frame[..]);
+                let address = received.address().unwrap();
+                assert_eq!(address.interface_index(), 1);
+                assert_eq!(address.protocol(), 0x88b5);
+                let auxdata = received.control().iter().find_map(|control| match control {
+                    ControlMessage::PacketAuxData(auxdata) => Some(*auxdata),
+                    _ => None,
+                });
+                let auxdata = auxdata.unwrap();
+                assert_eq!(auxdata.length() as usize, frame.len());
+                assert_eq!(auxdata.vlan_tci(), None);
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn socket_builder_filter_before_bind() {
+                use bs_filter::idiom::transport::dst_port_in;
+                use std::time::Duration;
+
+                let (_server, server_address) = udp_server();
+                let (_other, other_address) = udp_server();
+                let p = dst_port_in::<Classic, _>(&[server_address.port()]);
+                let s: Socket<PacketLayer2Socket> = Socket::builder()
+                    .bind(LinkAddress::new(1, libc::ETH_P_ALL as u16))
+                    .filter(p.compile().unwrap().build().unwrap())
+                    .device("lo")
+                    .option(ReceiveTimeout::new(Some(Duration::from_millis(100))))
+                    .build()
+                    .unwrap();
+
+                let client: Socket<UdpSocket> = Socket::new().unwrap();
+                client.send_to(b"filtered", &other_address, 0).unwrap();
+                client.send_to(b"captured", &server_address, 0).unwrap();
+
+                let mut frames = 0;
+                let mut buffer = [0; 2048];
+                while let Ok(length) = s.receive(&mut buffer, 0) {
+                    assert!(buffer[..length].ends_with(b"captured"));
+                    frames += 1;
+                }
+                assert!(frames > 0);
+
+                // a stream socket has nothing to flush before it's connected
+                let p = dst_port_in::<Classic, _>(&[server_address.port()]);
+                let _: Socket<TcpSocket> = Socket::builder()
+                    .filter(p.compile().unwrap().build().unwrap())
+                    .build()
+                    .unwrap();
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn packet_layer2_socket_rx_ring() {
+                use super::ring::{RxRing, RxRingConfig};
+                use bs_filter::idiom::transport::dst_port_in;
+                use std::time::{Duration, Instant};
+
+                let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                s.bind(&LinkAddress::new(1, libc::ETH_P_ALL as u16)).unwrap();
+                let config = RxRingConfig::new(1 << 16, 4).retire_timeout(Duration::from_millis(10));
+                let mut ring = RxRing::new(s, config).unwrap();
+
+                let (_server, server_address) = udp_server();
+                let (_other, other_address) = udp_server();
+                let p = dst_port_in::<Classic, _>(&[server_address.port()]);
+                ring.socket_mut().set_filter(p.compile().unwrap().build().unwrap()).unwrap();
+
+                let client: Socket<UdpSocket> = Socket::new().unwrap();
+                for _ in 0..4 {
+                    client.send_to(b"captured", &server_address, 0).unwrap();
+                    client.send_to(b"filtered", &other_address, 0).unwrap();
+                }
+
+                let mut captured = 0;
+                let deadline = Instant::now() + Duration::from_secs(2);
+                while captured < 4 && Instant::now() < deadline {
+                    if !ring.wait(Some(Duration::from_millis(100))).unwrap() {
+                        continue;
+                    }
+                    while let Some(block) = ring.next_block() {
+                        for frame in block.frames() {
+                            let data = frame.data();
+                            // ethernet, then an IPv4 header without options, then UDP
+                            assert_eq!(&data[36..38], &server_address.port().to_be_bytes());
+                            assert_eq!(&data[42..], b"captured");
+                            assert_eq!(frame.length(), data.len());
+                            assert!(frame.timestamp().elapsed().is_ok());
+                            captured += 1;
+                        }
+                    }
+                }
+                assert!(captured >= 4);
+            }
+
+            #[test]
+            fn packet_layer2_socket_rx_ring_bad_config() {
+                use super::ring::{RxRing, RxRingConfig};
+                for &config in &[
+                    // unaligned frames
+                    RxRingConfig::new(1 << 16, 4).frame_size(100),
+                    // frames with no room for their header
+                    RxRingConfig::new(1 << 16, 4).frame_size(64),
+                    // blocks not made of pages
+                    RxRingConfig::new(1 << 12 | 1 << 11, 4),
+                    // blocks smaller than their frames
+                    RxRingConfig::new(1 << 12, 4).frame_size(1 << 13),
+                    // more frames than fit in the frame count
+                    RxRingConfig::new(1 << 12, u32::MAX),
+                ] {
+                    let s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                    assert!(matches!(
+                        RxRing::new(s, config),
+                        Err(SystemError::Validation(_))
+                    ));
+                }
+            }
+
+            #[test]
+            fn packet_layer2_socket_tx_ring_bad_config() {
+                use super::ring::{TxRing, TxRingConfig};
+                for &config in &[
+                    // frames with no room for their header
+                    TxRingConfig::new(1 << 12, 2).frame_size(48),
+                    // more frames than fit in the frame count
+                    TxRingConfig::new(1 << 12, u32::MAX),
+                ] {
+                    let s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                    assert!(matches!(
+                        TxRing::new(s, config),
+                        Err(SystemError::Validation(_))
+                    ));
+                }
+            }
+
+            fn tx_ring_receiver() -> Socket<PacketLayer2Socket> {
+                let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                let _ = s.bind(&LinkAddress::new(1, 0x88b6)).unwrap();
+                s
+            }
+
+            fn tx_ring_frame(buffer: &mut [u8], tag: u8) -> usize {
+                buffer[..6].copy_from_slice(&[0xff; 6]);
+                buffer[12..14].copy_from_slice(&0x88b6_u16.to_be_bytes());
+                buffer[14] = tag;
+                60
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn packet_layer2_socket_tx_ring() {
+                use super::ring::{TxRing, TxRingConfig, TxStatus};
+
+                let receiver = tx_ring_receiver();
+                let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                s.bind(&LinkAddress::new(1, 0x88b6)).unwrap();
+                let mut ring = TxRing::new(s, TxRingConfig::new(1 << 12, 2)).unwrap();
+                assert_eq!(ring.frame_count(), 4);
+
+                for tag in 0..3 {
+                    let mut slot = ring.next_slot().unwrap();
+                    let length = tx_ring_frame(slot.buffer_mut(), tag);
+                    slot.submit(length).unwrap();
+                }
+                assert_eq!(ring.status(0), TxStatus::Pending);
+                assert_eq!(ring.status(3), TxStatus::Available);
+                assert_eq!(ring.flush().unwrap(), 3 * 60);
+
+                let mut tags = Vec::new();
+                let mut buffer = [0; 64];
+                while tags.len() < 3 {
+                    let (n, _) = receiver.receive_from(&mut buffer, 0).unwrap();
+                    assert_eq!(n, 60);
+                    if !tags.contains(&buffer[14]) {
+                        tags.push(buffer[14]);
+                    }
+                }
+                tags.sort();
+                assert_eq!(tags, [0, 1, 2]);
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn packet_layer2_socket_tx_ring_padded_blocks() {
+                use super::ring::{TxRing, TxRingConfig};
+
+                let receiver = tx_ring_receiver();
+                let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                s.bind(&LinkAddress::new(1, 0x88b6)).unwrap();
+                // 2 slots of each block, followed by 1024 octets of padding
+                let config = TxRingConfig::new(1 << 12, 2).frame_size(1536);
+                let mut ring = TxRing::new(s, config).unwrap();
+                assert_eq!(ring.frame_count(), 4);
+
+                for tag in 0..4 {
+                    let mut slot = ring.next_slot().unwrap();
+                    let length = tx_ring_frame(slot.buffer_mut(), tag);
+                    slot.submit(length).unwrap();
+                }
+                assert_eq!(ring.flush().unwrap(), 4 * 60);
+
+                let mut tags = Vec::new();
+                let mut buffer = [0; 64];
+                while tags.len() < 4 {
+                    let (n, _) = receiver.receive_from(&mut buffer, 0).unwrap();
+                    assert_eq!(n, 60);
+                    if !tags.contains(&buffer[14]) {
+                        tags.push(buffer[14]);
+                    }
+                }
+                tags.sort();
+                assert_eq!(tags, [0, 1, 2, 3]);
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn packet_layer2_socket_tx_ring_rejected() {
+                use super::ring::{TxRing, TxRingConfig, TxStatus};
+
+                let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                s.bind(&LinkAddress::new(1, 0x88b6)).unwrap();
+                let mut ring = TxRing::new(s, TxRingConfig::new(1 << 12, 1)).unwrap();
+
+                // shorter than an ethernet header
+                ring.next_slot().unwrap().submit(4).unwrap();
+                assert!(ring.flush().is_err());
+                assert_eq!(ring.status(0), TxStatus::Rejected);
+
+                let mut slot = ring.rejected().unwrap();
+                assert!(slot.was_rejected());
+                let length = tx_ring_frame(slot.buffer_mut(), 0);
+                slot.submit(length).unwrap();
+                assert_eq!(ring.flush().unwrap(), 60);
+                assert!(ring.rejected().is_none());
+
+                let capacity = ring.frame_capacity();
+                assert!(ring.next_slot().unwrap().submit(capacity + 1).is_err());
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn packet_layer2_socket_fanout_steering() {
+                use bs_filter::idiom::offset_equals_u8;
+                use bs_filter::Steering;
+                use std::time::{Duration, Instant};
+
+                let fanout =
+                    Fanout::new(std::process::id() as u16, FanoutMode::Classic).ignore_outgoing(true);
+                let mut members = Vec::new();
+                for _ in 0..2 {
+                    let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                    s.bind(&LinkAddress::new(1, 0x88b7)).unwrap();
+                    s.join_fanout(&fanout).unwrap();
+                    members.push(s);
+                }
+                // steering programs see incoming packets from their network layer header, which
+                // is where the tag starts. frames sent on the loopback interface come back in as
+                // incoming ones, and their outgoing copies, which start at their link layer
+                // header, are ignored by the group
+                let steering = Steering::<Classic, _>::new(0).route(offset_equals_u8(0, 1), 1);
+                members[0].set_fanout_program(steering.compile().unwrap()).unwrap();
+
+                let sender: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                let mut frame = [0_u8; 60];
+                frame[..6].copy_from_slice(&[0xff; 6]);
+                frame[12..14].copy_from_slice(&0x88b7_u16.to_be_bytes());
+                for tag in 0..2 {
+                    frame[14] = tag;
+                    sender.send_to(&frame, &LinkAddress::new(1, 0x88b7), 0).unwrap();
+                }
+
+                let mut received = [0; 2];
+                let mut buffer = [0; 64];
+                let deadline = Instant::now() + Duration::from_secs(2);
+                while received.contains(&0) && Instant::now() < deadline {
+                    for (index, member) in members.iter().enumerate() {
+                        if let Ok((n, _)) = member.receive_from(&mut buffer, MSG_DONTWAIT) {
+                            assert_eq!(n, frame.len());
+                            assert_eq!(buffer[14] as usize, index);
+                            received[index] += 1;
+                        }
+                    }
+                }
+                assert!(!received.contains(&0));
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn packet_layer2_socket_fanout_mismatch() {
+                let id = (std::process::id() as u16).wrapping_add(1);
+                let mut first: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                first.join_fanout(&Fanout::new(id, FanoutMode::Hash).defrag(true)).unwrap();
+                let mut second: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                second.join_fanout(&Fanout::new(id, FanoutMode::Hash).defrag(true)).unwrap();
+                let mut other: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                assert!(other.join_fanout(&Fanout::new(id, FanoutMode::LoadBalance)).is_err());
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn packet_layer2_socket_bind_interface() {
+                let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                s.bind_interface("lo", 0x88b5).unwrap();
+                let local = s.local_address().unwrap();
+                assert_eq!(local.interface_index(), interface_index("lo").unwrap());
+                assert_eq!(local.protocol(), 0x88b5);
+                assert!(s.bind_interface("no such interface", 0x88b5).is_err());
+                assert!(s.bind_interface("l\0o", 0x88b5).is_err());
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn packet_layer2_socket_membership() {
+                fn flags() -> u32 {
+                    let flags = std::fs::read_to_string("/sys/class/net/lo/flags").unwrap();
+                    u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).unwrap()
+                }
+
+                let mut s: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                let interface_index = interface_index("lo").unwrap();
+                let mut promiscuous = s
+                    .add_membership(Membership::Promiscuous { interface_index })
+                    .unwrap();
+                assert_ne!(flags() & libc::IFF_PROMISC as u32, 0);
+                // the socket can still be configured while the guard holds the membership
+                let p = ip_host::<Classic, _>("127.0.0.1".parse().unwrap());
+                promiscuous.set_filter(p.compile().unwrap().build().unwrap()).unwrap();
+                drop(promiscuous);
+                assert_eq!(flags() & libc::IFF_PROMISC as u32, 0);
+
+                let address = eui48::MacAddress::new([0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb]);
+                let mut multicast = s
+                    .add_membership(Membership::Multicast {
+                        interface_index,
+                        address,
+                    })
+                    .unwrap();
+                let all_multicast = multicast
+                    .add_membership(Membership::AllMulticast { interface_index })
+                    .unwrap();
+                assert_ne!(flags() & libc::IFF_ALLMULTI as u32, 0);
+                all_multicast.drop_membership().unwrap();
+                assert_eq!(flags() & libc::IFF_ALLMULTI as u32, 0);
+                drop(multicast);
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn packet_layer2_socket_ether_type() {
+                let mut s: Socket<PacketLayer2Socket<EtherType<0x88b8>>> = Socket::new().unwrap();
+                s.bind_interface_index(1, 0x88b8).unwrap();
+
+                let sender: Socket<PacketLayer2Socket> = Socket::new().unwrap();
+                let mut frame = [0_u8; 60];
+                frame[..6].copy_from_slice(&[0xff; 6]);
+                for &ether_type in &[0x88b9_u16, 0x88b8] {
+                    frame[12..14].copy_from_slice(&ether_type.to_be_bytes());
+                    sender.send_to(&frame, &LinkAddress::new(1, ether_type), 0).unwrap();
+                }
+
+                let mut buffer = [0; 64];
+                let (n, from) = s.receive_from(&mut buffer, 0).unwrap();
+                assert_eq!(n, frame.len());
+                assert_eq!(&buffer[12..14], &0x88b8_u16.to_be_bytes());
+                assert_eq!(from.protocol(), 0x88b8);
+            }
+
+            #[test]
+            fn udp_socket_receive_message() {
+                let (server, server_address) = udp_server();
+                enable_option(server.as_raw_fd(), libc::IPPROTO_IP, libc::IP_PKTINFO);
+                enable_option(server.as_raw_fd(), libc::IPPROTO_IP, libc::IP_RECVTTL);
+                enable_option(server.as_raw_fd(), libc::SOL_SOCKET, bs_system::consts::SO_TIMESTAMPNS);
+
+                let client: Socket<UdpSocket> = Socket::new().unwrap();
+                let message = Message::new(&[b"ping"])
+                    .to(server_address)
+                    .with_control(ControlMessage::IpTtl(7));
+                let _ = client.send_message(&message, 0).unwrap();
+
+                let mut buffer = [0; 8];
+                let received = server
+                    .receive_message(&mut [std::io::IoSliceMut::new(&mut buffer)], 0)
+                    .unwrap();
+                assert_eq!(received.length(), 4);
+                assert!(!received.is_truncated());
+                assert!(!received.is_control_truncated());
+                let client_port = client.local_address().unwrap().port();
+                assert_eq!(received.address().map(|a| a.port()), Some(client_port));
+
+                let control = received.control();
+                assert!(control.contains(&ControlMessage::IpTtl(7)));
+                assert!(control.contains(&ControlMessage::IpPacketInfo {
+                    interface_index: 1,
+                    local: *server_address.ip(),
+                    destination: *server_address.ip(),
+                }));
+                assert!(control.iter().any(|control| match control {
+                    ControlMessage::Timestamp(time) => time.elapsed().is_ok(),
+                    _ => false,
+                }));
+            }
+
+            #[test]
+            fn udp_socket_receive_messages() {
+                let (server, server_address) = udp_server();
+                enable_option(server.as_raw_fd(), libc::IPPROTO_IP, libc::IP_PKTINFO);
+                let (client, client_address) = udp_server();
+                let payloads: [&[u8]; 5] = [b"one", b"two", b"three", b"four", b"truncated"];
+                for payload in payloads.iter() {
+                    let _ = client.send_to(payload, &server_address, 0).unwrap();
+                }
+
+                let mut pool = ReceivePool::new(4, 6);
+                let timeout = Some(std::time::Duration::from_millis(100));
+                assert_eq!(server.receive_messages(&mut pool, 0, timeout).unwrap(), 4);
+                assert_eq!(pool.len(), 4);
+                for (packet, payload) in pool.iter().zip(payloads.iter()) {
+                    assert_eq!(packet.data(), *payload);
+                    assert!(!packet.is_truncated());
+                    assert_eq!(packet.address().unwrap(), client_address);
+                    assert!(packet.control().any(|control| match control {
+                        ControlMessage::IpPacketInfo { interface_index, .. } => interface_index == 1,
+                        _ => false,
+                    }));
+                }
+
+                // the pool is reused, and the rest of the queue fills only part of it
+                assert_eq!(server.receive_messages(&mut pool, MSG_DONTWAIT, None).unwrap(), 1);
+                let packet = pool.get(0).unwrap();
+                assert!(packet.is_truncated());
+                assert_eq!(packet.data(), b"trunca");
+                assert!(pool.get(1).is_none());
+
+                assert!(server.receive_messages(&mut pool, MSG_DONTWAIT, None).is_err());
+                assert!(pool.is_empty());
+            }
+
+            #[test]
+            fn udp_socket_send_messages() {
+                let (server, server_address) = udp_server();
+                let client: Socket<UdpSocket> = Socket::new().unwrap();
+                let payloads: [&[u8]; 3] = [b"one", b"two", b"three"];
+                let messages: Vec<Message<SocketAddrV4>> = payloads
+                    .iter()
+                    .map(|payload| Message::new(&[payload]).to(server_address))
+                    .collect();
+                assert_eq!(client.send_messages(&messages, 0).unwrap(), 3);
+
+                let mut buffer = [0; 8];
+                for payload in payloads.iter() {
+                    let n = server.receive(&mut buffer, 0).unwrap();
+                    assert_eq!(&buffer[..n], *payload);
+                }
+            }
+
             #[test]
             fn packet_layer2_socket_flags() {
                 let mut s: Socket<PacketLayer2Socket> = Socket::plain().unwrap();
@@ -221,6 +872,284 @@ mod tests {
                 let s: Socket<UdpSocket> = Socket::plain_nonblocking().unwrap();
                 assert!(s.as_raw_fd() >= 0);
             }
+
+            fn sctp_socket<S: NewSocket>() -> Option<Socket<S>> {
+                match Socket::new() {
+                    Ok(s) => Some(s),
+                    // the sctp module isn't loadable everywhere
+                    Err(SystemError::Os { errno: EPROTONOSUPPORT, .. })
+                    | Err(SystemError::Os { errno: ESOCKTNOSUPPORT, .. }) => None,
+                    Err(e) => panic!("{:?}", e),
+                }
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn sctp_socket_one_to_one() {
+                let mut server: Socket<SctpSocket> = match sctp_socket() {
+                    Some(s) => s,
+                    None => return,
+                };
+                server.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+                server.listen(1).unwrap();
+
+                let mut client: Socket<SctpSocket> = sctp_socket().unwrap();
+                client.connect(&server.local_address().unwrap()).unwrap();
+                let peer = server.accept().unwrap();
+                client.send(b"ping", 0).unwrap();
+                let mut buffer = [0; 4];
+                assert_eq!(peer.receive(&mut buffer, 0).unwrap(), 4);
+                assert_eq!(&buffer, b"ping");
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn sctp_socket_bindx_stream_info() {
+                let mut server: Socket<SctpSocket<OneToMany>> = match sctp_socket() {
+                    Some(s) => s,
+                    None => return,
+                };
+                let addresses = ["127.0.0.1:0".parse().unwrap(), "127.0.0.2:0".parse().unwrap()];
+                server.bindx(&addresses).unwrap();
+                server
+                    .set_events(&[SctpEvent::DataIo, SctpEvent::Association])
+                    .unwrap()
+                    .listen(5)
+                    .unwrap();
+                let port = server.local_address().unwrap().port();
+
+                let mut client: Socket<SctpSocket<OneToMany>> = sctp_socket().unwrap();
+                let peers = [
+                    SocketAddrV4::new("127.0.0.2".parse().unwrap(), port),
+                    SocketAddrV4::new("127.0.0.1".parse().unwrap(), port),
+                ];
+                assert!(client.connectx(&peers).unwrap() > 0);
+                let info = SctpInfo::new(1).with_ppid(42);
+                let message = Message::new(&[b"ping"])
+                    .to(peers[0])
+                    .with_control(ControlMessage::SctpInfo(info));
+                assert_eq!(client.send_message(&message, 0).unwrap(), 4);
+
+                // the association coming up is notified before the message
+                let mut buffer = [0; 64];
+                let received = server
+                    .receive_message(&mut [std::io::IoSliceMut::new(&mut buffer)], 0)
+                    .unwrap();
+                assert_ne!(received.flags() & MSG_NOTIFICATION, 0);
+                match Notification::parse(&buffer[..received.length()]).unwrap() {
+                    Notification::AssociationChange { state, .. } => {
+                        assert_eq!(state, AssociationState::Up)
+                    }
+                    other => panic!("{:?}", other),
+                }
+
+                let received = server
+                    .receive_message(&mut [std::io::IoSliceMut::new(&mut buffer)], 0)
+                    .unwrap();
+                assert_eq!(received.flags() & MSG_NOTIFICATION, 0);
+                assert_eq!(&buffer[..received.length()], b"ping");
+                let info = received.control().iter().find_map(|control| match control {
+                    ControlMessage::SctpInfo(info) => Some(*info),
+                    _ => None,
+                });
+                let info = info.unwrap();
+                assert_eq!(info.stream(), 1);
+                assert_eq!(info.ppid(), 42);
+            }
+
+            #[test]
+            fn sctp_notification_truncated() {
+                let mut data = SCTP_ASSOC_CHANGE.to_ne_bytes().to_vec();
+                data.extend_from_slice(&[0; 6]);
+                assert_eq!(Notification::parse(&data), Err(SystemError::from_raw_os_error(EINVAL)));
+                assert_eq!(Notification::parse(&[]), Err(SystemError::from_raw_os_error(EINVAL)));
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn unix_stream_socket_abstract_address() {
+                let name = format!("bs-{}-stream", std::process::id());
+                let address = UnixAddress::abstract_name(name.as_bytes()).unwrap();
+                let mut server: Socket<UnixStreamSocket> = Socket::new().unwrap();
+                server.bind(&address).unwrap();
+                server.listen(1).unwrap();
+                assert_eq!(server.local_address().unwrap(), address);
+
+                let mut client: Socket<UnixStreamSocket> = Socket::new().unwrap();
+                client.connect(&address).unwrap();
+                let peer = server.accept().unwrap();
+                assert_eq!(client.peer_address().unwrap(), address);
+                client.send(b"ping", 0).unwrap();
+                let mut buffer = [0; 4];
+                assert_eq!(peer.receive(&mut buffer, 0).unwrap(), 4);
+                assert_eq!(&buffer, b"ping");
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn unix_datagram_socket_send_to() {
+                let name = format!("bs-{}-datagram", std::process::id());
+                let address = UnixAddress::abstract_name(name.as_bytes()).unwrap();
+                let mut server: Socket<UnixDatagramSocket> = Socket::new().unwrap();
+                server.bind(&address).unwrap();
+
+                let client: Socket<UnixDatagramSocket> = Socket::new().unwrap();
+                client.send_to(b"ping", &address, 0).unwrap();
+                let mut buffer = [0; 8];
+                let (n, from) = server.receive_from(&mut buffer, 0).unwrap();
+                assert_eq!(&buffer[..n], b"ping");
+                assert!(from.is_unnamed());
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn unix_socket_credentials() {
+                let (client, mut server): (Socket<UnixDatagramSocket>, _) = Socket::pair().unwrap();
+                assert_eq!(server.peer_credentials().unwrap(), Credentials::current());
+
+                server.set_pass_credentials(true).unwrap();
+                let message = Message::<UnixAddress>::new(&[b"ping"])
+                    .with_control(ControlMessage::Credentials(Credentials::current()));
+                client.send_message(&message, 0).unwrap();
+                let mut buffer = [0; 4];
+                let received = server
+                    .receive_message(&mut [std::io::IoSliceMut::new(&mut buffer)], 0)
+                    .unwrap();
+                assert_eq!(
+                    received.control(),
+                    &[ControlMessage::Credentials(Credentials::current())]
+                );
+            }
+
+            #[test]
+            fn netlink_address_storage() {
+                let address = NetlinkAddress::new(1234, 1 << 3);
+                let (storage, length) = address.to_storage();
+                assert_eq!(NetlinkAddress::from_storage(&storage, length).unwrap(), address);
+            }
+
+            #[test]
+            fn netlink_message_roundtrip() {
+                let message = NetlinkMessage::new(libc::RTM_NEWLINK)
+                    .with_flags(NLM_F_DUMP)
+                    .with_sequence(7)
+                    .with_payload(&[1, 2, 3])
+                    .with_attribute(&Attribute::string(libc::IFLA_IFNAME, "bs0"))
+                    .with_attribute(&Attribute::nested(1, &[Attribute::u32(2, 42)]));
+                let data = [message.to_bytes(), message.to_bytes()].concat();
+                let parsed = NetlinkMessage::parse(&data).unwrap();
+                assert_eq!(parsed, vec![message.clone(), message]);
+
+                // the 3 octets of payload are padded to 4
+                let attributes = parsed[0].attributes(3).unwrap();
+                assert_eq!(attributes.len(), 2);
+                assert_eq!(attributes[0].as_str(), Some("bs0"));
+                assert!(attributes[1].is_nested());
+                assert_eq!(attributes[1].type_(), 1);
+                let nested = attributes[1].attributes().unwrap();
+                assert_eq!(nested[0].as_u32(), Some(42));
+            }
+
+            #[test]
+            fn netlink_message_malformed() {
+                let data = NetlinkMessage::new(libc::RTM_NEWLINK)
+                    .with_payload(&[0; 8])
+                    .to_bytes();
+                let result = NetlinkMessage::parse(&data[..data.len() - 1]);
+                assert_eq!(result, Err(SystemError::from_raw_os_error(EINVAL)));
+                let result = NetlinkMessage::parse(&[0; 16]);
+                assert_eq!(result, Err(SystemError::from_raw_os_error(EINVAL)));
+                assert_eq!(Attribute::parse(&[8, 0, 1, 0]), Err(SystemError::from_raw_os_error(EINVAL)));
+            }
+
+            fn netlink_links(s: &Socket<NetlinkSocket>) -> Vec<String> {
+                // struct ifinfomsg, empty to dump every link
+                let request = NetlinkMessage::new(libc::RTM_GETLINK)
+                    .with_flags(NLM_F_DUMP)
+                    .with_payload(&[0; 16]);
+                let links = s.request(request).unwrap();
+                links
+                    .iter()
+                    .flat_map(|link| link.attributes(16).unwrap())
+                    .filter(|attribute| attribute.type_() == libc::IFLA_IFNAME)
+                    .map(|attribute| attribute.as_str().unwrap().to_string())
+                    .collect()
+            }
+
+            #[test]
+            fn netlink_route_socket_dump() {
+                let s: Socket<NetlinkSocket> = Socket::new().unwrap();
+                assert!(netlink_links(&s).contains(&"lo".to_string()));
+            }
+
+            #[test]
+            fn netlink_route_socket_error() {
+                let s: Socket<NetlinkSocket> = Socket::new().unwrap();
+                // struct ifinfomsg for an interface index nothing uses
+                let mut link = [0; 16];
+                link[4..8].copy_from_slice(&i32::MAX.to_ne_bytes());
+                let request = NetlinkMessage::new(libc::RTM_GETLINK).with_payload(&link);
+                assert_eq!(s.request(request), Err(SystemError::from_raw_os_error(libc::ENODEV)));
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn netlink_route_socket_groups() {
+                let mut s: Socket<NetlinkSocket> = Socket::new().unwrap();
+                s.bind(&NetlinkAddress::new(0, 0)).unwrap();
+                assert_ne!(s.local_address().unwrap().port(), 0);
+                s.join_group(libc::RTNLGRP_LINK).unwrap();
+                s.leave_group(libc::RTNLGRP_LINK).unwrap();
+            }
+
+            #[test]
+            #[allow(unused_results)]
+            fn netlink_route_socket_filter() {
+                let mut s: Socket<NetlinkSocket> = Socket::new().unwrap();
+                // struct ifinfomsg for the loopback interface
+                let mut link = [0; 16];
+                link[4..8].copy_from_slice(&1_i32.to_ne_bytes());
+                let request = NetlinkMessage::new(libc::RTM_GETLINK).with_payload(&link);
+                assert_eq!(s.request(request.clone()).unwrap().len(), 1);
+
+                // the link is dropped, and only the acknowledgement gets through
+                let p = message_type::<Classic>(NLMSG_ERROR);
+                s.set_filter(p.compile().unwrap().build().unwrap()).unwrap();
+                assert!(s.request(request).unwrap().is_empty());
+            }
+
+            #[test]
+            fn netlink_generic_socket_family_id() {
+                let s: Socket<NetlinkSocket<Generic>> = Socket::new().unwrap();
+                assert_eq!(s.family_id("nlctrl").unwrap(), 16);
+                assert_eq!(s.family_id("bs-nonexistent"), Err(SystemError::from_raw_os_error(libc::ENOENT)));
+            }
+
+            #[test]
+            fn netlink_sock_diag_socket_dump() {
+                let server: Socket<TcpSocket> = Socket::new().unwrap();
+                let server = server.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+                let server = server.listen(1).unwrap();
+                let port = server.local_address().unwrap().port();
+
+                let s: Socket<NetlinkSocket<SockDiag>> = Socket::new().unwrap();
+                // struct inet_diag_req_v2 for listening TCP sockets over IPv4
+                let mut request = vec![libc::AF_INET as u8, libc::IPPROTO_TCP as u8, 0, 0];
+                request.extend_from_slice(&(1_u32 << 10).to_ne_bytes());
+                request.extend_from_slice(&[0; 48]);
+                let request = NetlinkMessage::new(SOCK_DIAG_BY_FAMILY)
+                    .with_flags(NLM_F_DUMP)
+                    .with_payload(&request);
+                // struct inet_diag_msg, starting with the family, state, timer, retransmits and
+                // the source port in network byte order
+                let ports: Vec<u16> = s
+                    .request(request)
+                    .unwrap()
+                    .iter()
+                    .map(|socket| u16::from_be_bytes([socket.payload()[4], socket.payload()[5]]))
+                    .collect();
+                assert!(ports.contains(&port));
+            }
         }
     }
 
@@ -236,6 +1165,104 @@ mod tests {
         assert!(s.as_raw_fd() >= 0);
     }
 
+    #[test]
+    #[allow(unused_results)]
+    fn raw_socket_header_included() {
+        let s: Socket<RawSocket> = Socket::new().unwrap();
+        assert!(s.header_included().unwrap());
+
+        let mut s: Socket<RawSocket<Proto<253>>> = Socket::new().unwrap();
+        assert!(!s.header_included().unwrap());
+        s.set_header_included(true).unwrap();
+        assert!(s.header_included().unwrap());
+    }
+
+    #[test]
+    #[allow(unused_results)]
+    fn raw_socket_icmp_echo() {
+        let s: Socket<RawSocket<Icmp>> = Socket::new().unwrap();
+        // an echo request, identified by the process id
+        let id = (std::process::id() as u16).to_be_bytes();
+        let mut request = [8, 0, 0, 0, id[0], id[1], 0, 1, b'b', b's'];
+        let sum = request
+            .chunks(2)
+            .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+            .sum::<u32>();
+        let checksum = !((sum & 0xffff) + (sum >> 16)) as u16;
+        request[2..4].copy_from_slice(&checksum.to_be_bytes());
+        s.send_to(&request, &"127.0.0.1:0".parse().unwrap(), 0)
+            .unwrap();
+
+        // the request is looped back as well, followed by the reply
+        let mut buffer = [0; 64];
+        let reply = (0..8).find_map(|_| {
+            let (n, from) = s.receive_from(&mut buffer, 0).unwrap();
+            // an IPv4 header without options, then ICMP
+            let icmp = &buffer[20..n];
+            if icmp[0] == 0 && icmp[4..6] == id {
+                Some((from, icmp.to_vec()))
+            } else {
+                None
+            }
+        });
+        let (from, icmp) = reply.unwrap();
+        assert_eq!(*from.ip(), std::net::Ipv4Addr::LOCALHOST);
+        assert_eq!(&icmp[8..], b"bs");
+    }
+
+    #[test]
+    #[allow(unused_results)]
+    fn raw6_socket_checksum_offset() {
+        let mut s: Socket<Raw6Socket<Proto<253>>> = Socket::new().unwrap();
+        assert_eq!(s.checksum_offset().unwrap(), None);
+        s.set_checksum_offset(Some(2)).unwrap();
+        assert_eq!(s.checksum_offset().unwrap(), Some(2));
+        s.set_checksum_offset(None).unwrap();
+        assert_eq!(s.checksum_offset().unwrap(), None);
+    }
+
+    #[test]
+    #[allow(unused_results)]
+    fn udp6_socket_send_to() {
+        let mut server: Socket<Udp6Socket> = Socket::new().unwrap();
+        server.set_v6_only(true).unwrap();
+        assert!(server.v6_only().unwrap());
+        server.bind(&"[::1]:0".parse().unwrap()).unwrap();
+        let server_address = server.local_address().unwrap();
+
+        let client: Socket<Udp6Socket> = Socket::new().unwrap();
+        client.send_to(b"ping", &server_address, 0).unwrap();
+        let mut buffer = [0; 4];
+        let (n, from) = server.receive_from(&mut buffer, 0).unwrap();
+        assert_eq!(n, 4);
+        assert_eq!(&buffer, b"ping");
+        assert_eq!(*from.ip(), std::net::Ipv6Addr::LOCALHOST);
+    }
+
+    #[test]
+    #[allow(unused_results)]
+    fn tcp6_socket_accept() {
+        let mut server: Socket<Tcp6Socket> = Socket::new().unwrap();
+        server.set_v6_only(false).unwrap();
+        let server = server.bind(&"[::]:0".parse().unwrap()).unwrap();
+        let server = server.listen(1).unwrap();
+        let port = server.local_address().unwrap().port();
+
+        // dual-stack sockets accept IPv4 connections through IPv4-mapped addresses
+        let client: Socket<TcpSocket> = Socket::new().unwrap();
+        let client = client
+            .connect(&SocketAddrV4::new(std::net::Ipv4Addr::LOCALHOST, port))
+            .unwrap();
+        let peer = server.accept().unwrap();
+        let mapped = std::net::Ipv4Addr::LOCALHOST.to_ipv6_mapped();
+        assert_eq!(*peer.peer_address().unwrap().ip(), mapped);
+
+        client.send(b"ping", 0).unwrap();
+        let mut buffer = [0; 4];
+        assert_eq!(peer.receive(&mut buffer, 0).unwrap(), 4);
+        assert_eq!(&buffer, b"ping");
+    }
+
     #[test]
     fn udp_socket_new() {
         let s: Socket<UdpSocket> = Socket::new().unwrap();
@@ -258,4 +1285,493 @@ mod tests {
         let s: Socket<TcpSocket> = Socket::plain().unwrap();
         assert!(s.as_raw_fd() >= 0);
     }
+
+    #[test]
+    #[allow(unused_results)]
+    fn udp_socket_bind() {
+        let mut s: Socket<UdpSocket> = Socket::new().unwrap();
+        s.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let local = s.local_address().unwrap();
+        assert_eq!(
+            local.ip(),
+            &"127.0.0.1".parse::<std::net::Ipv4Addr>().unwrap()
+        );
+        assert_ne!(local.port(), 0);
+    }
+
+    #[test]
+    #[allow(unused_results)]
+    fn udp_socket_connect() {
+        let mut server: Socket<UdpSocket> = Socket::new().unwrap();
+        server.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let server_address = server.local_address().unwrap();
+
+        let mut client: Socket<UdpSocket> = Socket::new().unwrap();
+        client.connect(&server_address).unwrap();
+        assert_eq!(client.peer_address().unwrap(), server_address);
+    }
+
+    #[test]
+    fn udp_socket_peer_address_unconnected() {
+        let s: Socket<UdpSocket> = Socket::new().unwrap();
+        assert!(s.peer_address().is_err());
+    }
+
+    #[test]
+    fn socket_address_v4_storage() {
+        let address: SocketAddrV4 = "10.1.2.3:4567".parse().unwrap();
+        let (storage, length) = address.to_storage();
+        assert_eq!(
+            SocketAddrV4::from_storage(&storage, length).unwrap(),
+            address
+        );
+        assert!(std::net::SocketAddrV6::from_storage(&storage, length).is_err());
+    }
+
+    #[test]
+    fn unix_address_storage() {
+        for address in &[
+            UnixAddress::unnamed(),
+            UnixAddress::pathname("/tmp/bs.sock").unwrap(),
+            UnixAddress::abstract_name(b"bs").unwrap(),
+        ] {
+            let (storage, length) = address.to_storage();
+            assert_eq!(
+                &UnixAddress::from_storage(&storage, length).unwrap(),
+                address
+            );
+        }
+        let (storage, _) = UnixAddress::unnamed().to_storage();
+        assert!(UnixAddress::from_storage(&storage, 0).unwrap().is_unnamed());
+    }
+
+    #[test]
+    fn tcp_socket_failed_transitions() {
+        use std::time::Duration;
+
+        let server: Socket<TcpSocket> = Socket::new().unwrap();
+        let server = server.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let server_address = server.local_address().unwrap();
+
+        // the socket is handed back, and can be bound again
+        let socket: Socket<TcpSocket> = Socket::new().unwrap();
+        let error = socket.bind(&server_address).unwrap_err();
+        assert_eq!(error.error().raw_os_error(), Some(libc::EADDRINUSE));
+        assert_eq!(error.error().operation(), Some(Operation::Syscall("bind")));
+        let socket = error.into_socket();
+        assert!(socket.bind(&"127.0.0.1:0".parse().unwrap()).is_ok());
+
+        // a nonblocking connection is connected once it's established
+        let server = server.listen(1).unwrap();
+        let mut client: Socket<TcpSocket> = Socket::nonblocking().unwrap();
+        let client = loop {
+            match client.connect(&server_address) {
+                Ok(client) => break client,
+                Err(error) => {
+                    let errno = error.error().raw_os_error();
+                    assert!(errno == Some(libc::EINPROGRESS) || errno == Some(libc::EALREADY));
+                    client = error.into_socket();
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            }
+        };
+        assert_eq!(client.peer_address().unwrap(), server_address);
+        assert!(server.accept().is_ok());
+    }
+
+    #[test]
+    #[allow(unused_results)]
+    fn tcp_socket_lifecycle() {
+        let server: Socket<TcpSocket> = Socket::new().unwrap();
+        let server = server
+            .bind(&"127.0.0.1:0".parse().unwrap())
+            .unwrap()
+            .listen(1)
+            .unwrap();
+        let server_address = server.local_address().unwrap();
+
+        let client: Socket<TcpSocket> = Socket::new().unwrap();
+        let mut client = client.connect(&server_address).unwrap();
+        let mut peer = server.accept().unwrap();
+        assert_ne!(peer.as_raw_fd(), server.as_raw_fd());
+        assert_eq!(
+            peer.peer_address().unwrap(),
+            client.local_address().unwrap()
+        );
+        assert_eq!(client.peer_address().unwrap(), server_address);
+
+        let mut buffer = [0; 4];
+        assert_eq!(client.send(b"ping", 0).unwrap(), 4);
+        assert_eq!(peer.receive(&mut buffer, 0).unwrap(), 4);
+        assert_eq!(&buffer, b"ping");
+
+        // closing the client's writing half is seen as EOF by the peer, which can still write
+        client.shutdown(Shutdown::Write).unwrap();
+        assert_eq!(peer.receive(&mut buffer, 0).unwrap(), 0);
+        assert_eq!(peer.send(b"pong", 0).unwrap(), 4);
+        assert_eq!(client.receive(&mut buffer, 0).unwrap(), 4);
+        assert_eq!(&buffer, b"pong");
+
+        peer.shutdown(Shutdown::Both).unwrap();
+    }
+
+    #[test]
+    fn tcp_socket_into_raw_fd() {
+        let s: Socket<TcpSocket> = Socket::new().unwrap();
+        let fd = s.into_raw_fd();
+        // the fd is still open after the socket was consumed
+        assert!(unsafe { libc::fcntl(fd, libc::F_GETFD) } >= 0);
+        let _ = unsafe { Socket::<TcpSocket>::from_raw_fd(fd) };
+    }
+
+    #[test]
+    fn tcp_socket_into_owned_fd() {
+        let s: Socket<TcpSocket> = Socket::new().unwrap();
+        let fd = s.as_fd().as_raw_fd();
+        let owned = OwnedFd::from(s);
+        assert_eq!(owned.as_raw_fd(), fd);
+        // the fd is still open after the socket was consumed, and closed with the `OwnedFd`
+        assert!(unsafe { libc::fcntl(fd, libc::F_GETFD) } >= 0);
+        // and it's only taken back by a socket of its kind, in its state
+        let error = Socket::<UdpSocket>::try_from(owned).unwrap_err();
+        assert_eq!(error.raw_os_error(), Some(libc::EPROTOTYPE));
+        assert_eq!(unsafe { libc::fcntl(fd, libc::F_GETFD) }, -1);
+
+        let s: Socket<TcpSocket> = Socket::new().unwrap();
+        let s = Socket::<TcpSocket>::try_from(OwnedFd::from(s)).unwrap();
+        let s = s.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let error = Socket::<TcpSocket>::try_from(OwnedFd::from(s)).unwrap_err();
+        assert_eq!(error.raw_os_error(), Some(libc::EPROTOTYPE));
+
+        let s: Socket<TcpSocket> = Socket::new().unwrap();
+        let s = s.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let s = Socket::<TcpSocket<Bound>>::try_from(OwnedFd::from(s)).unwrap();
+        let server = s.listen(1).unwrap();
+        let server = Socket::<TcpSocket<Listening>>::try_from(OwnedFd::from(server)).unwrap();
+        let client: Socket<TcpSocket> = Socket::new().unwrap();
+        let client = client.connect(&server.local_address().unwrap()).unwrap();
+        let peer = server.accept().unwrap();
+        assert!(Socket::<TcpSocket<Connected>>::try_from(OwnedFd::from(client)).is_ok());
+        let error = Socket::<TcpSocket<Listening>>::try_from(OwnedFd::from(peer)).unwrap_err();
+        assert_eq!(error.raw_os_error(), Some(libc::EPROTOTYPE));
+    }
+
+    fn udp_server() -> (Socket<UdpSocket>, SocketAddrV4) {
+        let mut server: Socket<UdpSocket> = Socket::new().unwrap();
+        let _ = server.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = server.local_address().unwrap();
+        (server, address)
+    }
+
+    #[test]
+    fn socket_builder() {
+        let s: Socket<UdpSocket> = Socket::builder()
+            .bind("127.0.0.1:0".parse().unwrap())
+            .option(ReuseAddress::new(true))
+            .nonblocking(true)
+            .build()
+            .unwrap();
+        assert_eq!(s.flags().unwrap() & libc::O_NONBLOCK, libc::O_NONBLOCK);
+        assert_eq!(s.fd_flags().unwrap() & libc::FD_CLOEXEC, libc::FD_CLOEXEC);
+        assert_eq!(s.option().map(ReuseAddress::value), Ok(true));
+        assert_eq!(
+            s.local_address().unwrap().ip(),
+            &std::net::Ipv4Addr::LOCALHOST
+        );
+
+        let s: Socket<UdpSocket> = Socket::builder().cloexec(false).build().unwrap();
+        assert_eq!(s.flags().unwrap() & libc::O_NONBLOCK, 0);
+        assert_eq!(s.fd_flags().unwrap() & libc::FD_CLOEXEC, 0);
+    }
+
+    #[cfg(feature = "mio")]
+    #[test]
+    fn udp_socket_mio_poll() {
+        use mio::{Events, Interest, Poll, Token};
+        use std::time::Duration;
+
+        let (mut server, server_address) = udp_server();
+        let mut poll = Poll::new().unwrap();
+        let mut events = Events::with_capacity(4);
+        poll.registry()
+            .register(&mut server, Token(7), Interest::READABLE)
+            .unwrap();
+
+        let client: Socket<UdpSocket> = Socket::new().unwrap();
+        let _ = client.send_to(b"ping", &server_address, 0).unwrap();
+        poll.poll(&mut events, Some(Duration::from_secs(5)))
+            .unwrap();
+        let event = events.iter().next().unwrap();
+        assert_eq!(event.token(), Token(7));
+        assert!(event.is_readable());
+
+        let mut buffer = [0; 8];
+        assert_eq!(server.receive(&mut buffer, 0).unwrap(), 4);
+        poll.registry().deregister(&mut server).unwrap();
+    }
+
+    #[test]
+    fn udp_socket_send_to() {
+        let (server, server_address) = udp_server();
+        let client: Socket<UdpSocket> = Socket::new().unwrap();
+        assert_eq!(client.send_to(b"ping", &server_address, 0).unwrap(), 4);
+
+        let mut buffer = [0; 8];
+        assert_eq!(server.receive(&mut buffer, 0).unwrap(), 4);
+        assert_eq!(&buffer[..4], b"ping");
+    }
+
+    #[test]
+    #[allow(unused_results)]
+    fn udp_socket_send_connected() {
+        let (server, server_address) = udp_server();
+        let mut client: Socket<UdpSocket> = Socket::new().unwrap();
+        client.connect(&server_address).unwrap();
+        assert_eq!(client.send(b"ping", 0).unwrap(), 4);
+
+        let mut buffer = [0; 8];
+        assert_eq!(server.receive(&mut buffer, 0).unwrap(), 4);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_udp_socket_receive_from() {
+        use super::async_socket::AsyncSocket;
+
+        let (server, server_address) = udp_server();
+        let server = AsyncSocket::new(server).unwrap();
+        let (client, client_address) = udp_server();
+        let client = AsyncSocket::new(client).unwrap();
+
+        let mut buffer = [0; 8];
+        let (received, sent) = tokio::join!(
+            server.receive_from(&mut buffer, 0),
+            client.send_to(b"ping", &server_address, 0)
+        );
+        assert_eq!(sent.unwrap(), 4);
+        assert_eq!(received.unwrap(), (4, client_address));
+        assert_eq!(&buffer[..4], b"ping");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_udp_socket_drain() {
+        use super::async_socket::AsyncSocket;
+        use std::time::Duration;
+        use tokio::time::timeout;
+
+        let (server, server_address) = udp_server();
+        let mut server = AsyncSocket::new(server).unwrap();
+        let client: Socket<UdpSocket> = Socket::new().unwrap();
+        for _ in 0..3 {
+            let _ = client.send_to(b"stale", &server_address, 0).unwrap();
+        }
+        let mut buffer = [0; 8];
+        assert_eq!(server.receive(&mut buffer, 0).await.unwrap(), 5);
+        let _ = server.drain().await.unwrap();
+        let nothing = timeout(Duration::from_millis(50), server.receive(&mut buffer, 0));
+        assert!(nothing.await.is_err());
+
+        // packets arriving after the drain are still reported
+        let _ = client.send_to(b"fresh", &server_address, 0).unwrap();
+        let fresh = timeout(Duration::from_secs(5), server.receive(&mut buffer, 0));
+        assert_eq!(fresh.await.unwrap().unwrap(), 5);
+        assert_eq!(&buffer[..5], b"fresh");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_udp_socket_set_filter() {
+        use super::async_socket::AsyncSocket;
+
+        let (server, server_address) = udp_server();
+        let mut server = AsyncSocket::new(server).unwrap();
+        let client: Socket<UdpSocket> = Socket::new().unwrap();
+        let _ = client.send_to(b"stale", &server_address, 0).unwrap();
+
+        let p = ip_host::<Classic, _>("127.0.0.1".parse().unwrap());
+        let _ = server
+            .set_filter(p.compile().unwrap().build().unwrap())
+            .await
+            .unwrap();
+        let _ = client.send_to(b"fresh", &server_address, 0).unwrap();
+        let mut buffer = [0; 8];
+        assert_eq!(server.receive(&mut buffer, 0).await.unwrap(), 5);
+        assert_eq!(&buffer[..5], b"fresh");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_tcp_socket_accept() {
+        use super::async_socket::AsyncSocket;
+
+        let listener: Socket<TcpSocket> = Socket::new().unwrap();
+        let listener = listener
+            .bind(&"127.0.0.1:0".parse().unwrap())
+            .unwrap()
+            .listen(1)
+            .unwrap();
+        let address = listener.local_address().unwrap();
+        let listener = AsyncSocket::new(listener).unwrap();
+
+        let client: Socket<TcpSocket> = Socket::new().unwrap();
+        let (accepted, client) = tokio::join!(listener.accept(), async {
+            AsyncSocket::new(client.connect(&address).unwrap())
+        });
+        let (accepted, client) = (accepted.unwrap(), client.unwrap());
+        assert_eq!(client.send(b"ping", 0).await.unwrap(), 4);
+        let mut buffer = [0; 8];
+        assert_eq!(accepted.receive(&mut buffer, 0).await.unwrap(), 4);
+        assert_eq!(&buffer[..4], b"ping");
+    }
+
+    #[test]
+    fn udp_socket_send_message() {
+        let (server, server_address) = udp_server();
+        let client: Socket<UdpSocket> = Socket::new().unwrap();
+        let message = Message::new(&[b"scatter", b"-", b"gather"])
+            .to(server_address)
+            .with_control(ControlMessage::IpTtl(1))
+            .with_control(ControlMessage::IpTos(0x10));
+        assert_eq!(client.send_message(&message, 0).unwrap(), message.len());
+
+        let mut buffer = [0; 16];
+        let n = server.receive(&mut buffer, 0).unwrap();
+        assert_eq!(&buffer[..n], b"scatter-gather");
+    }
+
+    #[test]
+    fn udp_socket_send_message_bad_control() {
+        let (_server, server_address) = udp_server();
+        let client: Socket<UdpSocket> = Socket::new().unwrap();
+        let message =
+            Message::new(&[b"ping"])
+                .to(server_address)
+                .with_control(ControlMessage::Other {
+                    level: libc::IPPROTO_IP,
+                    type_: libc::IP_TTL,
+                    data: vec![0],
+                });
+        assert!(client.send_message(&message, 0).is_err());
+    }
+
+    #[test]
+    fn udp_socket_receive_from() {
+        let (server, server_address) = udp_server();
+        let client: Socket<UdpSocket> = Socket::new().unwrap();
+        let _ = client.send_to(b"ping", &server_address, 0).unwrap();
+
+        let mut buffer = [0; 8];
+        let (n, address) = server.receive_from(&mut buffer, 0).unwrap();
+        assert_eq!(&buffer[..n], b"ping");
+        assert_eq!(address.ip(), server_address.ip());
+        assert_eq!(address.port(), client.local_address().unwrap().port());
+    }
+
+    #[test]
+    fn udp_socket_receive_truncated() {
+        let (server, server_address) = udp_server();
+        let client: Socket<UdpSocket> = Socket::new().unwrap();
+        let _ = client.send_to(b"ping pong", &server_address, 0).unwrap();
+
+        let mut buffer = [0; 4];
+        let received = server
+            .receive_message(&mut [std::io::IoSliceMut::new(&mut buffer)], 0)
+            .unwrap();
+        assert!(received.is_truncated());
+        assert_eq!(received.length(), 4);
+        assert_eq!(&buffer, b"ping");
+    }
+
+    #[test]
+    #[allow(unused_results)]
+    fn unix_seqpacket_socket_pair() {
+        let (client, server): (Socket<UnixSeqpacketSocket>, _) = Socket::pair().unwrap();
+        client.send(b"ping", 0).unwrap();
+        client.send(b"pong", 0).unwrap();
+        // message boundaries are preserved
+        let mut buffer = [0; 8];
+        assert_eq!(server.receive(&mut buffer, 0).unwrap(), 4);
+        assert_eq!(&buffer[..4], b"ping");
+        assert_eq!(server.receive(&mut buffer, 0).unwrap(), 4);
+        assert_eq!(&buffer[..4], b"pong");
+    }
+
+    #[test]
+    #[allow(unused_results)]
+    fn unix_socket_pass_socket() {
+        let (sender, receiver): (Socket<UnixStreamSocket>, _) = Socket::pair().unwrap();
+        let (server, server_address) = udp_server();
+        sender.send_socket(&server).unwrap();
+        let received: Socket<UdpSocket> = receiver.receive_socket().unwrap();
+        assert_ne!(received.as_raw_fd(), server.as_raw_fd());
+        assert_eq!(received.local_address().unwrap(), server_address);
+
+        // the received socket shares the sent one's queue
+        let client: Socket<UdpSocket> = Socket::new().unwrap();
+        client.send_to(b"ping", &server_address, 0).unwrap();
+        let mut buffer = [0; 4];
+        assert_eq!(received.receive(&mut buffer, 0).unwrap(), 4);
+        assert_eq!(&buffer, b"ping");
+    }
+
+    #[test]
+    fn socket_option_names() {
+        use bs_system::{Level, Name};
+        assert_eq!(Name::ReuseAddress.level(), Level::Socket);
+        assert_eq!(Name::ReuseAddress.value(), libc::SO_REUSEADDR);
+        // `IP_TTL` and `SO_REUSEADDR` share the same value on some targets
+        assert_eq!(Name::IpTtl.level(), Level::Ip);
+        assert_eq!(Name::IpTtl.value(), libc::IP_TTL);
+        assert_eq!(Name::TcpNoDelay.level() as i32, libc::IPPROTO_TCP);
+        assert_eq!(Name::Ipv6V6Only.level() as i32, libc::IPPROTO_IPV6);
+    }
+
+    #[test]
+    #[allow(unused_results)]
+    fn socket_set_option() {
+        use super::socket::private::PrivateBasicSocket;
+        use std::time::Duration;
+        let mut s: Socket<TcpSocket> = Socket::new().unwrap();
+        s.set_option(TcpNoDelay::new(true))
+            .unwrap()
+            .set_option(ReuseAddress::new(true))
+            .unwrap()
+            .set_option(ReceiveTimeout::new(Some(Duration::from_millis(1500))))
+            .unwrap();
+        assert_eq!(s.int_option(libc::IPPROTO_TCP, libc::TCP_NODELAY), Ok(1));
+        assert_eq!(s.int_option(libc::SOL_SOCKET, libc::SO_REUSEADDR), Ok(1));
+        assert_eq!(s.option().map(TcpNoDelay::value), Ok(true));
+        assert_eq!(
+            s.option().map(ReceiveTimeout::timeout),
+            Ok(Some(Duration::from_millis(1500)))
+        );
+
+        let mut s: Socket<UdpSocket> = Socket::new().unwrap();
+        s.set_option(IpTtl::new(7)).unwrap();
+        assert_eq!(s.int_option(libc::IPPROTO_IP, libc::IP_TTL), Ok(7));
+
+        let mut s: Socket<Udp6Socket> = Socket::new().unwrap();
+        s.set_option(Ipv6V6Only::new(true)).unwrap();
+        assert!(s.v6_only().unwrap());
+    }
+
+    #[test]
+    fn unix_socket_pass_socket_mismatch() {
+        let (sender, receiver): (Socket<UnixSeqpacketSocket>, _) = Socket::pair().unwrap();
+        let (server, _) = udp_server();
+        sender.send_socket(&server).unwrap();
+        let received = receiver.receive_socket::<TcpSocket>();
+        assert_eq!(
+            received.map(|s| s.as_raw_fd()),
+            Err(SystemError::from_raw_os_error(libc::EPROTOTYPE))
+        );
+
+        drop(sender);
+        let received = receiver.receive_socket::<UdpSocket>();
+        assert_eq!(
+            received.map(|s| s.as_raw_fd()),
+            Err(SystemError::from_raw_os_error(libc::ENOMSG))
+        );
+    }
 }
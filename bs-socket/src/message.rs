@@ -0,0 +1,1121 @@
+use crate::address::SocketAddress;
+use bs_system::Result;
+use cfg_if::cfg_if;
+use libc::{c_void, cmsghdr, iovec, msghdr, sockaddr_storage, socklen_t};
+use libc::{CMSG_DATA, CMSG_LEN, CMSG_SPACE, IPPROTO_IP, IP_TOS, IP_TTL};
+use libc::{MSG_CTRUNC, MSG_TRUNC, SCM_RIGHTS, SOL_SOCKET};
+use std::convert::TryInto;
+use std::io::{IoSlice, IoSliceMut};
+use std::marker::PhantomData;
+use std::mem::{size_of, size_of_val, zeroed};
+use std::net::Ipv4Addr;
+use std::os::unix::io::RawFd;
+use std::ptr::copy_nonoverlapping;
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use bs_system::consts::{PACKET_AUXDATA, SCM_TIMESTAMPNS, SCTP_SNDRCV, SOL_SCTP};
+        use bs_system::consts::UDP_SEGMENT;
+        use bs_system::consts::{TP_STATUS_CSUMNOTREADY, TP_STATUS_CSUM_VALID};
+        use bs_system::consts::{TP_STATUS_VLAN_TPID_VALID, TP_STATUS_VLAN_VALID};
+        use libc::{IPPROTO_IPV6, IPV6_PKTINFO, IP_PKTINFO, SCM_CREDENTIALS, SOL_PACKET, SOL_UDP};
+        use std::net::Ipv6Addr;
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    }
+}
+
+/// Size of the control buffer `recvmsg(2)` fills, enough for all the control messages that may be
+/// enabled at once
+pub(crate) const CONTROL_BUFFER_SIZE: usize = 512;
+
+/// A control message (`cmsg(3)`) passed to the kernel along with a [`Message`](struct.Message.html)
+/// or received along with a [`ReceivedMessage`](struct.ReceivedMessage.html)
+///
+/// Most control messages are only received after enabling the matching socket option, e.g.
+/// `IP_RECVTTL` for `IpTtl`, `PACKET_AUXDATA` for `PacketAuxData`, `SCTP_EVENTS` for `SctpInfo` or
+/// `SO_PASSCRED` for `Credentials`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ControlMessage {
+    /// `IP_TTL`, the TTL of the IPv4 packet
+    IpTtl(u8),
+
+    /// `IP_TOS`, the TOS field of the IPv4 packet
+    IpTos(u8),
+
+    /// `IP_PKTINFO`, the interface and the addresses of the IPv4 packet, see `ip(7)`
+    #[cfg(target_os = "linux")]
+    IpPacketInfo {
+        /// The interface the packet arrived on, or the outgoing interface, where 0 lets the kernel
+        /// route the packet
+        interface_index: i32,
+        /// The local address the packet was routed to, or the source address of the outgoing
+        /// packet, where `0.0.0.0` lets the kernel pick one
+        local: Ipv4Addr,
+        /// The destination address in the packet's header, ignored when sending
+        destination: Ipv4Addr,
+    },
+
+    /// `IPV6_PKTINFO`, the interface and the local address of the IPv6 packet, see `ipv6(7)`
+    #[cfg(target_os = "linux")]
+    Ip6PacketInfo {
+        /// The interface the packet arrived on, or the outgoing interface
+        interface_index: u32,
+        /// The destination address of the packet, or the source address of the outgoing packet
+        address: Ipv6Addr,
+    },
+
+    /// `SO_TIMESTAMPNS`, the time the packet was received, see `socket(7)`
+    #[cfg(target_os = "linux")]
+    Timestamp(SystemTime),
+
+    /// `PACKET_AUXDATA`, metadata of a packet received by a `packet(7)` socket
+    #[cfg(target_os = "linux")]
+    PacketAuxData(AuxData),
+
+    /// `UDP_SEGMENT`, splits the payload to datagrams of the given size (UDP GSO), see `udp(7)`
+    #[cfg(target_os = "linux")]
+    UdpSegment(u16),
+
+    /// `SCTP_SNDRCV`, the stream and other attributes of an SCTP message, see `sctp(7)`
+    #[cfg(target_os = "linux")]
+    SctpInfo(SctpInfo),
+
+    /// `SCM_RIGHTS`, file descriptors passed over a `unix(7)` socket
+    ///
+    /// The receiver owns the received descriptors, and is responsible for closing them.
+    Rights(Vec<RawFd>),
+
+    /// `SCM_CREDENTIALS`, the credentials of the process sending over a `unix(7)` socket, only
+    /// received after enabling `SO_PASSCRED`
+    #[cfg(target_os = "linux")]
+    Credentials(Credentials),
+
+    /// Any other control message, with `cmsg_level` `level`, `cmsg_type` `type_` and the
+    /// already encoded `data`
+    Other {
+        /// `cmsg_level`, e.g. `SOL_SOCKET`
+        level: i32,
+        /// `cmsg_type`, e.g. `SCM_RIGHTS`
+        type_: i32,
+        /// The message's payload, in the layout the kernel expects
+        data: Vec<u8>,
+    },
+}
+
+impl ControlMessage {
+    fn level(&self) -> i32 {
+        match self {
+            ControlMessage::IpTtl(_) | ControlMessage::IpTos(_) => IPPROTO_IP,
+            #[cfg(target_os = "linux")]
+            ControlMessage::IpPacketInfo { .. } => IPPROTO_IP,
+            #[cfg(target_os = "linux")]
+            ControlMessage::Ip6PacketInfo { .. } => IPPROTO_IPV6,
+            #[cfg(target_os = "linux")]
+            ControlMessage::Timestamp(_) => SOL_SOCKET,
+            #[cfg(target_os = "linux")]
+            ControlMessage::PacketAuxData(_) => SOL_PACKET,
+            #[cfg(target_os = "linux")]
+            ControlMessage::UdpSegment(_) => SOL_UDP,
+            #[cfg(target_os = "linux")]
+            ControlMessage::SctpInfo(_) => SOL_SCTP,
+            ControlMessage::Rights(_) => SOL_SOCKET,
+            #[cfg(target_os = "linux")]
+            ControlMessage::Credentials(_) => SOL_SOCKET,
+            ControlMessage::Other { level, .. } => *level,
+        }
+    }
+
+    fn type_(&self) -> i32 {
+        match self {
+            ControlMessage::IpTtl(_) => IP_TTL,
+            ControlMessage::IpTos(_) => IP_TOS,
+            #[cfg(target_os = "linux")]
+            ControlMessage::IpPacketInfo { .. } => IP_PKTINFO,
+            #[cfg(target_os = "linux")]
+            ControlMessage::Ip6PacketInfo { .. } => IPV6_PKTINFO,
+            #[cfg(target_os = "linux")]
+            ControlMessage::Timestamp(_) => SCM_TIMESTAMPNS,
+            #[cfg(target_os = "linux")]
+            ControlMessage::PacketAuxData(_) => PACKET_AUXDATA,
+            #[cfg(target_os = "linux")]
+            ControlMessage::UdpSegment(_) => UDP_SEGMENT,
+            #[cfg(target_os = "linux")]
+            ControlMessage::SctpInfo(_) => SCTP_SNDRCV,
+            ControlMessage::Rights(_) => SCM_RIGHTS,
+            #[cfg(target_os = "linux")]
+            ControlMessage::Credentials(_) => SCM_CREDENTIALS,
+            ControlMessage::Other { type_, .. } => *type_,
+        }
+    }
+
+    fn data(&self) -> Vec<u8> {
+        match self {
+            ControlMessage::IpTtl(value) | ControlMessage::IpTos(value) => {
+                i32::from(*value).to_ne_bytes().to_vec()
+            }
+            #[cfg(target_os = "linux")]
+            ControlMessage::IpPacketInfo {
+                interface_index,
+                local,
+                destination,
+            } => {
+                // struct in_pktinfo { ipi_ifindex, ipi_spec_dst, ipi_addr }
+                let mut data = interface_index.to_ne_bytes().to_vec();
+                data.extend_from_slice(&local.octets());
+                data.extend_from_slice(&destination.octets());
+                data
+            }
+            #[cfg(target_os = "linux")]
+            ControlMessage::Ip6PacketInfo {
+                interface_index,
+                address,
+            } => {
+                // struct in6_pktinfo { ipi6_addr, ipi6_ifindex }
+                let mut data = address.octets().to_vec();
+                data.extend_from_slice(&interface_index.to_ne_bytes());
+                data
+            }
+            #[cfg(target_os = "linux")]
+            ControlMessage::Timestamp(time) => {
+                // struct timespec { tv_sec, tv_nsec }
+                let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+                let mut data = (since_epoch.as_secs() as libc::time_t)
+                    .to_ne_bytes()
+                    .to_vec();
+                data.extend_from_slice(&(since_epoch.subsec_nanos() as libc::c_long).to_ne_bytes());
+                data
+            }
+            #[cfg(target_os = "linux")]
+            ControlMessage::PacketAuxData(auxdata) => auxdata.to_bytes(),
+            #[cfg(target_os = "linux")]
+            ControlMessage::UdpSegment(size) => size.to_ne_bytes().to_vec(),
+            #[cfg(target_os = "linux")]
+            ControlMessage::SctpInfo(info) => info.to_bytes(),
+            ControlMessage::Rights(fds) => fds
+                .iter()
+                .flat_map(|fd| fd.to_ne_bytes().to_vec())
+                .collect(),
+            #[cfg(target_os = "linux")]
+            ControlMessage::Credentials(credentials) => credentials.to_bytes(),
+            ControlMessage::Other { data, .. } => data.clone(),
+        }
+    }
+
+    /// Decodes a control message of `level` and `type_` from its payload, falling back to
+    /// `Other` for unknown or malformed messages
+    fn decode(level: i32, type_: i32, data: &[u8]) -> Self {
+        let decoded = match (level, type_) {
+            (IPPROTO_IP, IP_TTL) => read_i32(data, 0).map(|ttl| ControlMessage::IpTtl(ttl as u8)),
+            // the kernel passes IP_TOS as a single octet
+            (IPPROTO_IP, IP_TOS) => data.first().map(|&tos| ControlMessage::IpTos(tos)),
+            #[cfg(target_os = "linux")]
+            (IPPROTO_IP, IP_PKTINFO) => read_i32(data, 0).and_then(|interface_index| {
+                Some(ControlMessage::IpPacketInfo {
+                    interface_index,
+                    local: read_ipv4(data, 4)?,
+                    destination: read_ipv4(data, 8)?,
+                })
+            }),
+            #[cfg(target_os = "linux")]
+            (IPPROTO_IPV6, IPV6_PKTINFO) => {
+                let address: Option<[u8; 16]> = data.get(..16).and_then(|a| a.try_into().ok());
+                address.and_then(|address| {
+                    Some(ControlMessage::Ip6PacketInfo {
+                        interface_index: read_i32(data, 16)? as u32,
+                        address: address.into(),
+                    })
+                })
+            }
+            #[cfg(target_os = "linux")]
+            (SOL_SOCKET, SCM_TIMESTAMPNS) => {
+                let seconds_length = size_of::<libc::time_t>();
+                let nanoseconds_length = size_of::<libc::c_long>();
+                let seconds = read_bytes(data, 0, seconds_length)
+                    .map(|b| libc::time_t::from_ne_bytes(b.try_into().unwrap_or_default()) as u64);
+                let nanoseconds = read_bytes(data, seconds_length, nanoseconds_length)
+                    .map(|b| libc::c_long::from_ne_bytes(b.try_into().unwrap_or_default()) as u32);
+                seconds.and_then(|seconds| {
+                    let since_epoch = Duration::new(seconds, nanoseconds?);
+                    Some(ControlMessage::Timestamp(UNIX_EPOCH + since_epoch))
+                })
+            }
+            #[cfg(target_os = "linux")]
+            (SOL_PACKET, PACKET_AUXDATA) => {
+                AuxData::from_bytes(data).map(ControlMessage::PacketAuxData)
+            }
+            #[cfg(target_os = "linux")]
+            (SOL_UDP, UDP_SEGMENT) => read_bytes(data, 0, 2)
+                .map(|b| ControlMessage::UdpSegment(u16::from_ne_bytes([b[0], b[1]]))),
+            #[cfg(target_os = "linux")]
+            (SOL_SCTP, SCTP_SNDRCV) => SctpInfo::from_bytes(data).map(ControlMessage::SctpInfo),
+            (SOL_SOCKET, SCM_RIGHTS) => {
+                let fds: Option<Vec<RawFd>> = (0..data.len() / size_of::<RawFd>())
+                    .map(|i| read_i32(data, i * size_of::<RawFd>()))
+                    .collect();
+                fds.map(ControlMessage::Rights)
+            }
+            #[cfg(target_os = "linux")]
+            (SOL_SOCKET, SCM_CREDENTIALS) => {
+                Credentials::from_bytes(data).map(ControlMessage::Credentials)
+            }
+            _ => None,
+        };
+        decoded.unwrap_or_else(|| ControlMessage::Other {
+            level,
+            type_,
+            data: data.to_vec(),
+        })
+    }
+}
+
+pub(crate) fn read_bytes(data: &[u8], offset: usize, length: usize) -> Option<&[u8]> {
+    data.get(offset..offset.checked_add(length)?)
+}
+
+pub(crate) fn read_i32(data: &[u8], offset: usize) -> Option<i32> {
+    let bytes = read_bytes(data, offset, 4)?;
+    Some(i32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = read_bytes(data, offset, 2)?;
+    Some(u16::from_ne_bytes([bytes[0], bytes[1]]))
+}
+
+#[cfg(target_os = "linux")]
+fn read_ipv4(data: &[u8], offset: usize) -> Option<Ipv4Addr> {
+    let bytes = read_bytes(data, offset, 4)?;
+    Some(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]))
+}
+
+/// `PACKET_AUXDATA`'s `struct tpacket_auxdata`, metadata the kernel keeps about a packet received
+/// by a `packet(7)` socket
+#[cfg(target_os = "linux")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AuxData {
+    status: u32,
+    length: u32,
+    snap_length: u32,
+    mac_offset: u16,
+    network_offset: u16,
+    vlan_tci: u16,
+    vlan_tpid: u16,
+}
+
+#[cfg(target_os = "linux")]
+impl AuxData {
+    /// `TP_STATUS_*` flags of the packet
+    pub fn status(&self) -> u32 {
+        self.status
+    }
+
+    /// Original length of the packet
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Length of the packet after truncation by a filter
+    pub fn snap_length(&self) -> u32 {
+        self.snap_length
+    }
+
+    /// The VLAN tag control information, if the packet was VLAN tagged and the tag was stripped by
+    /// the NIC or the kernel
+    pub fn vlan_tci(&self) -> Option<u16> {
+        if self.status & TP_STATUS_VLAN_VALID != 0 {
+            Some(self.vlan_tci)
+        } else {
+            None
+        }
+    }
+
+    /// The VLAN tag protocol identifier, e.g. `ETH_P_8021Q`, if the packet was VLAN tagged
+    pub fn vlan_tpid(&self) -> Option<u16> {
+        if self.status & TP_STATUS_VLAN_TPID_VALID != 0 {
+            Some(self.vlan_tpid)
+        } else {
+            None
+        }
+    }
+
+    /// true iff the packet's checksum was already validated, e.g. by the NIC
+    pub fn checksum_valid(&self) -> bool {
+        self.status & TP_STATUS_CSUM_VALID != 0
+    }
+
+    /// true iff the packet's checksum is yet to be computed, as with locally generated packets
+    /// whose checksum is offloaded to the NIC
+    pub fn checksum_not_ready(&self) -> bool {
+        self.status & TP_STATUS_CSUMNOTREADY != 0
+    }
+
+    fn from_bytes(data: &[u8]) -> Option<Self> {
+        Some(Self {
+            status: read_i32(data, 0)? as u32,
+            length: read_i32(data, 4)? as u32,
+            snap_length: read_i32(data, 8)? as u32,
+            mac_offset: read_u16(data, 12)?,
+            network_offset: read_u16(data, 14)?,
+            vlan_tci: read_u16(data, 16)?,
+            vlan_tpid: read_u16(data, 18)?,
+        })
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(20);
+        for field in &[self.status, self.length, self.snap_length] {
+            data.extend_from_slice(&field.to_ne_bytes());
+        }
+        for field in &[
+            self.mac_offset,
+            self.network_offset,
+            self.vlan_tci,
+            self.vlan_tpid,
+        ] {
+            data.extend_from_slice(&field.to_ne_bytes());
+        }
+        data
+    }
+}
+
+/// `SCTP_SNDRCV`'s `struct sctp_sndrcvinfo`, the attributes of a message sent or received by an
+/// [`SctpSocket`](../sctp/struct.SctpSocket.html)
+#[cfg(target_os = "linux")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SctpInfo {
+    stream: u16,
+    ssn: u16,
+    flags: u16,
+    ppid: u32,
+    context: u32,
+    time_to_live: u32,
+    tsn: u32,
+    cumulative_tsn: u32,
+    association_id: i32,
+}
+
+#[cfg(target_os = "linux")]
+impl SctpInfo {
+    /// Attributes of a message sent on stream `stream`
+    pub fn new(stream: u16) -> Self {
+        Self {
+            stream,
+            ssn: 0,
+            flags: 0,
+            ppid: 0,
+            context: 0,
+            time_to_live: 0,
+            tsn: 0,
+            cumulative_tsn: 0,
+            association_id: 0,
+        }
+    }
+
+    /// Sets the `SCTP_*` flags of the message, e.g. `SCTP_UNORDERED`
+    pub fn with_flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets the payload protocol identifier of the message, which is passed to the peer as is
+    pub fn with_ppid(mut self, ppid: u32) -> Self {
+        self.ppid = ppid;
+        self
+    }
+
+    /// Sets the context reported back in `SCTP_SEND_FAILED` notifications about the message
+    pub fn with_context(mut self, context: u32) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Sets the time in milliseconds after which the message expires if it wasn't sent, where 0
+    /// means never
+    pub fn with_time_to_live(mut self, time_to_live: u32) -> Self {
+        self.time_to_live = time_to_live;
+        self
+    }
+
+    /// Sets the association the message is sent on, for one-to-many sockets
+    pub fn with_association_id(mut self, association_id: i32) -> Self {
+        self.association_id = association_id;
+        self
+    }
+
+    /// The stream of the message
+    pub fn stream(&self) -> u16 {
+        self.stream
+    }
+
+    /// The stream sequence number of a received message
+    pub fn ssn(&self) -> u16 {
+        self.ssn
+    }
+
+    /// `SCTP_*` flags of the message
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// The payload protocol identifier of the message
+    pub fn ppid(&self) -> u32 {
+        self.ppid
+    }
+
+    /// The context of the message
+    pub fn context(&self) -> u32 {
+        self.context
+    }
+
+    /// The time to live of the message, in milliseconds
+    pub fn time_to_live(&self) -> u32 {
+        self.time_to_live
+    }
+
+    /// The transmission sequence number of a received message
+    pub fn tsn(&self) -> u32 {
+        self.tsn
+    }
+
+    /// The cumulative transmission sequence number of the association, for received messages
+    pub fn cumulative_tsn(&self) -> u32 {
+        self.cumulative_tsn
+    }
+
+    /// The association the message belongs to
+    pub fn association_id(&self) -> i32 {
+        self.association_id
+    }
+
+    fn from_bytes(data: &[u8]) -> Option<Self> {
+        Some(Self {
+            stream: read_u16(data, 0)?,
+            ssn: read_u16(data, 2)?,
+            flags: read_u16(data, 4)?,
+            ppid: read_i32(data, 8)? as u32,
+            context: read_i32(data, 12)? as u32,
+            time_to_live: read_i32(data, 16)? as u32,
+            tsn: read_i32(data, 20)? as u32,
+            cumulative_tsn: read_i32(data, 24)? as u32,
+            association_id: read_i32(data, 28)?,
+        })
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(32);
+        for field in &[self.stream, self.ssn, self.flags, 0] {
+            data.extend_from_slice(&field.to_ne_bytes());
+        }
+        for field in &[
+            self.ppid,
+            self.context,
+            self.time_to_live,
+            self.tsn,
+            self.cumulative_tsn,
+        ] {
+            data.extend_from_slice(&field.to_ne_bytes());
+        }
+        data.extend_from_slice(&self.association_id.to_ne_bytes());
+        data
+    }
+}
+
+/// `SCM_CREDENTIALS`'s and `SO_PEERCRED`'s `struct ucred`, the credentials of a process on the
+/// other end of a `unix(7)` socket
+#[cfg(target_os = "linux")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Credentials {
+    pid: i32,
+    uid: u32,
+    gid: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl Credentials {
+    /// Credentials of the process `pid`, running as `uid` and `gid`
+    ///
+    /// Only privileged processes may send credentials other than their own.
+    pub fn new(pid: i32, uid: u32, gid: u32) -> Self {
+        Self { pid, uid, gid }
+    }
+
+    /// The credentials of the calling process
+    pub fn current() -> Self {
+        unsafe { Self::new(libc::getpid(), libc::getuid(), libc::getgid()) }
+    }
+
+    /// The process id
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// The user id
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// The group id
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    pub(crate) fn from_bytes(data: &[u8]) -> Option<Self> {
+        Some(Self {
+            pid: read_i32(data, 0)?,
+            uid: read_i32(data, 4)? as u32,
+            gid: read_i32(data, 8)? as u32,
+        })
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut data = self.pid.to_ne_bytes().to_vec();
+        data.extend_from_slice(&self.uid.to_ne_bytes());
+        data.extend_from_slice(&self.gid.to_ne_bytes());
+        data
+    }
+}
+
+/// An iterator over the control messages in a control buffer, decoding each message as it's
+/// reached
+#[derive(Debug, Clone)]
+pub struct ControlMessages<'a> {
+    control: &'a [u64],
+    length: usize,
+    offset: usize,
+}
+
+impl<'a> ControlMessages<'a> {
+    /// The control messages in the first `length` octets of `control`
+    fn new(control: &'a [u64], length: usize) -> Self {
+        Self {
+            control,
+            length: length.min(size_of_val(control)),
+            offset: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for ControlMessages<'a> {
+    type Item = ControlMessage;
+
+    // the width of cmsg_len varies between platforms
+    #[allow(trivial_numeric_casts)]
+    fn next(&mut self) -> Option<ControlMessage> {
+        let header_length = unsafe { CMSG_LEN(0) } as usize;
+        if self.offset + header_length > self.length {
+            return None;
+        }
+        unsafe {
+            let base = self.control.as_ptr() as *const u8;
+            let header = base.add(self.offset) as *const cmsghdr;
+            let message_length: usize = (*header).cmsg_len as _;
+            if message_length < header_length || self.offset + message_length > self.length {
+                self.offset = self.length;
+                return None;
+            }
+            let data_length = message_length - header_length;
+            let data = std::slice::from_raw_parts(CMSG_DATA(header), data_length);
+            self.offset += CMSG_SPACE(data_length as u32) as usize;
+            Some(ControlMessage::decode(
+                (*header).cmsg_level,
+                (*header).cmsg_type,
+                data,
+            ))
+        }
+    }
+}
+
+/// Decodes the control messages in the first `length` octets of `control`
+fn decode_control(control: &[u64], length: usize) -> Vec<ControlMessage> {
+    ControlMessages::new(control, length).collect()
+}
+
+/// Encodes `messages` into a control buffer, aligned for `cmsghdr`s, and returns it along with the
+/// length of its used part
+fn encode_control(messages: &[ControlMessage]) -> (Vec<u64>, usize) {
+    let encoded: Vec<(i32, i32, Vec<u8>)> = messages
+        .iter()
+        .map(|message| (message.level(), message.type_(), message.data()))
+        .collect();
+    let length: usize = encoded
+        .iter()
+        .map(|(_, _, data)| unsafe { CMSG_SPACE(data.len() as u32) } as usize)
+        .sum();
+    let mut buffer = vec![0_u64; length / size_of::<u64>() + 1];
+    let base = buffer.as_mut_ptr() as *mut u8;
+    let mut offset = 0;
+    for (level, type_, data) in encoded {
+        unsafe {
+            let header = base.add(offset) as *mut cmsghdr;
+            (*header).cmsg_len = CMSG_LEN(data.len() as u32) as _;
+            (*header).cmsg_level = level;
+            (*header).cmsg_type = type_;
+            copy_nonoverlapping(data.as_ptr(), CMSG_DATA(header), data.len());
+            offset += CMSG_SPACE(data.len() as u32) as usize;
+        }
+    }
+    (buffer, length)
+}
+
+/// A message to be sent by `sendmsg(2)`, made of scattered buffers, an optional destination
+/// address and control messages
+///
+/// # Example
+/// ```
+/// # use bs_socket::message::{ControlMessage, Message};
+/// # use std::net::SocketAddrV4;
+/// let header = [0_u8; 8];
+/// let payload = b"payload";
+/// let message: Message<SocketAddrV4> = Message::new(&[&header, payload])
+///     .to("127.0.0.1:9999".parse().unwrap())
+///     .with_control(ControlMessage::IpTtl(1));
+/// # assert_eq!(message.len(), 15);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Message<'a, A: SocketAddress> {
+    address: Option<A>,
+    buffers: Vec<IoSlice<'a>>,
+    control: Vec<ControlMessage>,
+}
+
+impl<'a, A: SocketAddress> Message<'a, A> {
+    /// Creates a new `Message` gathering `buffers`, in order
+    pub fn new(buffers: &[&'a [u8]]) -> Self {
+        Self {
+            address: None,
+            buffers: buffers.iter().map(|buffer| IoSlice::new(buffer)).collect(),
+            control: Vec::new(),
+        }
+    }
+
+    /// Sets the destination address of the message
+    pub fn to(mut self, address: A) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    /// Adds `control` to the control messages sent along with the message
+    pub fn with_control(mut self, control: ControlMessage) -> Self {
+        self.control.push(control);
+        self
+    }
+
+    /// The destination address of the message, if set
+    pub fn address(&self) -> Option<&A> {
+        self.address.as_ref()
+    }
+
+    /// The total length of the message's buffers
+    pub fn len(&self) -> usize {
+        self.buffers.iter().map(|buffer| buffer.len()).sum()
+    }
+
+    /// true iff the message's buffers are all empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn raw(&self) -> RawMessage {
+        let (control, control_length) = encode_control(&self.control);
+        RawMessage {
+            address: self.address.as_ref().map(SocketAddress::to_storage),
+            control,
+            control_length,
+        }
+    }
+
+    pub(crate) fn buffers(&self) -> &[IoSlice<'a>] {
+        &self.buffers
+    }
+}
+
+/// The `sockaddr_*` and the control buffer of a `Message`, which a `msghdr` points to
+pub(crate) struct RawMessage {
+    address: Option<(sockaddr_storage, socklen_t)>,
+    control: Vec<u64>,
+    control_length: usize,
+}
+
+impl RawMessage {
+    /// A `msghdr` for sending `buffers` along with `self`, valid for as long as both are
+    // the widths of msg_iovlen and msg_controllen vary between platforms
+    #[allow(trivial_numeric_casts)]
+    pub(crate) fn header(&mut self, buffers: &[IoSlice<'_>]) -> msghdr {
+        let mut header: msghdr = unsafe { zeroed() };
+        if let Some((storage, length)) = self.address.as_mut() {
+            let ptr: *mut sockaddr_storage = storage;
+            header.msg_name = ptr as *mut c_void;
+            header.msg_namelen = *length;
+        }
+        // IoSlice is guaranteed to be ABI compatible with iovec
+        header.msg_iov = buffers.as_ptr() as *mut iovec;
+        header.msg_iovlen = buffers.len() as _;
+        if self.control_length > 0 {
+            header.msg_control = self.control.as_mut_ptr() as *mut c_void;
+            header.msg_controllen = self.control_length as _;
+        }
+        header
+    }
+}
+
+/// A message received by `recvmsg(2)`, along with its source address and control messages
+#[derive(Debug, Clone)]
+pub struct ReceivedMessage<A: SocketAddress> {
+    length: usize,
+    address: Option<A>,
+    control: Vec<ControlMessage>,
+    flags: i32,
+}
+
+impl<A: SocketAddress> ReceivedMessage<A> {
+    /// The number of octets received into the buffers
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// The source address of the message, if the socket reports one
+    pub fn address(&self) -> Option<&A> {
+        self.address.as_ref()
+    }
+
+    /// The control messages received along with the message
+    pub fn control(&self) -> &[ControlMessage] {
+        &self.control
+    }
+
+    /// The `msg_flags` `recvmsg(2)` returned, e.g. `MSG_TRUNC`
+    pub fn flags(&self) -> i32 {
+        self.flags
+    }
+
+    /// true iff the message was longer than the buffers, and its end was discarded
+    pub fn is_truncated(&self) -> bool {
+        self.flags & MSG_TRUNC != 0
+    }
+
+    /// true iff some control messages were discarded for lack of space
+    pub fn is_control_truncated(&self) -> bool {
+        self.flags & MSG_CTRUNC != 0
+    }
+}
+
+/// The `sockaddr_storage` and the control buffer `recvmsg(2)` fills
+pub(crate) struct RawReceive {
+    address: sockaddr_storage,
+    control: Vec<u64>,
+}
+
+impl RawReceive {
+    pub(crate) fn new() -> Self {
+        Self {
+            address: unsafe { zeroed() },
+            control: vec![0; CONTROL_BUFFER_SIZE / size_of::<u64>()],
+        }
+    }
+
+    /// A `msghdr` for receiving into `buffers` and `self`, valid for as long as both are
+    // the widths of msg_iovlen and msg_controllen vary between platforms
+    #[allow(trivial_numeric_casts)]
+    pub(crate) fn header(&mut self, buffers: &mut [IoSliceMut<'_>]) -> msghdr {
+        let mut header: msghdr = unsafe { zeroed() };
+        let ptr: *mut sockaddr_storage = &mut self.address;
+        header.msg_name = ptr as *mut c_void;
+        header.msg_namelen = size_of::<sockaddr_storage>() as socklen_t;
+        // IoSliceMut is guaranteed to be ABI compatible with iovec
+        header.msg_iov = buffers.as_mut_ptr() as *mut iovec;
+        header.msg_iovlen = buffers.len() as _;
+        header.msg_control = self.control.as_mut_ptr() as *mut c_void;
+        header.msg_controllen = (self.control.len() * size_of::<u64>()) as _;
+        header
+    }
+
+    /// Parses the message `header` describes, after `recvmsg(2)` received `length` octets
+    // the width of msg_controllen varies between platforms
+    #[allow(trivial_numeric_casts)]
+    pub(crate) fn finish<A: SocketAddress>(
+        &self,
+        header: &msghdr,
+        length: usize,
+    ) -> Result<ReceivedMessage<A>> {
+        let address = if header.msg_namelen == 0 {
+            None
+        } else {
+            Some(A::from_storage(&self.address, header.msg_namelen)?)
+        };
+        Ok(ReceivedMessage {
+            length,
+            address,
+            control: decode_control(&self.control, header.msg_controllen as _),
+            flags: header.msg_flags,
+        })
+    }
+}
+
+/// A reusable pool of preallocated buffers that `recvmmsg(2)` fills with a batch of packets, along
+/// with their lengths, source addresses and control messages
+///
+/// Receiving into a pool doesn't allocate, so the same pool can be used on the hot path of a
+/// capture loop, see [`ReceiveFrom::receive_messages`].
+///
+/// # Example
+/// ```no_run
+/// # use bs_socket::message::ReceivePool;
+/// # use bs_socket::packet::PacketLayer2Socket;
+/// # use bs_socket::socket::{ReceiveFrom, Socket};
+/// # fn main() -> bs_system::Result<()> {
+/// let s: Socket<PacketLayer2Socket> = Socket::new()?;
+/// let mut pool = ReceivePool::new(64, 2048);
+/// loop {
+///     let _ = s.receive_messages(&mut pool, 0, None)?;
+///     for packet in pool.iter() {
+///         println!("{} octets on {}", packet.length(), packet.address()?.interface_index());
+///     }
+/// }
+/// # }
+/// ```
+///
+/// [`ReceiveFrom::receive_messages`]: ../socket/trait.ReceiveFrom.html#method.receive_messages
+#[cfg(target_os = "linux")]
+pub struct ReceivePool<A: SocketAddress> {
+    buffer_size: usize,
+    data: Vec<u8>,
+    addresses: Vec<sockaddr_storage>,
+    control: Vec<u64>,
+    iovecs: Vec<iovec>,
+    headers: Vec<libc::mmsghdr>,
+    received: usize,
+    address: PhantomData<A>,
+}
+
+// the raw pointers in iovecs and headers only ever point into the pool's own buffers, and are
+// rebuilt before each use
+#[cfg(target_os = "linux")]
+unsafe impl<A: SocketAddress + Send> Send for ReceivePool<A> {}
+
+#[cfg(target_os = "linux")]
+impl<A: SocketAddress> ReceivePool<A> {
+    /// Creates a pool of `count` buffers of `buffer_size` octets each
+    pub fn new(count: usize, buffer_size: usize) -> Self {
+        let control_words = CONTROL_BUFFER_SIZE / size_of::<u64>();
+        Self {
+            buffer_size,
+            data: vec![0; count * buffer_size],
+            addresses: vec![unsafe { zeroed() }; count],
+            control: vec![0; count * control_words],
+            iovecs: Vec::with_capacity(count),
+            headers: Vec::with_capacity(count),
+            received: 0,
+            address: PhantomData,
+        }
+    }
+
+    /// The number of buffers in the pool, i.e. the most packets received at once
+    pub fn capacity(&self) -> usize {
+        self.addresses.len()
+    }
+
+    /// The number of packets received by the last call
+    pub fn len(&self) -> usize {
+        self.received
+    }
+
+    /// true iff the last call received no packets
+    pub fn is_empty(&self) -> bool {
+        self.received == 0
+    }
+
+    /// The `index`th packet received by the last call
+    // the width of msg_controllen varies between platforms
+    #[allow(trivial_numeric_casts)]
+    pub fn get(&self, index: usize) -> Option<PooledMessage<'_, A>> {
+        if index >= self.received {
+            return None;
+        }
+        let header = &self.headers[index];
+        let start = index * self.buffer_size;
+        let length = header.msg_len as usize;
+        let control_words = CONTROL_BUFFER_SIZE / size_of::<u64>();
+        let control_start = index * control_words;
+        Some(PooledMessage {
+            data: &self.data[start..start + length.min(self.buffer_size)],
+            length,
+            address: &self.addresses[index],
+            address_length: header.msg_hdr.msg_namelen,
+            control: ControlMessages::new(
+                &self.control[control_start..control_start + control_words],
+                header.msg_hdr.msg_controllen as _,
+            ),
+            flags: header.msg_hdr.msg_flags,
+            phantom: PhantomData,
+        })
+    }
+
+    /// An iterator over the packets received by the last call
+    pub fn iter(&self) -> impl Iterator<Item = PooledMessage<'_, A>> {
+        (0..self.received).filter_map(move |index| self.get(index))
+    }
+
+    /// Points the `mmsghdr`s at the pool's buffers, returns them for `recvmmsg(2)` to fill
+    // the widths of msg_iovlen and msg_controllen vary between platforms
+    #[allow(trivial_numeric_casts)]
+    pub(crate) fn headers(&mut self) -> &mut [libc::mmsghdr] {
+        let control_length = CONTROL_BUFFER_SIZE / size_of::<u64>();
+        self.received = 0;
+        self.iovecs.clear();
+        self.headers.clear();
+        for index in 0..self.capacity() {
+            let buffer = &mut self.data[index * self.buffer_size..][..self.buffer_size];
+            self.iovecs.push(iovec {
+                iov_base: buffer.as_mut_ptr() as *mut c_void,
+                iov_len: buffer.len(),
+            });
+        }
+        for (index, iovec) in self.iovecs.iter_mut().enumerate() {
+            let mut header: msghdr = unsafe { zeroed() };
+            let address: *mut sockaddr_storage = &mut self.addresses[index];
+            header.msg_name = address as *mut c_void;
+            header.msg_namelen = size_of::<sockaddr_storage>() as socklen_t;
+            header.msg_iov = iovec;
+            header.msg_iovlen = 1;
+            let control = &mut self.control[index * control_length..][..control_length];
+            header.msg_control = control.as_mut_ptr() as *mut c_void;
+            header.msg_controllen = size_of_val(control) as _;
+            self.headers.push(libc::mmsghdr {
+                msg_hdr: header,
+                msg_len: 0,
+            });
+        }
+        &mut self.headers
+    }
+
+    /// Records that `recvmmsg(2)` filled the first `received` `mmsghdr`s
+    pub(crate) fn set_received(&mut self, received: usize) {
+        self.received = received.min(self.headers.len());
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<A: SocketAddress> std::fmt::Debug for ReceivePool<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReceivePool")
+            .field("capacity", &self.capacity())
+            .field("buffer_size", &self.buffer_size)
+            .field("received", &self.received)
+            .finish()
+    }
+}
+
+/// A packet received into a [`ReceivePool`](struct.ReceivePool.html), borrowed from the pool
+#[cfg(target_os = "linux")]
+#[derive(Clone)]
+pub struct PooledMessage<'a, A: SocketAddress> {
+    data: &'a [u8],
+    length: usize,
+    address: &'a sockaddr_storage,
+    address_length: socklen_t,
+    control: ControlMessages<'a>,
+    flags: i32,
+    phantom: PhantomData<A>,
+}
+
+#[cfg(target_os = "linux")]
+impl<'a, A: SocketAddress> PooledMessage<'a, A> {
+    /// The received part of the packet
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The number of octets received, or the real length of the packet if `MSG_TRUNC` was passed
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// The source address of the packet, converted on demand
+    pub fn address(&self) -> Result<A> {
+        A::from_storage(self.address, self.address_length)
+    }
+
+    /// The control messages received along with the packet, decoded on demand
+    pub fn control(&self) -> ControlMessages<'a> {
+        self.control.clone()
+    }
+
+    /// The `msg_flags` of the packet, e.g. `MSG_TRUNC`
+    pub fn flags(&self) -> i32 {
+        self.flags
+    }
+
+    /// true iff the packet was longer than the pool's buffers, and its end was discarded
+    pub fn is_truncated(&self) -> bool {
+        self.flags & MSG_TRUNC != 0
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<'a, A: SocketAddress> std::fmt::Debug for PooledMessage<'a, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PooledMessage")
+            .field("length", &self.length)
+            .field("flags", &self.flags)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_os = "linux")]
+    use bs_system::consts::SCTP_UNORDERED;
+
+    #[test]
+    fn control_roundtrip() {
+        let mut messages = vec![
+            ControlMessage::IpTtl(64),
+            ControlMessage::Rights(vec![0, 1, 2]),
+            ControlMessage::Other {
+                level: 1,
+                type_: 2,
+                data: vec![3; 5],
+            },
+        ];
+        if cfg!(target_os = "linux") {
+            messages.extend(vec![
+                ControlMessage::IpPacketInfo {
+                    interface_index: 2,
+                    local: Ipv4Addr::new(10, 0, 0, 1),
+                    destination: Ipv4Addr::BROADCAST,
+                },
+                ControlMessage::Ip6PacketInfo {
+                    interface_index: 3,
+                    address: Ipv6Addr::LOCALHOST,
+                },
+                ControlMessage::Timestamp(UNIX_EPOCH + Duration::new(1_600_000_000, 123)),
+                ControlMessage::UdpSegment(1200),
+                ControlMessage::Credentials(Credentials::current()),
+                ControlMessage::SctpInfo(
+                    SctpInfo::new(3)
+                        .with_ppid(42)
+                        .with_flags(SCTP_UNORDERED)
+                        .with_association_id(7),
+                ),
+            ]);
+        }
+        let (control, length) = encode_control(&messages);
+        assert_eq!(decode_control(&control, length), messages);
+    }
+
+    #[test]
+    fn control_malformed() {
+        let messages = vec![ControlMessage::Other {
+            level: IPPROTO_IP,
+            type_: IP_TTL,
+            data: vec![1, 2],
+        }];
+        let (control, length) = encode_control(&messages);
+        assert_eq!(decode_control(&control, length), messages);
+        // a message cut short is dropped
+        let cut = unsafe { CMSG_LEN(2) } as usize - 1;
+        assert_eq!(decode_control(&control, cut), vec![]);
+    }
+}
@@ -1,7 +1,8 @@
-use crate::socket::SocketKind;
 use crate::socket::PROTO_NULL;
-use crate::socket::{SetFilter, Socket};
+use crate::socket::{Bind, NewSocket, SetFilter, Socket, SocketKind};
+use bs_filter::layer::Transport;
 use libc::{AF_INET, SOCK_STREAM};
+use std::net::SocketAddrV4;
 use std::os::unix::io::RawFd;
 
 #[derive(Debug, Copy, Clone)]
@@ -10,6 +11,8 @@ pub struct MockSocket {
 }
 
 impl SocketKind for MockSocket {
+    type Address = SocketAddrV4;
+
     fn new(fd: RawFd) -> Self {
         Self { fd }
     }
@@ -27,4 +30,12 @@ impl SocketKind for MockSocket {
     }
 }
 
-impl SetFilter for Socket<MockSocket> {}
+impl NewSocket for MockSocket {}
+
+impl Bind for Socket<MockSocket> {
+    type Address = SocketAddrV4;
+}
+
+impl SetFilter for Socket<MockSocket> {
+    type Layer = Transport;
+}
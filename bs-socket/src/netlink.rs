@@ -0,0 +1,510 @@
+use crate::address::{NetlinkAddress, SocketAddress};
+use crate::message::{read_bytes, read_i32, read_u16};
+use crate::socket::private::PrivateBasicSocket;
+#[cfg(feature = "bs-filter")]
+use crate::socket::SetFilter;
+use crate::socket::{Bind, NewSocket, Receive, ReceiveFrom, SendTo, Socket, SocketKind};
+#[cfg(feature = "bs-filter")]
+use bs_filter::layer::Netlink;
+use bs_system::consts::{CTRL_ATTR_FAMILY_ID, CTRL_ATTR_FAMILY_NAME, CTRL_CMD_GETFAMILY};
+use bs_system::consts::{GENL_ID_CTRL, NLA_F_NESTED, NLA_HDRLEN, NLA_TYPE_MASK, NLMSG_ALIGNTO};
+use bs_system::consts::{NLMSG_DONE, NLMSG_ERROR, NLMSG_HDRLEN, NLMSG_NOOP};
+use bs_system::consts::{NLM_F_ACK, NLM_F_REQUEST};
+use bs_system::{Result, SystemError};
+use libc::{EINVAL, EMSGSIZE, ENOENT, MSG_TRUNC, SOCK_RAW};
+use libc::{NETLINK_ADD_MEMBERSHIP, NETLINK_DROP_MEMBERSHIP, SOL_NETLINK};
+use libc::{NETLINK_GENERIC, NETLINK_ROUTE, NETLINK_SOCK_DIAG};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Size of the buffer netlink messages are received to, enough for the largest messages of a dump
+const RECEIVE_BUFFER_SIZE: usize = 32768;
+
+/// Length of `struct genlmsghdr`, the header of generic netlink messages
+const GENERIC_HEADER_LENGTH: usize = 4;
+
+/// The sequence number of the next request, shared by all sockets
+static SEQUENCE: AtomicU32 = AtomicU32::new(1);
+
+/// The netlink family a [`NetlinkSocket`](struct.NetlinkSocket.html) talks to, see `netlink(7)`
+pub trait NetlinkFamily: Copy + Debug {
+    /// The family's protocol number, e.g. `NETLINK_ROUTE`
+    fn protocol() -> i32;
+}
+
+/// `NETLINK_ROUTE`, routes, links, addresses and queueing disciplines, see `rtnetlink(7)`
+#[derive(Debug, Copy, Clone)]
+pub struct Route;
+
+/// `NETLINK_GENERIC`, the families registered by kernel subsystems and modules, see `genl(8)`
+#[derive(Debug, Copy, Clone)]
+pub struct Generic;
+
+/// `NETLINK_SOCK_DIAG`, the sockets of the host, see `sock_diag(7)`
+#[derive(Debug, Copy, Clone)]
+pub struct SockDiag;
+
+impl NetlinkFamily for Route {
+    fn protocol() -> i32 {
+        NETLINK_ROUTE
+    }
+}
+
+impl NetlinkFamily for Generic {
+    fn protocol() -> i32 {
+        NETLINK_GENERIC
+    }
+}
+
+impl NetlinkFamily for SockDiag {
+    fn protocol() -> i32 {
+        NETLINK_SOCK_DIAG
+    }
+}
+
+/// `netlink(7)` socket, exchanging [`NetlinkMessage`](struct.NetlinkMessage.html)s with the
+/// kernel's `Family`
+///
+/// ```
+/// # use bs_socket::netlink::{NetlinkMessage, NetlinkSocket};
+/// # use bs_socket::socket::Socket;
+/// # use bs_system::consts::NLM_F_DUMP;
+/// # fn main() -> bs_system::Result<()> {
+/// let s: Socket<NetlinkSocket> = Socket::new()?;
+/// // RTM_GETLINK, followed by an empty struct ifinfomsg
+/// let request = NetlinkMessage::new(libc::RTM_GETLINK)
+///     .with_flags(NLM_F_DUMP)
+///     .with_payload(&[0; 16]);
+/// let links = s.request(request)?;
+/// assert!(!links.is_empty());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct NetlinkSocket<Family: NetlinkFamily = Route> {
+    fd: RawFd,
+    family: PhantomData<Family>,
+}
+
+impl<Family: NetlinkFamily> SocketKind for NetlinkSocket<Family> {
+    type Address = NetlinkAddress;
+
+    fn new(fd: RawFd) -> Self {
+        Self {
+            fd,
+            family: PhantomData,
+        }
+    }
+    fn domain() -> i32 {
+        NetlinkAddress::family()
+    }
+    fn type_() -> i32 {
+        SOCK_RAW
+    }
+    fn protocol() -> i32 {
+        Family::protocol()
+    }
+    fn os(&self) -> i32 {
+        self.fd
+    }
+}
+
+impl<Family: NetlinkFamily> NewSocket for NetlinkSocket<Family> {}
+
+impl<Family: NetlinkFamily> Bind for Socket<NetlinkSocket<Family>> {
+    type Address = NetlinkAddress;
+}
+
+impl<Family: NetlinkFamily> SendTo for Socket<NetlinkSocket<Family>> {}
+
+impl<Family: NetlinkFamily> Receive for Socket<NetlinkSocket<Family>> {}
+
+impl<Family: NetlinkFamily> ReceiveFrom for Socket<NetlinkSocket<Family>> {}
+
+#[cfg(feature = "bs-filter")]
+impl<Family: NetlinkFamily> SetFilter for Socket<NetlinkSocket<Family>> {
+    type Layer = Netlink;
+}
+
+fn align(length: usize) -> usize {
+    (length + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1)
+}
+
+fn pad(data: &mut Vec<u8>) {
+    data.resize(align(data.len()), 0);
+}
+
+/// A netlink message, an `nlmsghdr` followed by the family specific payload
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NetlinkMessage {
+    type_: u16,
+    flags: u16,
+    sequence: u32,
+    port: u32,
+    payload: Vec<u8>,
+}
+
+impl NetlinkMessage {
+    /// Creates a new message of type `type_` (`nlmsg_type`), e.g. `RTM_GETLINK`, with an empty
+    /// payload
+    pub fn new(type_: u16) -> Self {
+        Self {
+            type_,
+            flags: 0,
+            sequence: 0,
+            port: 0,
+            payload: Vec::new(),
+        }
+    }
+
+    /// Sets the `NLM_F_*` flags of the message, e.g. `NLM_F_DUMP`
+    pub fn with_flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets the sequence number of the message, which replies to it carry as well
+    pub fn with_sequence(mut self, sequence: u32) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Appends `data` to the payload, e.g. the family specific header, padded to the netlink
+    /// alignment
+    pub fn with_payload(mut self, data: &[u8]) -> Self {
+        self.payload.extend_from_slice(data);
+        pad(&mut self.payload);
+        self
+    }
+
+    /// Appends `attribute` to the payload
+    pub fn with_attribute(mut self, attribute: &Attribute) -> Self {
+        attribute.write(&mut self.payload);
+        self
+    }
+
+    /// The type of the message (`nlmsg_type`)
+    pub fn type_(&self) -> u16 {
+        self.type_
+    }
+
+    /// The `NLM_F_*` flags of the message
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// The sequence number of the message
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    /// The port of the message's sender (`nlmsg_pid`), 0 for the kernel
+    pub fn port(&self) -> u32 {
+        self.port
+    }
+
+    /// The payload of the message, following its `nlmsghdr`
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Parses the attributes of the message, following the family specific header of
+    /// `header_length` octets at the start of the payload, e.g. 16 for `struct ifinfomsg`
+    ///
+    /// # Errors
+    /// `EINVAL` if the payload is too short for the header or holds malformed attributes
+    pub fn attributes(&self, header_length: usize) -> Result<Vec<Attribute>> {
+        let attributes = self
+            .payload
+            .get(align(header_length)..)
+            .ok_or(SystemError::from_raw_os_error(EINVAL))?;
+        Attribute::parse(attributes)
+    }
+
+    /// The outcome of the request a message ends: `Ok` for acknowledgements and for the
+    /// `NLMSG_DONE` ending a dump, the error reported by the kernel otherwise
+    ///
+    /// `None` for messages of any other type.
+    pub fn outcome(&self) -> Option<Result<()>> {
+        let error = match self.type_ {
+            // struct nlmsgerr, followed by the request's header
+            NLMSG_ERROR => read_i32(&self.payload, 0).unwrap_or(-EINVAL),
+            // older kernels end dumps without reporting an error
+            NLMSG_DONE => read_i32(&self.payload, 0).unwrap_or(0),
+            _ => return None,
+        };
+        if error < 0 {
+            Some(Err(SystemError::from_raw_os_error(-error)))
+        } else {
+            Some(Ok(()))
+        }
+    }
+
+    /// Encodes the message, `nlmsghdr` included
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let length = (NLMSG_HDRLEN + self.payload.len()) as u32;
+        let mut data = Vec::with_capacity(length as usize);
+        data.extend_from_slice(&length.to_ne_bytes());
+        data.extend_from_slice(&self.type_.to_ne_bytes());
+        data.extend_from_slice(&self.flags.to_ne_bytes());
+        data.extend_from_slice(&self.sequence.to_ne_bytes());
+        data.extend_from_slice(&self.port.to_ne_bytes());
+        data.extend_from_slice(&self.payload);
+        data
+    }
+
+    /// Parses the messages packed in `data`, e.g. a single datagram received from the kernel
+    ///
+    /// # Errors
+    /// `EINVAL` if a message's length is inconsistent with `data`
+    pub fn parse(data: &[u8]) -> Result<Vec<Self>> {
+        let mut messages = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let length = read_i32(data, offset).ok_or(SystemError::from_raw_os_error(EINVAL))?
+                as u32 as usize;
+            if length < NLMSG_HDRLEN {
+                return Err(SystemError::from_raw_os_error(EINVAL));
+            }
+            let message =
+                read_bytes(data, offset, length).ok_or(SystemError::from_raw_os_error(EINVAL))?;
+            messages.push(Self {
+                type_: read_u16(message, 4).ok_or(SystemError::from_raw_os_error(EINVAL))?,
+                flags: read_u16(message, 6).ok_or(SystemError::from_raw_os_error(EINVAL))?,
+                sequence: read_i32(message, 8).ok_or(SystemError::from_raw_os_error(EINVAL))?
+                    as u32,
+                port: read_i32(message, 12).ok_or(SystemError::from_raw_os_error(EINVAL))? as u32,
+                payload: message[NLMSG_HDRLEN..].to_vec(),
+            });
+            offset += align(length);
+        }
+        Ok(messages)
+    }
+}
+
+/// A netlink attribute, an `nlattr` followed by its data, which may hold nested attributes
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Attribute {
+    type_: u16,
+    data: Vec<u8>,
+}
+
+impl Attribute {
+    /// Creates a new attribute of type `type_`, e.g. `IFLA_IFNAME`, holding the already encoded
+    /// `data`
+    pub fn new(type_: u16, data: &[u8]) -> Self {
+        Self {
+            type_,
+            data: data.to_vec(),
+        }
+    }
+
+    /// An attribute holding a single octet
+    pub fn u8(type_: u16, value: u8) -> Self {
+        Self::new(type_, &[value])
+    }
+
+    /// An attribute holding a u16, in host byte order
+    pub fn u16(type_: u16, value: u16) -> Self {
+        Self::new(type_, &value.to_ne_bytes())
+    }
+
+    /// An attribute holding a u32, in host byte order
+    pub fn u32(type_: u16, value: u32) -> Self {
+        Self::new(type_, &value.to_ne_bytes())
+    }
+
+    /// An attribute holding the NUL terminated `value`
+    pub fn string(type_: u16, value: &str) -> Self {
+        let mut data = value.as_bytes().to_vec();
+        data.push(0);
+        Self { type_, data }
+    }
+
+    /// An attribute holding the nested `attributes`, flagged `NLA_F_NESTED`
+    pub fn nested(type_: u16, attributes: &[Attribute]) -> Self {
+        let mut data = Vec::new();
+        for attribute in attributes {
+            attribute.write(&mut data);
+        }
+        Self {
+            type_: type_ | NLA_F_NESTED,
+            data,
+        }
+    }
+
+    /// The type of the attribute, without the `NLA_F_*` flags
+    pub fn type_(&self) -> u16 {
+        self.type_ & NLA_TYPE_MASK
+    }
+
+    /// true iff the attribute is flagged as holding nested attributes
+    pub fn is_nested(&self) -> bool {
+        self.type_ & NLA_F_NESTED != 0
+    }
+
+    /// The data of the attribute
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The data of the attribute as a single octet, if it's that long
+    pub fn as_u8(&self) -> Option<u8> {
+        match self.data[..] {
+            [value] => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The data of the attribute as a u16, if it's that long
+    pub fn as_u16(&self) -> Option<u16> {
+        match self.data[..] {
+            [a, b] => Some(u16::from_ne_bytes([a, b])),
+            _ => None,
+        }
+    }
+
+    /// The data of the attribute as a u32, if it's that long
+    pub fn as_u32(&self) -> Option<u32> {
+        match self.data[..] {
+            [a, b, c, d] => Some(u32::from_ne_bytes([a, b, c, d])),
+            _ => None,
+        }
+    }
+
+    /// The data of the attribute as a string, up to its terminating NUL, if it's UTF-8
+    pub fn as_str(&self) -> Option<&str> {
+        let end = self
+            .data
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(self.data.len());
+        std::str::from_utf8(&self.data[..end]).ok()
+    }
+
+    /// Parses the nested attributes the attribute holds
+    ///
+    /// # Errors
+    /// `EINVAL` if the data of the attribute isn't a sequence of attributes
+    pub fn attributes(&self) -> Result<Vec<Attribute>> {
+        Self::parse(&self.data)
+    }
+
+    /// Parses the attributes packed in `data`
+    ///
+    /// # Errors
+    /// `EINVAL` if an attribute's length is inconsistent with `data`
+    pub fn parse(data: &[u8]) -> Result<Vec<Self>> {
+        let mut attributes = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let length =
+                read_u16(data, offset).ok_or(SystemError::from_raw_os_error(EINVAL))? as usize;
+            if length < NLA_HDRLEN {
+                return Err(SystemError::from_raw_os_error(EINVAL));
+            }
+            let attribute =
+                read_bytes(data, offset, length).ok_or(SystemError::from_raw_os_error(EINVAL))?;
+            attributes.push(Self {
+                type_: read_u16(attribute, 2).ok_or(SystemError::from_raw_os_error(EINVAL))?,
+                data: attribute[NLA_HDRLEN..].to_vec(),
+            });
+            offset += align(length);
+        }
+        Ok(attributes)
+    }
+
+    fn write(&self, buffer: &mut Vec<u8>) {
+        let length = (NLA_HDRLEN + self.data.len()) as u16;
+        buffer.extend_from_slice(&length.to_ne_bytes());
+        buffer.extend_from_slice(&self.type_.to_ne_bytes());
+        buffer.extend_from_slice(&self.data);
+        pad(buffer);
+    }
+}
+
+impl<Family: NetlinkFamily> Socket<NetlinkSocket<Family>> {
+    /// Joins the multicast group `group`, e.g. `RTNLGRP_LINK`, to receive the notifications
+    /// sent to it (`NETLINK_ADD_MEMBERSHIP`)
+    pub fn join_group(&mut self, group: u32) -> Result<&mut Self> {
+        self.set_int_option(SOL_NETLINK, NETLINK_ADD_MEMBERSHIP, group as i32)
+    }
+
+    /// Leaves the multicast group `group` (`NETLINK_DROP_MEMBERSHIP`)
+    pub fn leave_group(&mut self, group: u32) -> Result<&mut Self> {
+        self.set_int_option(SOL_NETLINK, NETLINK_DROP_MEMBERSHIP, group as i32)
+    }
+
+    /// Sends `message` to the kernel, returns the number of bytes sent
+    pub fn send_netlink(&self, message: &NetlinkMessage) -> Result<usize> {
+        self.send_buffer_to(&message.to_bytes(), &NetlinkAddress::kernel(), 0)
+    }
+
+    /// Receives a datagram of netlink messages, e.g. a part of a dump or a notification
+    ///
+    /// # Errors
+    /// `EMSGSIZE` if the datagram didn't fit in the receive buffer, in which case it's lost
+    pub fn receive_netlink(&self, flags: i32) -> Result<Vec<NetlinkMessage>> {
+        let mut buffer = vec![0; RECEIVE_BUFFER_SIZE];
+        // MSG_TRUNC reports the datagram's real length
+        let length = self.recv(&mut buffer, flags | MSG_TRUNC)?;
+        if length > buffer.len() {
+            return Err(SystemError::from_raw_os_error(EMSGSIZE));
+        }
+        NetlinkMessage::parse(&buffer[..length])
+    }
+
+    /// Sends `message` to the kernel as an acknowledged request, and returns the replies to it,
+    /// e.g. the entries of a dump requested with `NLM_F_DUMP`
+    ///
+    /// The request is assigned the next sequence number, and received messages carrying other
+    /// sequence numbers, e.g. notifications of joined groups, are dropped.
+    ///
+    /// # Errors
+    /// The error the kernel reports for the request
+    pub fn request(&self, message: NetlinkMessage) -> Result<Vec<NetlinkMessage>> {
+        let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+        let flags = message.flags() | NLM_F_REQUEST | NLM_F_ACK;
+        let request = message.with_flags(flags).with_sequence(sequence);
+        let _ = self.send_netlink(&request)?;
+
+        let mut replies = Vec::new();
+        loop {
+            for reply in self.receive_netlink(0)? {
+                if reply.sequence() != sequence || reply.type_() == NLMSG_NOOP {
+                    continue;
+                }
+                match reply.outcome() {
+                    Some(outcome) => return outcome.map(|_| replies),
+                    None => replies.push(reply),
+                }
+            }
+        }
+    }
+}
+
+impl Socket<NetlinkSocket<Generic>> {
+    /// Resolves the id of the generic netlink family `name`, e.g. `"nlctrl"`, the type of the
+    /// messages sent to the family
+    ///
+    /// # Errors
+    /// `ENOENT` if no family is registered as `name`
+    pub fn family_id(&self, name: &str) -> Result<u16> {
+        // struct genlmsghdr { cmd, version, reserved }
+        let request = NetlinkMessage::new(GENL_ID_CTRL)
+            .with_payload(&[CTRL_CMD_GETFAMILY, 1, 0, 0])
+            .with_attribute(&Attribute::string(CTRL_ATTR_FAMILY_NAME, name));
+        for reply in self.request(request)? {
+            for attribute in reply.attributes(GENERIC_HEADER_LENGTH)? {
+                if attribute.type_() == CTRL_ATTR_FAMILY_ID {
+                    return attribute
+                        .as_u16()
+                        .ok_or(SystemError::from_raw_os_error(EINVAL));
+                }
+            }
+        }
+        Err(SystemError::from_raw_os_error(ENOENT))
+    }
+}
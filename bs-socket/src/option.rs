@@ -0,0 +1,172 @@
+use crate::address::IpFamily;
+use crate::raw::{IpProtocol, RawSocket};
+use crate::socket::private::PrivateBasicSocket;
+use crate::socket::{Ipv4SocketKind, Ipv6SocketKind, Socket, SocketKind};
+use crate::tcp::{TcpSocket, TcpState};
+use crate::udp::UdpSocket;
+pub use bs_system::option::*;
+use bs_system::{GetSocketOption, Result, SetSocketOption, SocketOption};
+use cfg_if::cfg_if;
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use crate::packet::PacketSocketKind;
+    }
+}
+
+/// A `SocketOption` available for `Socket<S>`
+///
+/// Options are only available for the socket kinds of their level, e.g. `tcp(7)` options such
+/// as `TcpNoDelay` are only available for `TcpSocket`s, and `ip(7)` options such as `IpTtl` for
+/// IPv4 sockets.
+///
+/// ```compile_fail
+/// # use bs_socket::option::TcpNoDelay;
+/// # use bs_socket::socket::Socket;
+/// # use bs_socket::udp::UdpSocket;
+/// let mut s: Socket<UdpSocket> = Socket::new().unwrap();
+/// // `TCP_NODELAY` is a `tcp(7)` option
+/// s.set_option(TcpNoDelay::new(true)).unwrap();
+/// ```
+pub trait OptionFor<S: SocketKind>: SocketOption {}
+
+impl<S: SocketKind> Socket<S> {
+    /// Sets `option` on the socket (`setsockopt(2)`)
+    ///
+    /// ```
+    /// # use bs_socket::option::{ReuseAddress, TcpNoDelay};
+    /// # use bs_socket::socket::Socket;
+    /// # use bs_socket::tcp::TcpSocket;
+    /// # fn main() -> bs_system::Result<()> {
+    /// let mut s: Socket<TcpSocket> = Socket::new()?;
+    /// s.set_option(ReuseAddress::new(true))?
+    ///     .set_option(TcpNoDelay::new(true))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_option<O: SetSocketOption + OptionFor<S>>(
+        &mut self,
+        option: O,
+    ) -> Result<&mut Self> {
+        let _ = option.set(self.os())?;
+        Ok(self)
+    }
+
+    /// Returns the socket's current value of the option `O` (`getsockopt(2)`)
+    pub fn option<O: GetSocketOption + OptionFor<S>>(&self) -> Result<O> {
+        O::get(self.os())
+    }
+}
+
+/// Makes each of the options available for the socket kind
+macro_rules! options_for {
+    ($generics:tt $kind:ty: $($option:ident),* $(,)?) => {
+        $(options_for!(@impl $generics $kind: $option);)*
+    };
+    (@impl [$($param:ident: $bound:path),*] $kind:ty: $option:ident) => {
+        impl<$($param: $bound),*> OptionFor<$kind> for $option {}
+    };
+}
+
+options_for!([S: SocketKind] S:
+    AcceptConnections,
+    Broadcast,
+    DontRoute,
+    Error,
+    KeepAlive,
+    Linger,
+    ReceiveBuffer,
+    ReceiveLowWatermark,
+    ReceiveTimeout,
+    ReuseAddress,
+    ReusePort,
+    SendBuffer,
+    SendTimeout,
+    Type,
+);
+
+options_for!([S: Ipv4SocketKind] S:
+    IpMulticastLoop,
+    IpMulticastTtl,
+    IpReceiveTypeOfService,
+    IpReceiveTtl,
+    IpTypeOfService,
+    IpTtl,
+);
+
+options_for!([P: IpProtocol] RawSocket<P>: IpHeaderIncluded);
+
+options_for!([S: Ipv6SocketKind] S:
+    Ipv6MulticastHops,
+    Ipv6MulticastLoop,
+    Ipv6ReceiveHopLimit,
+    Ipv6ReceivePacketInfo,
+    Ipv6ReceiveTrafficClass,
+    Ipv6TrafficClass,
+    Ipv6UnicastHops,
+    Ipv6V6Only,
+);
+
+options_for!([State: TcpState, F: IpFamily] TcpSocket<State, F>:
+    TcpKeepAliveCount,
+    TcpKeepAliveInterval,
+    TcpMaxSegment,
+    TcpNoDelay,
+);
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        options_for!([S: SocketKind] S:
+            BindToDevice,
+            BusyPoll,
+            DetachFilter,
+            Domain,
+            LockFilter,
+            Mark,
+            PassCredentials,
+            Priority,
+            Protocol,
+            Timestamp,
+            TimestampNs,
+            Timestamping,
+        );
+
+        options_for!([S: Ipv4SocketKind] S:
+            IpBindAddressNoPort,
+            IpFreeBind,
+            IpMtuDiscover,
+            IpPacketInfo,
+            IpReceiveError,
+            IpTransparent,
+        );
+
+        options_for!([State: TcpState, F: IpFamily] TcpSocket<State, F>:
+            TcpCongestion,
+            TcpCork,
+            TcpDeferAccept,
+            TcpFastOpen,
+            TcpInfo,
+            TcpKeepAliveIdle,
+            TcpNotSentLowWatermark,
+            TcpQuickAck,
+            TcpUserTimeout,
+        );
+
+        options_for!([F: IpFamily] UdpSocket<F>:
+            UdpCork,
+            UdpGenericReceiveOffload,
+            UdpSegment,
+        );
+
+        options_for!([S: PacketSocketKind] S:
+            PacketAuxData,
+            PacketIgnoreOutgoing,
+            PacketLoss,
+            PacketQdiscBypass,
+            PacketReserve,
+            PacketStatistics,
+            PacketTimestamp,
+            PacketVersion,
+        );
+    }
+}
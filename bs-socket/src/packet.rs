@@ -1,16 +1,79 @@
-use crate::socket::SocketKind;
-use libc::{AF_PACKET, ETH_P_ALL, SOCK_DGRAM, SOCK_RAW};
+use crate::address::LinkAddress;
+use crate::ring::set_packet_option;
+use crate::socket::private::PrivateBasicSocket;
+#[cfg(feature = "bs-filter")]
+use crate::socket::SetFilter;
+use crate::socket::{Bind, NewSocket, Receive, ReceiveFrom, SendTo, Socket, SocketKind};
+#[cfg(feature = "bs-filter")]
+use bs_filter::layer::{Ethernet, Network};
+#[cfg(feature = "bs-filter")]
+use bs_filter::AttachSteering;
+use bs_system::consts::*;
+use bs_system::{Operation, Result, SystemError};
+use eui48::MacAddress;
+use libc::{AF_PACKET, EINVAL, ETH_P_ALL, SOCK_DGRAM, SOCK_RAW};
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
 use std::os::unix::io::RawFd;
 
-/// Raw layer 2 `packet(7)` socket
+/// Returns the index of the interface named `name` (`if_nametoindex(3)`)
+pub fn interface_index(name: &str) -> Result<i32> {
+    let name = CString::new(name).map_err(|_| SystemError::from_raw_os_error(EINVAL))?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(SystemError::last_os_error().with_operation(Operation::Syscall("if_nametoindex"))),
+        index => Ok(index as i32),
+    }
+}
+
+/// `packet(7)` socket kinds
+#[doc(hidden)]
+pub trait PacketSocketKind: SocketKind {}
+
+/// The link layer protocol a `packet(7)` socket receives
+pub trait LinkProtocol: Copy + std::fmt::Debug {
+    /// The protocol's EtherType, in host byte order
+    fn ether_type() -> u16;
+}
+
+/// The link layer protocol with EtherType `N`, in host byte order
+#[derive(Debug, Clone, Copy)]
+pub struct EtherType<const N: u16>;
+
+impl<const N: u16> LinkProtocol for EtherType<N> {
+    fn ether_type() -> u16 {
+        N
+    }
+}
+
+/// Every link layer protocol (`ETH_P_ALL`)
+pub type AllEtherTypes = EtherType<{ ETH_P_ALL as u16 }>;
+
+/// Raw layer 2 `packet(7)` socket, receiving frames of the link layer protocol `E`
+///
+/// ```no_run
+/// # use bs_socket::packet::{EtherType, PacketLayer2Socket};
+/// # use bs_socket::socket::Socket;
+/// # fn main() -> bs_system::Result<()> {
+/// // LLDP frames only
+/// let s: Socket<PacketLayer2Socket<EtherType<0x88cc>>> = Socket::new()?;
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug, Clone, Copy)]
-pub struct PacketLayer2Socket {
+pub struct PacketLayer2Socket<E: LinkProtocol = AllEtherTypes> {
     fd: RawFd,
+    protocol: PhantomData<E>,
 }
 
-impl SocketKind for PacketLayer2Socket {
+impl<E: LinkProtocol> SocketKind for PacketLayer2Socket<E> {
+    type Address = LinkAddress;
+
     fn new(fd: RawFd) -> Self {
-        Self { fd }
+        Self {
+            fd,
+            protocol: PhantomData,
+        }
     }
     fn domain() -> i32 {
         AF_PACKET
@@ -19,22 +82,42 @@ impl SocketKind for PacketLayer2Socket {
         SOCK_RAW
     }
     fn protocol() -> i32 {
-        (ETH_P_ALL as u16).to_be() as i32
+        E::ether_type().to_be().into()
     }
     fn os(&self) -> i32 {
         self.fd
     }
 }
 
-/// Raw layer 3 `packet(7)` socket
+impl<E: LinkProtocol> NewSocket for PacketLayer2Socket<E> {}
+
+impl<E: LinkProtocol> PacketSocketKind for PacketLayer2Socket<E> {}
+
+impl<E: LinkProtocol> Bind for Socket<PacketLayer2Socket<E>> {
+    type Address = LinkAddress;
+}
+
+impl<S: PacketSocketKind> Receive for Socket<S> {}
+
+impl<E: LinkProtocol> SendTo for Socket<PacketLayer2Socket<E>> {}
+
+impl<E: LinkProtocol> ReceiveFrom for Socket<PacketLayer2Socket<E>> {}
+
+/// Raw layer 3 `packet(7)` socket, receiving packets of the link layer protocol `E`
 #[derive(Debug, Clone, Copy)]
-pub struct PacketLayer3Socket {
+pub struct PacketLayer3Socket<E: LinkProtocol = AllEtherTypes> {
     fd: RawFd,
+    protocol: PhantomData<E>,
 }
 
-impl SocketKind for PacketLayer3Socket {
+impl<E: LinkProtocol> SocketKind for PacketLayer3Socket<E> {
+    type Address = LinkAddress;
+
     fn new(fd: RawFd) -> Self {
-        Self { fd }
+        Self {
+            fd,
+            protocol: PhantomData,
+        }
     }
     fn domain() -> i32 {
         AF_PACKET
@@ -43,9 +126,342 @@ impl SocketKind for PacketLayer3Socket {
         SOCK_DGRAM
     }
     fn protocol() -> i32 {
-        (ETH_P_ALL as u16).to_be() as i32
+        E::ether_type().to_be().into()
     }
     fn os(&self) -> i32 {
         self.fd
     }
 }
+
+impl<E: LinkProtocol> NewSocket for PacketLayer3Socket<E> {}
+
+impl<E: LinkProtocol> PacketSocketKind for PacketLayer3Socket<E> {}
+
+impl<E: LinkProtocol> Bind for Socket<PacketLayer3Socket<E>> {
+    type Address = LinkAddress;
+}
+
+impl<E: LinkProtocol> SendTo for Socket<PacketLayer3Socket<E>> {}
+
+impl<E: LinkProtocol> ReceiveFrom for Socket<PacketLayer3Socket<E>> {}
+
+#[cfg(feature = "bs-filter")]
+impl<E: LinkProtocol> SetFilter for Socket<PacketLayer2Socket<E>> {
+    type Layer = Ethernet;
+}
+
+#[cfg(feature = "bs-filter")]
+impl<E: LinkProtocol> SetFilter for Socket<PacketLayer3Socket<E>> {
+    type Layer = Network;
+}
+
+/// The way a `PACKET_FANOUT` group steers packets between its sockets
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FanoutMode {
+    /// By the packet's flow hash, keeping flows on a single socket (`PACKET_FANOUT_HASH`)
+    Hash,
+    /// Round-robin (`PACKET_FANOUT_LB`)
+    LoadBalance,
+    /// By the CPU the packet arrived on (`PACKET_FANOUT_CPU`)
+    Cpu,
+    /// To a single socket, moving on to the next once it's backlogged (`PACKET_FANOUT_ROLLOVER`)
+    Rollover,
+    /// Randomly (`PACKET_FANOUT_RND`)
+    Random,
+    /// By the packet's recorded receive queue (`PACKET_FANOUT_QM`)
+    QueueMapping,
+    /// By a classic BPF [`Steering`] program (`PACKET_FANOUT_CBPF`)
+    ///
+    /// [`Steering`]: ../../bs_filter/struct.Steering.html
+    Classic,
+    /// By an extended BPF [`Steering`] program (`PACKET_FANOUT_EBPF`)
+    ///
+    /// [`Steering`]: ../../bs_filter/struct.Steering.html
+    Extended,
+}
+
+impl FanoutMode {
+    fn value(self) -> u16 {
+        match self {
+            FanoutMode::Hash => PACKET_FANOUT_HASH,
+            FanoutMode::LoadBalance => PACKET_FANOUT_LB,
+            FanoutMode::Cpu => PACKET_FANOUT_CPU,
+            FanoutMode::Rollover => PACKET_FANOUT_ROLLOVER,
+            FanoutMode::Random => PACKET_FANOUT_RND,
+            FanoutMode::QueueMapping => PACKET_FANOUT_QM,
+            FanoutMode::Classic => PACKET_FANOUT_CBPF,
+            FanoutMode::Extended => PACKET_FANOUT_EBPF,
+        }
+    }
+}
+
+/// A `PACKET_FANOUT` group, spreading the packets its sockets capture between them
+///
+/// A group is identified by its `id`, and created by the first socket joining it. Every socket
+/// joining it after that must be bound to the same interface and protocol, and agree on its mode
+/// and flags.
+///
+/// # Example
+/// ```no_run
+/// # use bs_socket::packet::{Fanout, FanoutMode, PacketLayer2Socket};
+/// # use bs_socket::socket::Socket;
+/// # fn main() -> bs_system::Result<()> {
+/// let fanout = Fanout::new(42, FanoutMode::Hash).defrag(true);
+/// let mut workers = Vec::new();
+/// for _ in 0..4 {
+///     let mut s: Socket<PacketLayer2Socket> = Socket::new()?;
+///     let _ = s.join_fanout(&fanout)?;
+///     workers.push(s);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Fanout {
+    id: u16,
+    mode: FanoutMode,
+    defrag: bool,
+    rollover: bool,
+    ignore_outgoing: bool,
+}
+
+impl Fanout {
+    /// The group `id`, steering packets according to `mode`
+    pub fn new(id: u16, mode: FanoutMode) -> Self {
+        Self {
+            id,
+            mode,
+            defrag: false,
+            rollover: false,
+            ignore_outgoing: false,
+        }
+    }
+
+    /// Sets whether IP fragments are reassembled before being steered, so all fragments of a
+    /// packet reach the same socket (`PACKET_FANOUT_FLAG_DEFRAG`)
+    pub fn defrag(mut self, defrag: bool) -> Self {
+        self.defrag = defrag;
+        self
+    }
+
+    /// Sets whether packets are steered to another socket when the selected one is backlogged
+    /// (`PACKET_FANOUT_FLAG_ROLLOVER`)
+    pub fn rollover(mut self, rollover: bool) -> Self {
+        self.rollover = rollover;
+        self
+    }
+
+    /// Sets whether packets sent by the host are left out of the group
+    /// (`PACKET_FANOUT_FLAG_IGNORE_OUTGOING`)
+    ///
+    /// Steering programs see outgoing packets from their link layer header, unlike incoming
+    /// ones, see [`set_fanout_program`](../socket/struct.Socket.html#method.set_fanout_program).
+    pub fn ignore_outgoing(mut self, ignore_outgoing: bool) -> Self {
+        self.ignore_outgoing = ignore_outgoing;
+        self
+    }
+
+    /// The group's id
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// The group's mode
+    pub fn mode(&self) -> FanoutMode {
+        self.mode
+    }
+
+    fn option(&self) -> u32 {
+        let mut type_flags = self.mode.value();
+        if self.defrag {
+            type_flags |= PACKET_FANOUT_FLAG_DEFRAG;
+        }
+        if self.rollover {
+            type_flags |= PACKET_FANOUT_FLAG_ROLLOVER;
+        }
+        if self.ignore_outgoing {
+            type_flags |= PACKET_FANOUT_FLAG_IGNORE_OUTGOING;
+        }
+        u32::from(type_flags) << 16 | u32::from(self.id)
+    }
+}
+
+impl<S: PacketSocketKind> Socket<S> {
+    /// Joins the `PACKET_FANOUT` group `fanout`, creating it if it doesn't exist
+    ///
+    /// The socket stays in the group until it's closed.
+    pub fn join_fanout(&mut self, fanout: &Fanout) -> Result<&mut Self> {
+        set_packet_option(self.os(), PACKET_FANOUT, &fanout.option())?;
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "bs-filter")]
+impl<S: PacketSocketKind> Socket<S> {
+    /// Sets the steering program of the `PACKET_FANOUT` group the socket joined, replacing the
+    /// group's existing program if already set
+    ///
+    /// The group's mode must be [`Classic`](enum.FanoutMode.html#variant.Classic) for classic
+    /// programs, or [`Extended`](enum.FanoutMode.html#variant.Extended) for extended ones.
+    /// Steering programs see incoming packets from their network layer header regardless of the
+    /// socket kind, as they run before the link layer header is restored for
+    /// `PacketLayer2Socket`s. Outgoing packets still start at their link layer header, so
+    /// groups steering by `Network` layer offsets should
+    /// [`ignore_outgoing`](struct.Fanout.html#method.ignore_outgoing) packets.
+    pub fn set_fanout_program(
+        &mut self,
+        program: impl AttachSteering<Network>,
+    ) -> Result<&mut Self> {
+        let _ = program.attach(self.os())?;
+        Ok(self)
+    }
+}
+
+/// `struct packet_mreq`
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct MembershipRequest {
+    interface_index: i32,
+    type_: u16,
+    address_length: u16,
+    address: [u8; 8],
+}
+
+/// A `PACKET_ADD_MEMBERSHIP` of a `packet(7)` socket, making an interface receive packets it
+/// would otherwise discard
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Membership {
+    /// Receive every packet on the interface (`PACKET_MR_PROMISC`)
+    Promiscuous {
+        /// The interface's index
+        interface_index: i32,
+    },
+    /// Receive every multicast packet on the interface (`PACKET_MR_ALLMULTI`)
+    AllMulticast {
+        /// The interface's index
+        interface_index: i32,
+    },
+    /// Receive packets sent to the multicast group `address` on the interface
+    /// (`PACKET_MR_MULTICAST`)
+    Multicast {
+        /// The interface's index
+        interface_index: i32,
+        /// The group's MAC address
+        address: MacAddress,
+    },
+}
+
+impl Membership {
+    fn request(&self) -> MembershipRequest {
+        let (interface_index, type_, mac) = match *self {
+            Membership::Promiscuous { interface_index } => {
+                (interface_index, PACKET_MR_PROMISC, None)
+            }
+            Membership::AllMulticast { interface_index } => {
+                (interface_index, PACKET_MR_ALLMULTI, None)
+            }
+            Membership::Multicast {
+                interface_index,
+                address,
+            } => (interface_index, PACKET_MR_MULTICAST, Some(address)),
+        };
+        let mut address = [0; 8];
+        let address_length = match mac {
+            Some(mac) => {
+                address[..6].copy_from_slice(mac.as_bytes());
+                6
+            }
+            None => 0,
+        };
+        MembershipRequest {
+            interface_index,
+            type_,
+            address_length,
+            address,
+        }
+    }
+}
+
+/// A [`Membership`](enum.Membership.html) added to a `packet(7)` socket, dropped along with the
+/// guard
+///
+/// Memberships are reference counted by the kernel, so an interface stays e.g. promiscuous as
+/// long as any socket holds a promiscuous membership of it.
+#[derive(Debug)]
+pub struct MembershipGuard<'a, S: PacketSocketKind> {
+    socket: &'a mut Socket<S>,
+    membership: Membership,
+}
+
+impl<'a, S: PacketSocketKind> MembershipGuard<'a, S> {
+    /// The guarded membership
+    pub fn membership(&self) -> Membership {
+        self.membership
+    }
+
+    /// Drops the membership right away, reporting failures which dropping the guard ignores
+    pub fn drop_membership(self) -> Result<()> {
+        let result = self.leave();
+        std::mem::forget(self);
+        result
+    }
+
+    fn leave(&self) -> Result<()> {
+        set_packet_option(
+            self.socket.os(),
+            PACKET_DROP_MEMBERSHIP,
+            &self.membership.request(),
+        )
+    }
+}
+
+impl<'a, S: PacketSocketKind> Deref for MembershipGuard<'a, S> {
+    type Target = Socket<S>;
+
+    fn deref(&self) -> &Socket<S> {
+        self.socket
+    }
+}
+
+impl<'a, S: PacketSocketKind> DerefMut for MembershipGuard<'a, S> {
+    fn deref_mut(&mut self) -> &mut Socket<S> {
+        self.socket
+    }
+}
+
+impl<'a, S: PacketSocketKind> Drop for MembershipGuard<'a, S> {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+
+impl<S: PacketSocketKind> Socket<S> {
+    /// Binds the socket to the interface with index `interface_index`, receiving only packets
+    /// of EtherType `protocol` in host byte order, or packets of every EtherType for
+    /// `ETH_P_ALL`
+    pub fn bind_interface_index(
+        &mut self,
+        interface_index: i32,
+        protocol: u16,
+    ) -> Result<&mut Self> {
+        self.bind_address(&LinkAddress::new(interface_index, protocol))
+    }
+
+    /// Binds the socket to the interface named `name`, see
+    /// [`bind_interface_index`](#method.bind_interface_index)
+    pub fn bind_interface(&mut self, name: &str, protocol: u16) -> Result<&mut Self> {
+        self.bind_interface_index(interface_index(name)?, protocol)
+    }
+
+    /// Adds `membership` to the socket, until the returned guard is dropped
+    ///
+    /// The guard dereferences to the socket, which can still be used while it holds the
+    /// membership.
+    pub fn add_membership(&mut self, membership: Membership) -> Result<MembershipGuard<'_, S>> {
+        set_packet_option(self.os(), PACKET_ADD_MEMBERSHIP, &membership.request())?;
+        Ok(MembershipGuard {
+            socket: self,
+            membership,
+        })
+    }
+}
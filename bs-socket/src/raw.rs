@@ -1,27 +1,208 @@
-use crate::socket::SocketKind;
-use libc::{AF_INET, IPPROTO_RAW, SOCK_RAW};
+use crate::address::{Inet, Inet6, IpFamily, SocketAddress};
+use crate::option::IpHeaderIncluded;
+use crate::socket::private::PrivateBasicSocket;
+#[cfg(all(target_os = "linux", feature = "bs-filter"))]
+use crate::socket::SetFilter;
+use crate::socket::{
+    Bind, Connect, Ipv4SocketKind, Ipv6SocketKind, NewSocket, Receive, ReceiveFrom, SendTo,
+};
+use crate::socket::{Socket, SocketKind};
+#[cfg(all(target_os = "linux", feature = "bs-filter"))]
+use bs_filter::layer::{Network, Transport};
+use bs_system::Result;
+use libc::{IPPROTO_ICMP, IPPROTO_ICMPV6, IPPROTO_IGMP, IPPROTO_RAW, IPPROTO_TCP, IPPROTO_UDP};
+use libc::{IPPROTO_IPV6, IPV6_CHECKSUM, SOCK_RAW};
+use std::fmt::Debug;
+use std::marker::PhantomData;
 use std::os::unix::io::RawFd;
 
-/// `raw(7)` layer 3 socket
+/// The IP protocol a [`RawSocket`](struct.RawSocket.html) sends and receives
+pub trait IpProtocol: Copy + Debug {
+    /// The protocol's number, as in `/etc/protocols`
+    fn number() -> i32;
+}
+
+/// `IPPROTO_RAW`, which can only send packets, and always includes their IP header
+#[derive(Debug, Clone, Copy)]
+pub struct Raw;
+
+/// `IPPROTO_ICMP`
+#[derive(Debug, Clone, Copy)]
+pub struct Icmp;
+
+/// `IPPROTO_ICMPV6`
+#[derive(Debug, Clone, Copy)]
+pub struct Icmp6;
+
+/// `IPPROTO_IGMP`
 #[derive(Debug, Clone, Copy)]
-pub struct RawSocket {
+pub struct Igmp;
+
+/// `IPPROTO_TCP`
+#[derive(Debug, Clone, Copy)]
+pub struct Tcp;
+
+/// `IPPROTO_UDP`
+#[derive(Debug, Clone, Copy)]
+pub struct Udp;
+
+/// The IP protocol numbered `N`
+#[derive(Debug, Clone, Copy)]
+pub struct Proto<const N: u8>;
+
+impl IpProtocol for Raw {
+    fn number() -> i32 {
+        IPPROTO_RAW
+    }
+}
+
+impl IpProtocol for Icmp {
+    fn number() -> i32 {
+        IPPROTO_ICMP
+    }
+}
+
+impl IpProtocol for Icmp6 {
+    fn number() -> i32 {
+        IPPROTO_ICMPV6
+    }
+}
+
+impl IpProtocol for Igmp {
+    fn number() -> i32 {
+        IPPROTO_IGMP
+    }
+}
+
+impl IpProtocol for Tcp {
+    fn number() -> i32 {
+        IPPROTO_TCP
+    }
+}
+
+impl IpProtocol for Udp {
+    fn number() -> i32 {
+        IPPROTO_UDP
+    }
+}
+
+impl<const N: u8> IpProtocol for Proto<N> {
+    fn number() -> i32 {
+        N.into()
+    }
+}
+
+/// `raw(7)` layer 3 socket, sending and receiving packets of the IP protocol `P` over `ip(7)`,
+/// or over `ipv6(7)` for `RawSocket<P, Inet6>`
+///
+/// Unlike IPv4 raw sockets, IPv6 raw sockets never include the IPv6 header in the packets they
+/// send or receive.
+///
+/// ```no_run
+/// # use bs_socket::raw::{Icmp, RawSocket};
+/// # use bs_socket::socket::{ReceiveFrom, Socket};
+/// # fn main() -> bs_system::Result<()> {
+/// let s: Socket<RawSocket<Icmp>> = Socket::new()?;
+/// let mut buffer = [0; 1500];
+/// let (length, from) = s.receive_from(&mut buffer, 0)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RawSocket<P: IpProtocol = Raw, F: IpFamily = Inet> {
     fd: RawFd,
+    protocol: PhantomData<(P, F)>,
 }
 
-impl SocketKind for RawSocket {
+/// `raw(7)` layer 3 socket over `ipv6(7)`
+pub type Raw6Socket<P = Raw> = RawSocket<P, Inet6>;
+
+impl<P: IpProtocol, F: IpFamily> SocketKind for RawSocket<P, F> {
+    type Address = F::Address;
+
     fn new(fd: RawFd) -> Self {
-        Self { fd }
+        Self {
+            fd,
+            protocol: PhantomData,
+        }
     }
     fn domain() -> i32 {
-        AF_INET
+        F::Address::family()
     }
     fn type_() -> i32 {
         SOCK_RAW
     }
     fn protocol() -> i32 {
-        IPPROTO_RAW
+        P::number()
     }
     fn os(&self) -> i32 {
         self.fd
     }
 }
+
+impl<P: IpProtocol, F: IpFamily> NewSocket for RawSocket<P, F> {}
+
+impl<P: IpProtocol> Ipv4SocketKind for RawSocket<P, Inet> {}
+impl<P: IpProtocol> Ipv6SocketKind for RawSocket<P, Inet6> {}
+
+impl<P: IpProtocol, F: IpFamily> Bind for Socket<RawSocket<P, F>> {
+    type Address = F::Address;
+}
+
+impl<P: IpProtocol, F: IpFamily> SendTo for Socket<RawSocket<P, F>> {}
+
+impl<P: IpProtocol, F: IpFamily> Receive for Socket<RawSocket<P, F>> {}
+
+impl<P: IpProtocol, F: IpFamily> ReceiveFrom for Socket<RawSocket<P, F>> {}
+
+impl<P: IpProtocol, F: IpFamily> Connect for Socket<RawSocket<P, F>> {}
+
+impl<P: IpProtocol> Socket<RawSocket<P>> {
+    /// Sets whether sent packets include their IP header (`IP_HDRINCL`), rather than having
+    /// the kernel prepend one
+    ///
+    /// Always set for `RawSocket<Raw>`.
+    pub fn set_header_included(&mut self, included: bool) -> Result<&mut Self> {
+        self.set_option(IpHeaderIncluded::new(included))
+    }
+
+    /// Returns whether sent packets include their IP header (`IP_HDRINCL`)
+    pub fn header_included(&self) -> Result<bool> {
+        self.option().map(IpHeaderIncluded::value)
+    }
+}
+
+impl<P: IpProtocol> Socket<RawSocket<P, Inet6>> {
+    /// Sets the offset in the packet's payload at which the kernel stores the checksum of sent
+    /// packets and verifies the checksum of received ones (`IPV6_CHECKSUM`), or disables
+    /// checksum handling for `None`
+    ///
+    /// The kernel always handles the checksums of `RawSocket<Icmp6, Inet6>`s.
+    pub fn set_checksum_offset(&mut self, offset: Option<u16>) -> Result<&mut Self> {
+        let offset = offset.map_or(-1, i32::from);
+        self.set_int_option(IPPROTO_IPV6, IPV6_CHECKSUM, offset)
+    }
+
+    /// Returns the offset of the checksum in the packet's payload (`IPV6_CHECKSUM`), if the
+    /// kernel handles it
+    pub fn checksum_offset(&self) -> Result<Option<u16>> {
+        self.int_option(IPPROTO_IPV6, IPV6_CHECKSUM).map(|offset| {
+            if offset < 0 {
+                None
+            } else {
+                Some(offset as u16)
+            }
+        })
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "bs-filter"))]
+impl<P: IpProtocol> SetFilter for Socket<RawSocket<P>> {
+    type Layer = Network;
+}
+
+// the IPv6 header is pulled before packets reach IPv6 raw sockets
+#[cfg(all(target_os = "linux", feature = "bs-filter"))]
+impl<P: IpProtocol> SetFilter for Socket<RawSocket<P, Inet6>> {
+    type Layer = Transport;
+}
@@ -0,0 +1,824 @@
+use crate::option::{PacketLoss, PacketVersion};
+use crate::packet::{AllEtherTypes, LinkProtocol, PacketLayer2Socket, PacketSocketKind};
+use crate::socket::Socket;
+use bs_system::consts::{PACKET_RX_RING, PACKET_TX_RING};
+use bs_system::consts::{TPACKET_ALIGNMENT, TPACKET_V2, TPACKET_V3};
+use bs_system::consts::{TP_FT_REQ_FILL_RXHASH, TP_STATUS_KERNEL, TP_STATUS_USER};
+use bs_system::consts::{TP_STATUS_SENDING, TP_STATUS_SEND_REQUEST, TP_STATUS_WRONG_FORMAT};
+use bs_system::consts::{TP_STATUS_VLAN_TPID_VALID, TP_STATUS_VLAN_VALID};
+use bs_system::{cvt, Context, Operation, Result, SystemError};
+use libc::{
+    c_void, sockaddr_ll, socklen_t, _SC_PAGESIZE, MAP_FAILED, MAP_SHARED, POLLIN, POLLOUT,
+    PROT_READ, PROT_WRITE, SOL_PACKET,
+};
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr::{read_volatile, write_volatile, NonNull};
+use std::slice::{from_raw_parts, from_raw_parts_mut};
+use std::sync::atomic::{fence, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// `struct tpacket_req3`
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct RingRequest {
+    block_size: u32,
+    block_count: u32,
+    frame_size: u32,
+    frame_count: u32,
+    retire_timeout: u32,
+    private_size: u32,
+    feature_request: u32,
+}
+
+/// `struct tpacket_block_desc` with its `struct tpacket_hdr_v1`
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct BlockDescriptor {
+    version: u32,
+    offset_to_private: u32,
+    status: u32,
+    frame_count: u32,
+    offset_to_first_frame: u32,
+    length: u32,
+    sequence_number: u64,
+    first_timestamp: [u32; 2],
+    last_timestamp: [u32; 2],
+}
+
+/// `struct tpacket3_hdr`
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct FrameHeader {
+    next_offset: u32,
+    seconds: u32,
+    nanoseconds: u32,
+    snap_length: u32,
+    length: u32,
+    status: u32,
+    mac_offset: u16,
+    network_offset: u16,
+    rxhash: u32,
+    vlan_tci: u32,
+    vlan_tpid: u16,
+    padding: u16,
+    padding_end: [u8; 8],
+}
+
+/// `TPACKET_ALIGN`, `length` rounded up to a multiple of `TPACKET_ALIGNMENT`
+const fn tpacket_align(length: usize) -> usize {
+    (length + TPACKET_ALIGNMENT - 1) & !(TPACKET_ALIGNMENT - 1)
+}
+
+/// The room a `TPACKET_V3` frame needs for its header and the `sockaddr_ll` following it
+const TPACKET3_HDRLEN: usize = tpacket_align(size_of::<FrameHeader>()) + size_of::<sockaddr_ll>();
+
+/// Checks the layout constraints `packet_set_ring` enforces, so a bad configuration is reported
+/// by name rather than as a bare `EINVAL`
+fn validate_layout(block_size: u32, frame_size: u32, header_length: usize) -> Result<()> {
+    let page_size = unsafe { libc::sysconf(_SC_PAGESIZE) } as u32;
+    let error = if (frame_size as usize) < header_length {
+        format!(
+            "frame size {} is smaller than the {} octet frame header",
+            frame_size, header_length
+        )
+    } else if frame_size as usize & (TPACKET_ALIGNMENT - 1) != 0 {
+        format!(
+            "frame size {} isn't a multiple of TPACKET_ALIGNMENT ({})",
+            frame_size, TPACKET_ALIGNMENT
+        )
+    } else if block_size == 0 || block_size & (page_size - 1) != 0 {
+        format!(
+            "block size {} isn't a multiple of the page size ({})",
+            block_size, page_size
+        )
+    } else if block_size < frame_size {
+        format!(
+            "block size {} is smaller than the frame size {}",
+            block_size, frame_size
+        )
+    } else {
+        return Ok(());
+    };
+    Err(SystemError::Validation(error))
+}
+
+/// The number of frames in `block_count` blocks of `frames_per_block` frames
+fn frame_count(frames_per_block: u32, block_count: u32) -> Result<u32> {
+    frames_per_block.checked_mul(block_count).ok_or_else(|| {
+        SystemError::Validation(format!(
+            "{} blocks of {} frames hold more frames than a ring can",
+            block_count, frames_per_block
+        ))
+    })
+}
+
+pub(crate) fn set_packet_option<T>(fd: RawFd, name: i32, value: &T) -> Result<()> {
+    let ptr: *const T = value;
+    unsafe {
+        let _ = cvt(libc::setsockopt(
+            fd,
+            SOL_PACKET,
+            name,
+            ptr as *const c_void,
+            size_of::<T>() as socklen_t,
+        ))
+        .context(Operation::Syscall("setsockopt"))?;
+    }
+    Ok(())
+}
+
+/// A memory mapping of a `packet(7)` ring, unmapped on drop
+#[derive(Debug)]
+pub(crate) struct Mapping {
+    ptr: NonNull<u8>,
+    length: usize,
+}
+
+impl Mapping {
+    pub(crate) fn new(fd: RawFd, length: usize) -> Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                length,
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED,
+                fd,
+                0,
+            )
+        };
+        let mmap = Operation::Syscall("mmap");
+        if ptr == MAP_FAILED {
+            return Err(SystemError::last_os_error().with_operation(mmap));
+        }
+        NonNull::new(ptr as *mut u8)
+            .map(|ptr| Self { ptr, length })
+            .ok_or_else(|| SystemError::last_os_error().with_operation(mmap))
+    }
+
+    pub(crate) fn at(&self, offset: usize) -> *mut u8 {
+        debug_assert!(offset < self.length);
+        unsafe { self.ptr.as_ptr().add(offset) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        let _ = unsafe { libc::munmap(self.ptr.as_ptr() as *mut c_void, self.length) };
+    }
+}
+
+// the mapping is only accessed through the ring that owns it
+unsafe impl Send for Mapping {}
+
+/// Waits up to `timeout` for `fd` to become readable, returns false on timeout
+pub(crate) fn poll(fd: RawFd, events: i16, timeout: Option<Duration>) -> Result<bool> {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events,
+        revents: 0,
+    };
+    let timeout = timeout.map_or(-1, |timeout| {
+        timeout.as_millis().min(i32::MAX as u128) as i32
+    });
+    let n =
+        unsafe { cvt(libc::poll(&mut pollfd, 1, timeout)) }.context(Operation::Syscall("poll"))?;
+    Ok(n > 0)
+}
+
+/// Layout of a [`RxRing`](struct.RxRing.html)
+///
+/// The ring is made of `block_count` blocks of `block_size` octets, each holding as many frames
+/// as fit in it. The kernel hands a block over once it's full, or once `retire_timeout` passed
+/// since its first frame was written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RxRingConfig {
+    block_size: u32,
+    block_count: u32,
+    frame_size: u32,
+    retire_timeout: Duration,
+    fill_rxhash: bool,
+}
+
+impl RxRingConfig {
+    /// A ring of `block_count` blocks of `block_size` octets each
+    ///
+    /// `block_size` must be a multiple of the page size
+    pub fn new(block_size: u32, block_count: u32) -> Self {
+        Self {
+            block_size,
+            block_count,
+            frame_size: 2048,
+            retire_timeout: Duration::from_millis(60),
+            fill_rxhash: false,
+        }
+    }
+
+    /// Sets the nominal frame size, which bounds the size of each captured frame and must be a
+    /// multiple of `TPACKET_ALIGNMENT`, defaults to 2048
+    pub fn frame_size(mut self, frame_size: u32) -> Self {
+        self.frame_size = frame_size;
+        self
+    }
+
+    /// Sets the time after which a partially filled block is handed over, defaults to 60ms
+    pub fn retire_timeout(mut self, retire_timeout: Duration) -> Self {
+        self.retire_timeout = retire_timeout;
+        self
+    }
+
+    /// Sets whether the kernel fills the flow hash of each frame
+    pub fn fill_rxhash(mut self, fill_rxhash: bool) -> Self {
+        self.fill_rxhash = fill_rxhash;
+        self
+    }
+
+    fn request(&self) -> Result<RingRequest> {
+        validate_layout(self.block_size, self.frame_size, TPACKET3_HDRLEN)?;
+        let frames_per_block = self.block_size / self.frame_size;
+        Ok(RingRequest {
+            block_size: self.block_size,
+            block_count: self.block_count,
+            frame_size: self.frame_size,
+            frame_count: frame_count(frames_per_block, self.block_count)?,
+            retire_timeout: self.retire_timeout.as_millis().min(u32::MAX as u128) as u32,
+            private_size: 0,
+            feature_request: if self.fill_rxhash {
+                TP_FT_REQ_FILL_RXHASH
+            } else {
+                0
+            },
+        })
+    }
+}
+
+impl Default for RxRingConfig {
+    fn default() -> Self {
+        Self::new(1 << 20, 16)
+    }
+}
+
+/// A `TPACKET_V3` `PACKET_RX_RING`, a memory-mapped ring the kernel captures packets into
+///
+/// The ring owns its `Socket`, which can still be used to e.g. set a filter. Blocks are taken in
+/// order with [`next_block`](#method.next_block), and handed back to the kernel once dropped.
+///
+/// # Example
+/// ```no_run
+/// # use bs_socket::packet::PacketLayer2Socket;
+/// # use bs_socket::ring::{RxRing, RxRingConfig};
+/// # use bs_socket::socket::Socket;
+/// # fn main() -> bs_system::Result<()> {
+/// let s: Socket<PacketLayer2Socket> = Socket::new()?;
+/// let mut ring = RxRing::new(s, RxRingConfig::default())?;
+/// loop {
+///     let _ = ring.wait(None)?;
+///     while let Some(block) = ring.next_block() {
+///         for frame in block.frames() {
+///             println!("{} octets", frame.length());
+///         }
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RxRing<S: PacketSocketKind> {
+    mapping: Mapping,
+    block_size: usize,
+    block_count: usize,
+    current: usize,
+    socket: Socket<S>,
+}
+
+impl<S: PacketSocketKind> RxRing<S> {
+    /// Sets up a ring laid out by `config` for `socket`
+    pub fn new(mut socket: Socket<S>, config: RxRingConfig) -> Result<Self> {
+        let request = config.request()?;
+        let _ = socket.set_option(PacketVersion::new(TPACKET_V3))?;
+        let fd = socket.as_raw_fd();
+        set_packet_option(fd, PACKET_RX_RING, &request)?;
+        let block_size = request.block_size as usize;
+        let block_count = request.block_count as usize;
+        let mapping = Mapping::new(fd, block_size * block_count)?;
+        Ok(Self {
+            mapping,
+            block_size,
+            block_count,
+            current: 0,
+            socket,
+        })
+    }
+
+    /// The ring's socket
+    pub fn socket(&self) -> &Socket<S> {
+        &self.socket
+    }
+
+    /// The ring's socket, e.g. for setting a filter
+    pub fn socket_mut(&mut self) -> &mut Socket<S> {
+        &mut self.socket
+    }
+
+    /// The number of blocks in the ring
+    pub fn block_count(&self) -> usize {
+        self.block_count
+    }
+
+    /// Waits up to `timeout`, or indefinitely if `None`, for the next block to be handed over,
+    /// returns false on timeout
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<bool> {
+        if self.is_ready() {
+            return Ok(true);
+        }
+        poll(self.socket.as_raw_fd(), POLLIN, timeout).map(|_| self.is_ready())
+    }
+
+    /// Takes the next block if the kernel handed it over, the block is handed back when dropped
+    pub fn next_block(&mut self) -> Option<Block<'_>> {
+        if !self.is_ready() {
+            return None;
+        }
+        Some(Block {
+            descriptor: self.descriptor(),
+            current: &mut self.current,
+            block_count: self.block_count,
+            data: PhantomData,
+        })
+    }
+
+    fn descriptor(&self) -> *mut BlockDescriptor {
+        self.mapping.at(self.current * self.block_size) as *mut BlockDescriptor
+    }
+
+    fn is_ready(&self) -> bool {
+        let status = unsafe { read_volatile(&(*self.descriptor()).status) };
+        // pairs with the kernel's write barrier before handing the block over
+        fence(Ordering::Acquire);
+        status & TP_STATUS_USER != 0
+    }
+}
+
+/// A block of frames handed over by the kernel, borrowed from a [`RxRing`](struct.RxRing.html)
+///
+/// The block, and all its frames, are handed back to the kernel when the block is dropped.
+#[derive(Debug)]
+pub struct Block<'a> {
+    descriptor: *mut BlockDescriptor,
+    current: &'a mut usize,
+    block_count: usize,
+    data: PhantomData<&'a [u8]>,
+}
+
+impl<'a> Block<'a> {
+    /// The number of frames in the block
+    pub fn len(&self) -> usize {
+        unsafe { (*self.descriptor).frame_count as usize }
+    }
+
+    /// true iff the block holds no frames, as when it's retired by timeout
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The sequence number of the block, which increases with every block the kernel hands over
+    pub fn sequence_number(&self) -> u64 {
+        unsafe { (*self.descriptor).sequence_number }
+    }
+
+    /// An iterator over the frames in the block
+    pub fn frames(&self) -> Frames<'_> {
+        let base = self.descriptor as *const u8;
+        let offset = unsafe { (*self.descriptor).offset_to_first_frame } as usize;
+        Frames {
+            next: unsafe { base.add(offset) },
+            remaining: self.len(),
+            data: PhantomData,
+        }
+    }
+}
+
+impl<'a> Drop for Block<'a> {
+    fn drop(&mut self) {
+        // the frames must be read before the block is handed back
+        fence(Ordering::Release);
+        unsafe { write_volatile(&mut (*self.descriptor).status, TP_STATUS_KERNEL) };
+        *self.current = (*self.current + 1) % self.block_count;
+    }
+}
+
+/// An iterator over the frames of a [`Block`](struct.Block.html)
+#[derive(Debug)]
+pub struct Frames<'a> {
+    next: *const u8,
+    remaining: usize,
+    data: PhantomData<&'a [u8]>,
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Frame<'a>;
+
+    fn next(&mut self) -> Option<Frame<'a>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let header = unsafe { &*(self.next as *const FrameHeader) };
+        let data = unsafe {
+            from_raw_parts(
+                self.next.add(header.mac_offset as usize),
+                header.snap_length as usize,
+            )
+        };
+        self.next = unsafe { self.next.add(header.next_offset as usize) };
+        Some(Frame { header, data })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+/// A frame captured into a [`RxRing`](struct.RxRing.html)
+#[derive(Debug, Copy, Clone)]
+pub struct Frame<'a> {
+    header: &'a FrameHeader,
+    data: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    /// The captured part of the frame, starting at its link layer header for
+    /// `PacketLayer2Socket`s or at its network layer header for `PacketLayer3Socket`s
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The original length of the frame
+    pub fn length(&self) -> usize {
+        self.header.length as usize
+    }
+
+    /// true iff the frame was truncated to fit in the ring's frame size, or by a filter
+    pub fn is_truncated(&self) -> bool {
+        self.header.snap_length < self.header.length
+    }
+
+    /// The time the frame was captured
+    pub fn timestamp(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::new(self.header.seconds.into(), self.header.nanoseconds)
+    }
+
+    /// `TP_STATUS_*` flags of the frame
+    pub fn status(&self) -> u32 {
+        self.header.status
+    }
+
+    /// The flow hash of the frame, if requested with
+    /// [`RxRingConfig::fill_rxhash`](struct.RxRingConfig.html#method.fill_rxhash)
+    pub fn rxhash(&self) -> u32 {
+        self.header.rxhash
+    }
+
+    /// The VLAN tag control information, if the frame was VLAN tagged and the tag was stripped
+    pub fn vlan_tci(&self) -> Option<u16> {
+        if self.header.status & TP_STATUS_VLAN_VALID != 0 {
+            Some(self.header.vlan_tci as u16)
+        } else {
+            None
+        }
+    }
+
+    /// The VLAN tag protocol identifier, if the frame was VLAN tagged
+    pub fn vlan_tpid(&self) -> Option<u16> {
+        if self.header.status & TP_STATUS_VLAN_TPID_VALID != 0 {
+            Some(self.header.vlan_tpid)
+        } else {
+            None
+        }
+    }
+}
+
+/// `struct tpacket_req`
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct TxRingRequest {
+    block_size: u32,
+    block_count: u32,
+    frame_size: u32,
+    frame_count: u32,
+}
+
+/// `struct tpacket2_hdr`
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct TxFrameHeader {
+    status: u32,
+    length: u32,
+    snap_length: u32,
+    mac_offset: u16,
+    network_offset: u16,
+    seconds: u32,
+    nanoseconds: u32,
+    vlan_tci: u16,
+    vlan_tpid: u16,
+    padding: [u8; 4],
+}
+
+/// The offset of a frame's data in its slot, `TPACKET2_HDRLEN - sizeof(struct sockaddr_ll)`
+const TX_DATA_OFFSET: usize = tpacket_align(size_of::<TxFrameHeader>());
+
+/// The room a `TPACKET_V2` frame needs for its header and the `sockaddr_ll` following it
+const TPACKET2_HDRLEN: usize = TX_DATA_OFFSET + size_of::<sockaddr_ll>();
+
+/// Layout of a [`TxRing`](struct.TxRing.html)
+///
+/// The ring is made of `block_count` blocks of `block_size` octets, each split into as many
+/// frame slots as fit in it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TxRingConfig {
+    block_size: u32,
+    block_count: u32,
+    frame_size: u32,
+    drop_malformed: bool,
+}
+
+impl TxRingConfig {
+    /// A ring of `block_count` blocks of `block_size` octets each
+    ///
+    /// `block_size` must be a multiple of the page size
+    pub fn new(block_size: u32, block_count: u32) -> Self {
+        Self {
+            block_size,
+            block_count,
+            frame_size: 2048,
+            drop_malformed: false,
+        }
+    }
+
+    /// Sets the size of each frame slot, including its header, which must be a multiple of
+    /// `TPACKET_ALIGNMENT`, defaults to 2048
+    pub fn frame_size(mut self, frame_size: u32) -> Self {
+        self.frame_size = frame_size;
+        self
+    }
+
+    /// Sets whether the kernel silently drops frames it rejects (`PACKET_LOSS`) rather than
+    /// stopping at them and marking them as [`Rejected`](enum.TxStatus.html#variant.Rejected),
+    /// defaults to false
+    pub fn drop_malformed(mut self, drop_malformed: bool) -> Self {
+        self.drop_malformed = drop_malformed;
+        self
+    }
+
+    fn request(&self) -> Result<TxRingRequest> {
+        validate_layout(self.block_size, self.frame_size, TPACKET2_HDRLEN)?;
+        let frames_per_block = self.block_size / self.frame_size;
+        Ok(TxRingRequest {
+            block_size: self.block_size,
+            block_count: self.block_count,
+            frame_size: self.frame_size,
+            frame_count: frame_count(frames_per_block, self.block_count)?,
+        })
+    }
+}
+
+impl Default for TxRingConfig {
+    fn default() -> Self {
+        Self::new(1 << 16, 16)
+    }
+}
+
+/// The status of a frame slot of a [`TxRing`](struct.TxRing.html)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TxStatus {
+    /// The slot is free to be filled
+    Available,
+    /// The slot was submitted and awaits the next [`flush`](struct.TxRing.html#method.flush)
+    Pending,
+    /// The kernel is transmitting the slot's frame
+    Sending,
+    /// The kernel rejected the slot's frame, e.g. for being shorter than a link layer header,
+    /// and won't transmit any further frames until it's submitted again
+    Rejected,
+}
+
+/// A `TPACKET_V2` `PACKET_TX_RING`, a memory-mapped ring of frame slots the kernel transmits
+///
+/// Frames are written in place into slots taken in order with [`next_slot`](#method.next_slot),
+/// and submitted slots are transmitted in a batch by [`flush`](#method.flush). The ring's socket
+/// must be bound to an interface, which all frames are sent on.
+///
+/// # Example
+/// ```no_run
+/// # use bs_socket::address::LinkAddress;
+/// # use bs_socket::packet::PacketLayer2Socket;
+/// # use bs_socket::ring::{TxRing, TxRingConfig};
+/// # use bs_socket::socket::{Bind, Socket};
+/// # fn main() -> bs_system::Result<()> {
+/// let mut s: Socket<PacketLayer2Socket> = Socket::new()?;
+/// let _ = s.bind(&LinkAddress::new(1, 0x88b5))?;
+/// let mut ring = TxRing::new(s, TxRingConfig::default())?;
+/// while let Some(mut slot) = ring.next_slot() {
+///     let frame = slot.buffer_mut();
+///     frame[..6].copy_from_slice(&[0xff; 6]);
+///     frame[12..14].copy_from_slice(&0x88b5_u16.to_be_bytes());
+///     slot.submit(60)?;
+/// }
+/// let _ = ring.flush()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TxRing<E: LinkProtocol = AllEtherTypes> {
+    mapping: Mapping,
+    block_size: usize,
+    frames_per_block: usize,
+    frame_size: usize,
+    frame_count: usize,
+    current: usize,
+    socket: Socket<PacketLayer2Socket<E>>,
+}
+
+impl<E: LinkProtocol> TxRing<E> {
+    /// Sets up a ring laid out by `config` for `socket`
+    pub fn new(mut socket: Socket<PacketLayer2Socket<E>>, config: TxRingConfig) -> Result<Self> {
+        let request = config.request()?;
+        let _ = socket.set_option(PacketVersion::new(TPACKET_V2))?;
+        if config.drop_malformed {
+            let _ = socket.set_option(PacketLoss::new(true))?;
+        }
+        let fd = socket.as_raw_fd();
+        set_packet_option(fd, PACKET_TX_RING, &request)?;
+        let length = request.block_size as usize * request.block_count as usize;
+        let mapping = Mapping::new(fd, length)?;
+        Ok(Self {
+            mapping,
+            block_size: request.block_size as usize,
+            frames_per_block: (request.block_size / request.frame_size) as usize,
+            frame_size: request.frame_size as usize,
+            frame_count: request.frame_count as usize,
+            current: 0,
+            socket,
+        })
+    }
+
+    /// The ring's socket
+    pub fn socket(&self) -> &Socket<PacketLayer2Socket<E>> {
+        &self.socket
+    }
+
+    /// The ring's socket, e.g. for setting socket options
+    pub fn socket_mut(&mut self) -> &mut Socket<PacketLayer2Socket<E>> {
+        &mut self.socket
+    }
+
+    /// The number of frame slots in the ring
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// The largest frame a slot can hold
+    pub fn frame_capacity(&self) -> usize {
+        self.frame_size - TX_DATA_OFFSET
+    }
+
+    /// The status of the `index`th frame slot
+    ///
+    /// # Panics
+    /// if `index` is out of the ring's bounds
+    pub fn status(&self, index: usize) -> TxStatus {
+        assert!(index < self.frame_count);
+        let status = unsafe { read_volatile(&(*self.header(index)).status) };
+        // pairs with the kernel's write barrier before handing the slot back
+        fence(Ordering::Acquire);
+        if status & TP_STATUS_WRONG_FORMAT != 0 {
+            TxStatus::Rejected
+        } else if status & TP_STATUS_SENDING != 0 {
+            TxStatus::Sending
+        } else if status & TP_STATUS_SEND_REQUEST != 0 {
+            TxStatus::Pending
+        } else {
+            TxStatus::Available
+        }
+    }
+
+    /// The number of frames submitted or being transmitted
+    pub fn pending(&self) -> usize {
+        (0..self.frame_count)
+            .map(|index| self.status(index))
+            .filter(|&status| status == TxStatus::Pending || status == TxStatus::Sending)
+            .count()
+    }
+
+    /// Waits up to `timeout`, or indefinitely if `None`, for the next slot to be available,
+    /// returns false on timeout
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<bool> {
+        if self.is_ready() {
+            return Ok(true);
+        }
+        poll(self.socket.as_raw_fd(), POLLOUT, timeout).map(|_| self.is_ready())
+    }
+
+    /// Takes the next slot if it's available, the slot is only handed to the kernel once
+    /// [`submit`](struct.Slot.html#method.submit)ted
+    pub fn next_slot(&mut self) -> Option<Slot<'_>> {
+        if !self.is_ready() {
+            return None;
+        }
+        let index = self.current;
+        Some(self.slot(index, true))
+    }
+
+    /// Takes the slot of the frame the kernel rejected, if any, so it can be fixed and
+    /// submitted again
+    pub fn rejected(&mut self) -> Option<Slot<'_>> {
+        let index =
+            (0..self.frame_count).find(|&index| self.status(index) == TxStatus::Rejected)?;
+        Some(self.slot(index, false))
+    }
+
+    /// Starts transmitting all submitted frames without waiting for them to be sent,
+    /// returns the number of octets queued
+    ///
+    /// Fails with the reason for the rejection if the kernel rejected a frame, see
+    /// [`rejected`](#method.rejected)
+    pub fn flush(&self) -> Result<usize> {
+        let fd = self.socket.as_raw_fd();
+        unsafe { cvt(libc::send(fd, std::ptr::null(), 0, libc::MSG_DONTWAIT)) }
+            .context(Operation::Syscall("send"))
+            .map(|n| n as usize)
+    }
+
+    fn header(&self, index: usize) -> *mut TxFrameHeader {
+        // slots never straddle blocks, which end with padding unless the frame size divides them
+        let block = index / self.frames_per_block;
+        let slot = index % self.frames_per_block;
+        self.mapping
+            .at(block * self.block_size + slot * self.frame_size) as *mut TxFrameHeader
+    }
+
+    fn is_ready(&self) -> bool {
+        self.status(self.current) == TxStatus::Available
+    }
+
+    fn slot(&mut self, index: usize, advance: bool) -> Slot<'_> {
+        let header = self.header(index);
+        let data = unsafe {
+            from_raw_parts_mut(
+                (header as *mut u8).add(TX_DATA_OFFSET),
+                self.frame_size - TX_DATA_OFFSET,
+            )
+        };
+        let frame_count = self.frame_count;
+        Slot {
+            header,
+            data,
+            current: if advance {
+                Some((&mut self.current, frame_count))
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// A frame slot borrowed from a [`TxRing`](struct.TxRing.html)
+///
+/// A slot dropped without being submitted stays available, and is taken again by the next call
+/// to [`next_slot`](struct.TxRing.html#method.next_slot).
+#[derive(Debug)]
+pub struct Slot<'a> {
+    header: *mut TxFrameHeader,
+    data: &'a mut [u8],
+    current: Option<(&'a mut usize, usize)>,
+}
+
+impl<'a> Slot<'a> {
+    /// The slot's buffer, a frame starting at its link layer header is written into it in place
+    pub fn buffer_mut(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    /// true iff the kernel rejected the frame previously submitted in this slot
+    pub fn was_rejected(&self) -> bool {
+        unsafe { read_volatile(&(*self.header).status) & TP_STATUS_WRONG_FORMAT != 0 }
+    }
+
+    /// Hands the first `length` octets of the slot's buffer to the kernel, to be transmitted
+    /// by the next [`flush`](struct.TxRing.html#method.flush)
+    pub fn submit(self, length: usize) -> Result<()> {
+        if length > self.data.len() {
+            return Err(SystemError::from_raw_os_error(libc::EMSGSIZE));
+        }
+        unsafe {
+            (*self.header).length = length as u32;
+            // the frame must be written before the slot is handed over
+            fence(Ordering::Release);
+            write_volatile(&mut (*self.header).status, TP_STATUS_SEND_REQUEST);
+        }
+        if let Some((current, frame_count)) = self.current {
+            *current = (*current + 1) % frame_count;
+        }
+        Ok(())
+    }
+}
@@ -1,26 +1,364 @@
-use crate::socket::SocketKind;
-use crate::socket::PROTO_NULL;
-use libc::{AF_INET, SOCK_SCTP};
-use std::os::unix::io::RawFd;
+use crate::address::{Inet, Inet6, IpFamily, SocketAddress};
+use crate::message::{read_i32, read_u16};
+use crate::socket::private::PrivateBasicSocket;
+#[cfg(feature = "bs-filter")]
+use crate::socket::SetFilter;
+use crate::socket::{
+    Bind, Connect, Ipv4SocketKind, Ipv6SocketKind, NewSocket, Receive, ReceiveFrom, SendTo,
+};
+use crate::socket::{Socket, SocketKind};
+#[cfg(feature = "bs-filter")]
+use bs_filter::layer::Transport;
+use bs_system::consts::{SCTP_ASSOC_CHANGE, SCTP_EVENTS, SCTP_SENDER_DRY_EVENT};
+use bs_system::consts::{SCTP_SHUTDOWN_EVENT, SCTP_SOCKOPT_BINDX_ADD, SCTP_SOCKOPT_BINDX_REM};
+use bs_system::consts::{SCTP_SOCKOPT_CONNECTX, SOL_SCTP};
+use bs_system::{cvt, Context, Operation, Result, SystemError};
+use libc::{c_void, sockaddr_storage, socklen_t};
+use libc::{EINTR, EINVAL, IPPROTO_SCTP, SOCK_SEQPACKET, SOCK_STREAM};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::os::unix::io::{FromRawFd, RawFd};
 
-pub struct UdpSocket {
+mod private {
+    pub trait Sealed {}
+}
+
+/// The style of an [`SctpSocket`](struct.SctpSocket.html), see `sctp(7)`
+pub trait SctpStyle: private::Sealed + Copy + Debug {
+    /// The socket type implementing the style
+    fn type_() -> i32;
+}
+
+/// The TCP-style interface (`SOCK_STREAM`), a socket per association
+#[derive(Debug, Copy, Clone)]
+pub struct OneToOne;
+
+/// The UDP-style interface (`SOCK_SEQPACKET`), a single socket for every association, which
+/// are set up implicitly on the first message sent to a peer
+#[derive(Debug, Copy, Clone)]
+pub struct OneToMany;
+
+impl private::Sealed for OneToOne {}
+impl private::Sealed for OneToMany {}
+
+impl SctpStyle for OneToOne {
+    fn type_() -> i32 {
+        SOCK_STREAM
+    }
+}
+
+impl SctpStyle for OneToMany {
+    fn type_() -> i32 {
+        SOCK_SEQPACKET
+    }
+}
+
+/// `sctp(7)` over `ip(7)`, or over `ipv6(7)` for `SctpSocket<Style, Inet6>`
+///
+/// Messages are sent on a specific stream by attaching a
+/// [`ControlMessage::SctpInfo`](../message/enum.ControlMessage.html#variant.SctpInfo) to them,
+/// and the stream of received messages is reported the same way once
+/// [`SctpEvent::DataIo`](enum.SctpEvent.html#variant.DataIo) is enabled.
+/// ```no_run
+/// # use bs_socket::message::{ControlMessage, Message, SctpInfo};
+/// # use bs_socket::sctp::{OneToMany, SctpEvent, SctpSocket};
+/// # use bs_socket::socket::{Bind, SendTo, Socket};
+/// # fn main() -> bs_system::Result<()> {
+/// let mut server: Socket<SctpSocket<OneToMany>> = Socket::new()?;
+/// server.bind(&"127.0.0.1:0".parse().unwrap())?;
+/// server.set_events(&[SctpEvent::DataIo])?.listen(5)?;
+///
+/// let client: Socket<SctpSocket<OneToMany>> = Socket::new()?;
+/// let message = Message::new(&[b"ping"])
+///     .to(server.local_address()?)
+///     .with_control(ControlMessage::SctpInfo(SctpInfo::new(1)));
+/// let _ = client.send_message(&message, 0)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct SctpSocket<Style: SctpStyle = OneToOne, F: IpFamily = Inet> {
     fd: RawFd,
+    style: PhantomData<(Style, F)>,
 }
 
-impl SocketKind for SctpSocket {
+/// `sctp(7)` over `ipv6(7)`
+pub type Sctp6Socket<Style = OneToOne> = SctpSocket<Style, Inet6>;
+
+impl<Style: SctpStyle, F: IpFamily> SocketKind for SctpSocket<Style, F> {
+    type Address = F::Address;
+
     fn new(fd: RawFd) -> Self {
-        Self { fd }
+        Self {
+            fd,
+            style: PhantomData,
+        }
     }
     fn domain() -> i32 {
-        AF_INET
+        F::Address::family()
     }
     fn type_() -> i32 {
-        SOCK_SCTP
+        Style::type_()
     }
     fn protocol() -> i32 {
-        PROTO_NULL
+        IPPROTO_SCTP
     }
     fn os(&self) -> i32 {
         self.fd
     }
 }
+
+impl<Style: SctpStyle, F: IpFamily> NewSocket for SctpSocket<Style, F> {}
+
+impl<Style: SctpStyle> Ipv4SocketKind for SctpSocket<Style, Inet> {}
+impl<Style: SctpStyle> Ipv6SocketKind for SctpSocket<Style, Inet6> {}
+
+impl<Style: SctpStyle, F: IpFamily> Bind for Socket<SctpSocket<Style, F>> {
+    type Address = F::Address;
+}
+
+impl<Style: SctpStyle, F: IpFamily> SendTo for Socket<SctpSocket<Style, F>> {}
+
+impl<Style: SctpStyle, F: IpFamily> Receive for Socket<SctpSocket<Style, F>> {}
+
+impl<Style: SctpStyle, F: IpFamily> ReceiveFrom for Socket<SctpSocket<Style, F>> {}
+
+impl<F: IpFamily> Connect for Socket<SctpSocket<OneToOne, F>> {}
+
+/// An event the kernel notifies an [`SctpSocket`](struct.SctpSocket.html) about, as a message
+/// flagged `MSG_NOTIFICATION` or as control messages
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SctpEvent {
+    /// `ControlMessage::SctpInfo` along with every received message
+    DataIo,
+    /// Associations starting or ending (`SCTP_ASSOC_CHANGE`)
+    Association,
+    /// Peer addresses changing state (`SCTP_PEER_ADDR_CHANGE`)
+    Address,
+    /// Messages that couldn't be delivered (`SCTP_SEND_FAILED`)
+    SendFailure,
+    /// Operation errors reported by the peer (`SCTP_REMOTE_ERROR`)
+    PeerError,
+    /// The peer shutting down the association (`SCTP_SHUTDOWN_EVENT`)
+    Shutdown,
+    /// Partial delivery of a message being aborted (`SCTP_PARTIAL_DELIVERY_EVENT`)
+    PartialDelivery,
+    /// The peer's adaptation layer indication (`SCTP_ADAPTATION_INDICATION`)
+    AdaptationLayer,
+    /// Authentication keys changing (`SCTP_AUTHENTICATION_EVENT`)
+    Authentication,
+    /// No more user data to send on an association (`SCTP_SENDER_DRY_EVENT`)
+    SenderDry,
+}
+
+impl SctpEvent {
+    /// The offset of the event's flag in `struct sctp_event_subscribe`
+    fn offset(self) -> usize {
+        match self {
+            SctpEvent::DataIo => 0,
+            SctpEvent::Association => 1,
+            SctpEvent::Address => 2,
+            SctpEvent::SendFailure => 3,
+            SctpEvent::PeerError => 4,
+            SctpEvent::Shutdown => 5,
+            SctpEvent::PartialDelivery => 6,
+            SctpEvent::AdaptationLayer => 7,
+            SctpEvent::Authentication => 8,
+            SctpEvent::SenderDry => 9,
+        }
+    }
+}
+
+/// The state an association changed to, reported by an `SCTP_ASSOC_CHANGE` notification
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AssociationState {
+    /// The association is ready to exchange data (`SCTP_COMM_UP`)
+    Up,
+    /// The association failed (`SCTP_COMM_LOST`)
+    Lost,
+    /// The peer restarted (`SCTP_RESTART`)
+    Restart,
+    /// The association was gracefully shut down (`SCTP_SHUTDOWN_COMP`)
+    ShutdownComplete,
+    /// The association couldn't be set up (`SCTP_CANT_STR_ASSOC`)
+    CantStart,
+    /// A state unknown to this crate
+    Other(u16),
+}
+
+impl From<u16> for AssociationState {
+    fn from(state: u16) -> Self {
+        match state {
+            0 => AssociationState::Up,
+            1 => AssociationState::Lost,
+            2 => AssociationState::Restart,
+            3 => AssociationState::ShutdownComplete,
+            4 => AssociationState::CantStart,
+            other => AssociationState::Other(other),
+        }
+    }
+}
+
+/// A notification received by an [`SctpSocket`](struct.SctpSocket.html) in place of a message,
+/// when the received message is flagged `MSG_NOTIFICATION`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Notification {
+    /// `SCTP_ASSOC_CHANGE`
+    AssociationChange {
+        /// The state the association changed to
+        state: AssociationState,
+        /// The error that caused the change, if any
+        error: u16,
+        /// The number of streams to the peer
+        outbound_streams: u16,
+        /// The number of streams from the peer
+        inbound_streams: u16,
+        /// The association that changed
+        association_id: i32,
+    },
+    /// `SCTP_SHUTDOWN_EVENT`
+    Shutdown {
+        /// The association the peer shut down
+        association_id: i32,
+    },
+    /// `SCTP_SENDER_DRY_EVENT`
+    SenderDry {
+        /// The association with no more data to send
+        association_id: i32,
+    },
+    /// Any other notification, e.g. `SCTP_PEER_ADDR_CHANGE`, left as sent by the kernel
+    Other {
+        /// The notification type
+        type_: u16,
+        /// The whole notification, header included
+        data: Vec<u8>,
+    },
+}
+
+impl Notification {
+    /// Parses the data of a message flagged `MSG_NOTIFICATION`
+    ///
+    /// # Errors
+    /// `EINVAL` if `data` is too short for the notification it starts with
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let type_ = read_u16(data, 0).ok_or(SystemError::from_raw_os_error(EINVAL))?;
+        let notification =
+            match type_ {
+                SCTP_ASSOC_CHANGE => Self::parse_association_change(data),
+                SCTP_SHUTDOWN_EVENT => read_i32(data, 8)
+                    .map(|association_id| Notification::Shutdown { association_id }),
+                SCTP_SENDER_DRY_EVENT => read_i32(data, 8)
+                    .map(|association_id| Notification::SenderDry { association_id }),
+                _ => Some(Notification::Other {
+                    type_,
+                    data: data.to_vec(),
+                }),
+            };
+        notification.ok_or(SystemError::from_raw_os_error(EINVAL))
+    }
+
+    fn parse_association_change(data: &[u8]) -> Option<Self> {
+        Some(Notification::AssociationChange {
+            state: read_u16(data, 8)?.into(),
+            error: read_u16(data, 10)?,
+            outbound_streams: read_u16(data, 12)?,
+            inbound_streams: read_u16(data, 14)?,
+            association_id: read_i32(data, 16)?,
+        })
+    }
+}
+
+/// Packs `addresses` back to back, as `sctp_bindx(3)` and `sctp_connectx(3)` expect them
+fn pack_addresses<A: SocketAddress>(addresses: &[A]) -> Result<Vec<u8>> {
+    if addresses.is_empty() {
+        return Err(SystemError::from_raw_os_error(EINVAL));
+    }
+    let mut packed = Vec::new();
+    for address in addresses {
+        let (storage, length) = address.to_storage();
+        let ptr: *const sockaddr_storage = &storage;
+        let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, length as usize) };
+        packed.extend_from_slice(bytes);
+    }
+    Ok(packed)
+}
+
+impl<Style: SctpStyle, F: IpFamily> Socket<SctpSocket<Style, F>> {
+    fn set_sctp_option(&self, name: i32, data: &[u8]) -> Result<i32> {
+        unsafe {
+            cvt(libc::setsockopt(
+                self.os(),
+                SOL_SCTP,
+                name,
+                data.as_ptr() as *const c_void,
+                data.len() as socklen_t,
+            ))
+        }
+        .context(Operation::Syscall("setsockopt"))
+    }
+
+    /// Starts accepting associations, queueing at most `backlog` pending ones
+    ///
+    /// One-to-many sockets must be listening to accept any association.
+    pub fn listen(&mut self, backlog: i32) -> Result<&mut Self> {
+        let _ = unsafe { cvt(libc::listen(self.os(), backlog)) }
+            .context(Operation::Syscall("listen"))?;
+        Ok(self)
+    }
+
+    /// Binds the socket to every address in `addresses` (`sctp_bindx(3)` with
+    /// `SCTP_BINDX_ADD_ADDR`), making the local endpoint multi-homed
+    ///
+    /// All addresses must share the same port. An unbound socket is bound by its first call.
+    pub fn bindx(&mut self, addresses: &[F::Address]) -> Result<&mut Self> {
+        let packed = pack_addresses(addresses)?;
+        let _ = self.set_sctp_option(SCTP_SOCKOPT_BINDX_ADD, &packed)?;
+        Ok(self)
+    }
+
+    /// Removes every address in `addresses` from the addresses the socket is bound to
+    /// (`sctp_bindx(3)` with `SCTP_BINDX_REM_ADDR`)
+    pub fn bindx_remove(&mut self, addresses: &[F::Address]) -> Result<&mut Self> {
+        let packed = pack_addresses(addresses)?;
+        let _ = self.set_sctp_option(SCTP_SOCKOPT_BINDX_REM, &packed)?;
+        Ok(self)
+    }
+
+    /// Sets up an association with a multi-homed peer reachable at every address in
+    /// `addresses` (`sctp_connectx(3)`), returns the id of the association
+    pub fn connectx(&mut self, addresses: &[F::Address]) -> Result<i32> {
+        let packed = pack_addresses(addresses)?;
+        loop {
+            match self.set_sctp_option(SCTP_SOCKOPT_CONNECTX, &packed) {
+                Err(SystemError::Os { errno: EINTR, .. }) => continue,
+                result => return result,
+            }
+        }
+    }
+
+    /// Subscribes the socket to `events`, and unsubscribes it from every other event
+    /// (`SCTP_EVENTS`)
+    pub fn set_events(&mut self, events: &[SctpEvent]) -> Result<&mut Self> {
+        let mut subscription = [0_u8; 10];
+        for event in events {
+            subscription[event.offset()] = 1;
+        }
+        let _ = self.set_sctp_option(SCTP_EVENTS, &subscription)?;
+        Ok(self)
+    }
+}
+
+impl<F: IpFamily> Socket<SctpSocket<OneToOne, F>> {
+    /// Accepts a pending association, blocking until one arrives if the socket is blocking
+    ///
+    /// The returned `Socket` owns a new file descriptor, with the `O_CLOEXEC` flag set
+    pub fn accept(&self) -> Result<Self> {
+        let fd = self.accept_fd()?;
+        Ok(unsafe { Socket::from_raw_fd(fd) })
+    }
+}
+
+#[cfg(feature = "bs-filter")]
+impl<Style: SctpStyle, F: IpFamily> SetFilter for Socket<SctpSocket<Style, F>> {
+    type Layer = Transport;
+}
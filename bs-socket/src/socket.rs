@@ -1,20 +1,36 @@
+use crate::address::SocketAddress;
+use crate::message::{Message, RawReceive, ReceivedMessage};
+use crate::option::Ipv6V6Only;
 #[cfg(feature = "bs-filter")]
-use bs_filter::{backend, backend::Backend, AttachFilter, Filter};
-use bs_system::{cvt, Result, SystemError};
+use bs_filter::{backend, backend::Backend, layer::Layer, AttachFilter, Filter};
+use bs_system::{cvt, Context, Operation, Result, SystemError};
 use cfg_if::cfg_if;
 use libc::c_void;
-use libc::{close, fcntl, socket};
+use libc::{bind, close, connect, fcntl, getpeername, getsockname, socket};
+use libc::{sockaddr, sockaddr_storage, socklen_t, SOL_SOCKET, SO_TYPE};
 use libc::{
-    EAGAIN, EINTR, EWOULDBLOCK, FD_CLOEXEC, F_GETFD, F_GETFL, F_SETFD, F_SETFL, O_NONBLOCK,
+    EAGAIN, EINTR, EPROTOTYPE, EWOULDBLOCK, FD_CLOEXEC, F_GETFD, F_GETFL, F_SETFD, F_SETFL,
+    O_NONBLOCK,
 };
+#[cfg(feature = "mio")]
+use mio::{event::Source, unix::SourceFd, Interest, Registry, Token};
+use std::convert::TryFrom;
+#[cfg(feature = "mio")]
+use std::io;
+use std::io::IoSliceMut;
 use std::iter::FromIterator;
-use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::mem::{forget, size_of, zeroed};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::ptr::null_mut;
 
 cfg_if! {
     if #[cfg(target_os = "linux")] {
+        use crate::message::ReceivePool;
+        use std::time::Duration;
         pub(crate) use libc::MSG_DONTWAIT;
         pub(crate) use libc::SOCK_CLOEXEC;
         pub(crate) use libc::SOCK_NONBLOCK;
+        use libc::{SO_DOMAIN, SO_PROTOCOL};
     }
 }
 
@@ -24,70 +40,71 @@ pub(crate) const DRAIN_BUFFER_SIZE: usize = 4096;
 
 #[doc(hidden)]
 pub trait SocketKind {
+    type Address: SocketAddress;
+
     fn new(fd: RawFd) -> Self;
     fn os(&self) -> i32;
     fn domain() -> i32;
     fn type_() -> i32;
     fn protocol() -> i32;
+
+    /// true iff `socket`, of the kind's domain, type and protocol, is also in the state the
+    /// kind stands for
+    fn is_in_state(socket: &Socket<Self>) -> Result<bool>
+    where
+        Self: Sized,
+    {
+        let _ = socket;
+        Ok(true)
+    }
 }
 
+/// Socket kinds whose `Socket`s can be created from scratch, as opposed to e.g. connected
+/// `tcp(7)` sockets that can only be obtained from another `Socket`
+#[doc(hidden)]
+pub trait NewSocket: SocketKind {}
+
+/// `ip(7)` socket kinds
+#[doc(hidden)]
+pub trait Ipv4SocketKind: SocketKind {}
+
+/// `ipv6(7)` socket kinds
+#[doc(hidden)]
+pub trait Ipv6SocketKind: SocketKind {}
+
 /// a generic `socket(7)` type
 #[derive(Debug)]
 pub struct Socket<S: SocketKind> {
     inner: S,
 }
 
-impl<S: SocketKind> Socket<S> {
-    cfg_if! {
-        if #[cfg(target_os = "linux")] {
-
-            /// Creates a new `Socket`
-            ///
-            /// sets the O_CLOEXEC creation flag if available for the target
-            pub fn new() -> Result<Self> {
-                Self::with_flags(SOCK_CLOEXEC)
-            }
-
-            /// Creates a new `Socket` without setting any creation flags
-            pub fn plain() -> Result<Self> {
-                Self::with_flags(0)
-            }
-
-            /// Creates a new nonblocking `Socket` with the `O_CLOEXEC` and the `O_NONBLOCK` flags set
-            ///
-            /// this is the recommended way to create nonblocking `Socket`s
-            pub fn nonblocking() -> Result<Self> {
-                Self::with_flags(SOCK_CLOEXEC | SOCK_NONBLOCK)
-            }
+impl<S: NewSocket> Socket<S> {
+    /// Creates a new `Socket` with the `O_CLOEXEC` flag set
+    pub fn new() -> Result<Self> {
+        Self::builder().build()
+    }
 
-           /// Creates a new nonblocking `Socket` without setting the `O_CLOEXEC` flag
-            pub fn plain_nonblocking() -> Result<Self> {
-                Self::with_flags(SOCK_NONBLOCK)
-            }
-        } else {
+    /// Creates a new `Socket` without setting any creation flags
+    pub fn plain() -> Result<Self> {
+        Self::builder().cloexec(false).build()
+    }
 
-            /// Creates a new `Socket`
-            ///
-            /// sets the O_CLOEXEC creation flag if available for the target
-            pub fn new() -> Result<Self> {
-                Self::with_flags(0)
-            }
+    /// Creates a new nonblocking `Socket` with the `O_CLOEXEC` and the `O_NONBLOCK` flags set
+    ///
+    /// this is the recommended way to create nonblocking `Socket`s
+    pub fn nonblocking() -> Result<Self> {
+        Self::builder().nonblocking(true).build()
+    }
 
-            /// Creates a new nonblocking `Socket` with `O_NONBLOCK` flag set
-            ///
-            /// this is the recommended way to create nonblocking `Socket`s
-            pub fn nonblocking() -> Result<Self> {
-                // TODO - also set the O_CLOEXEC flag
-                Self::new().and_then(|mut s| s.set_nonblocking().map(|_| s))
-            }
-        }
+    /// Creates a new nonblocking `Socket` without setting the `O_CLOEXEC` flag
+    pub fn plain_nonblocking() -> Result<Self> {
+        Self::builder().cloexec(false).nonblocking(true).build()
     }
 
-    fn with_flags(flags: i32) -> Result<Self> {
-        match unsafe { cvt(socket(S::domain(), S::type_() | flags, S::protocol())) } {
-            Ok(fd) => Ok(Self { inner: S::new(fd) }),
-            Err(e) => Err(e.into()),
-        }
+    pub(crate) fn with_flags(flags: i32) -> Result<Self> {
+        let fd = unsafe { cvt(socket(S::domain(), S::type_() | flags, S::protocol())) }
+            .context(Operation::Syscall("socket"))?;
+        Ok(Self { inner: S::new(fd) })
     }
 }
 
@@ -96,29 +113,56 @@ impl<S: SocketKind> Drop for Socket<S> {
         loop {
             match unsafe { cvt(close(self.inner.os())) } {
                 Ok(_) => return,
-                Err(SystemError(EINTR)) => continue,
+                Err(SystemError::Os { errno: EINTR, .. }) => continue,
                 _ => unreachable!(),
             }
         }
     }
 }
 
-mod private {
+pub(crate) mod private {
     use super::*;
-    use bs_system::SetSocketOption;
 
     pub trait PrivateBasicSocket: Sized {
         fn os(&self) -> RawFd;
 
-        fn set_option(&mut self, option: impl SetSocketOption) -> Result<&mut Self> {
-            option.set(self.os()).map(|_| self)
+        fn set_int_option(&mut self, level: i32, name: i32, value: i32) -> Result<&mut Self> {
+            let ptr: *const i32 = &value;
+            unsafe {
+                let _ = cvt(libc::setsockopt(
+                    self.os(),
+                    level,
+                    name,
+                    ptr as *const c_void,
+                    size_of::<i32>() as socklen_t,
+                ))
+                .context(Operation::Syscall("setsockopt"))?;
+            }
+            Ok(self)
+        }
+
+        fn int_option(&self, level: i32, name: i32) -> Result<i32> {
+            let mut value: i32 = 0;
+            let mut length = size_of::<i32>() as socklen_t;
+            let ptr: *mut i32 = &mut value;
+            unsafe {
+                let _ = cvt(libc::getsockopt(
+                    self.os(),
+                    level,
+                    name,
+                    ptr as *mut c_void,
+                    &mut length,
+                ))
+                .context(Operation::Syscall("getsockopt"))?;
+            }
+            Ok(value)
         }
 
         // TODO - flags to bitflags
         fn set_flags(&mut self, flags: i32) -> Result<&mut Self> {
             unsafe {
                 cvt(fcntl(self.os(), F_SETFL, flags))
-                    .map_err(|e| SystemError::from(e))
+                    .context(Operation::Syscall("fcntl"))
                     .and(Ok(self))
             }
         }
@@ -127,35 +171,238 @@ mod private {
         fn set_fd_flags(&mut self, flags: i32) -> Result<&mut Self> {
             unsafe {
                 cvt(fcntl(self.os(), F_SETFD, flags))
-                    .map_err(|e| SystemError::from(e))
+                    .context(Operation::Syscall("fcntl"))
                     .map(|_| self)
             }
         }
 
         // TODO - make recv more fun and document
-        // TODO - recv_from
-        // TODO - send, send_from
         fn recv(&self, buf: &mut [u8], flags: i32) -> Result<usize> {
             unsafe {
                 let n = cvt({
                     libc::recv(self.os(), buf.as_mut_ptr() as *mut c_void, buf.len(), flags)
-                })?;
+                })
+                .context(Operation::Syscall("recv"))?;
+                Ok(n as usize)
+            }
+        }
+
+        fn recv_from<A: SocketAddress>(&self, buf: &mut [u8], flags: i32) -> Result<(usize, A)> {
+            let mut storage: sockaddr_storage = unsafe { zeroed() };
+            let mut length = size_of::<sockaddr_storage>() as socklen_t;
+            let ptr: *mut sockaddr_storage = &mut storage;
+            let n = unsafe {
+                cvt(libc::recvfrom(
+                    self.os(),
+                    buf.as_mut_ptr() as *mut c_void,
+                    buf.len(),
+                    flags,
+                    ptr as *mut sockaddr,
+                    &mut length,
+                ))
+                .context(Operation::Syscall("recvfrom"))?
+            };
+            Ok((n as usize, A::from_storage(&storage, length)?))
+        }
+
+        fn recv_message<A: SocketAddress>(
+            &self,
+            buffers: &mut [IoSliceMut<'_>],
+            flags: i32,
+        ) -> Result<ReceivedMessage<A>> {
+            let mut raw = RawReceive::new();
+            let mut header = raw.header(buffers);
+            let n = unsafe {
+                cvt(libc::recvmsg(self.os(), &mut header, flags))
+                    .context(Operation::Syscall("recvmsg"))?
+            };
+            raw.finish(&header, n as usize)
+        }
+
+        #[cfg(target_os = "linux")]
+        fn recv_messages<A: SocketAddress>(
+            &self,
+            pool: &mut ReceivePool<A>,
+            flags: i32,
+            timeout: Option<Duration>,
+        ) -> Result<usize> {
+            let mut timeout = timeout.map(|timeout| libc::timespec {
+                tv_sec: timeout.as_secs() as libc::time_t,
+                tv_nsec: timeout.subsec_nanos() as libc::c_long,
+            });
+            let timeout_ptr = match timeout.as_mut() {
+                Some(timeout) => timeout,
+                None => null_mut(),
+            };
+            let headers = pool.headers();
+            let n = unsafe {
+                cvt(libc::recvmmsg(
+                    self.os(),
+                    headers.as_mut_ptr(),
+                    headers.len() as u32,
+                    flags,
+                    timeout_ptr,
+                ))
+                .context(Operation::Syscall("recvmmsg"))?
+            };
+            pool.set_received(n as usize);
+            Ok(n as usize)
+        }
+
+        fn send_buffer(&self, buf: &[u8], flags: i32) -> Result<usize> {
+            unsafe {
+                let n = cvt(libc::send(
+                    self.os(),
+                    buf.as_ptr() as *const c_void,
+                    buf.len(),
+                    flags,
+                ))
+                .context(Operation::Syscall("send"))?;
+                Ok(n as usize)
+            }
+        }
+
+        fn send_buffer_to(
+            &self,
+            buf: &[u8],
+            address: &impl SocketAddress,
+            flags: i32,
+        ) -> Result<usize> {
+            let (storage, length) = address.to_storage();
+            let ptr: *const sockaddr_storage = &storage;
+            unsafe {
+                let n = cvt(libc::sendto(
+                    self.os(),
+                    buf.as_ptr() as *const c_void,
+                    buf.len(),
+                    flags,
+                    ptr as *const sockaddr,
+                    length,
+                ))
+                .context(Operation::Syscall("sendto"))?;
+                Ok(n as usize)
+            }
+        }
+
+        fn send_message_raw<A: SocketAddress>(
+            &self,
+            message: &Message<'_, A>,
+            flags: i32,
+        ) -> Result<usize> {
+            let mut raw = message.raw();
+            let header = raw.header(message.buffers());
+            unsafe {
+                let n = cvt(libc::sendmsg(self.os(), &header, flags))
+                    .context(Operation::Syscall("sendmsg"))?;
+                Ok(n as usize)
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        fn send_messages_raw<A: SocketAddress>(
+            &self,
+            messages: &[Message<'_, A>],
+            flags: i32,
+        ) -> Result<usize> {
+            let mut raws: Vec<_> = messages.iter().map(Message::raw).collect();
+            let mut headers: Vec<libc::mmsghdr> = raws
+                .iter_mut()
+                .zip(messages)
+                .map(|(raw, message)| libc::mmsghdr {
+                    msg_hdr: raw.header(message.buffers()),
+                    msg_len: 0,
+                })
+                .collect();
+            unsafe {
+                let n = cvt(libc::sendmmsg(
+                    self.os(),
+                    headers.as_mut_ptr(),
+                    headers.len() as u32,
+                    flags,
+                ))
+                .context(Operation::Syscall("sendmmsg"))?;
                 Ok(n as usize)
             }
         }
 
+        fn bind_address(&mut self, address: &impl SocketAddress) -> Result<&mut Self> {
+            let (storage, length) = address.to_storage();
+            let ptr: *const sockaddr_storage = &storage;
+            unsafe {
+                let _ = cvt(bind(self.os(), ptr as *const sockaddr, length))
+                    .context(Operation::Syscall("bind"))?;
+            }
+            Ok(self)
+        }
+
+        fn connect_address(&mut self, address: &impl SocketAddress) -> Result<&mut Self> {
+            let (storage, length) = address.to_storage();
+            let ptr: *const sockaddr_storage = &storage;
+            loop {
+                match unsafe { cvt(connect(self.os(), ptr as *const sockaddr, length)) } {
+                    Ok(_) => return Ok(self),
+                    Err(SystemError::Os { errno: EINTR, .. }) => continue,
+                    Err(e) => return Err(e.with_operation(Operation::Syscall("connect"))),
+                }
+            }
+        }
+
+        fn accept_fd(&self) -> Result<RawFd> {
+            loop {
+                match unsafe { cvt(accept(self.os(), null_mut(), null_mut())) } {
+                    Ok(fd) => return Ok(fd),
+                    Err(SystemError::Os { errno: EINTR, .. }) => continue,
+                    Err(e) => return Err(e.with_operation(Operation::Syscall("accept"))),
+                }
+            }
+        }
+
+        fn address<A: SocketAddress>(
+            &self,
+            getname: unsafe extern "C" fn(i32, *mut sockaddr, *mut socklen_t) -> i32,
+            syscall: &'static str,
+        ) -> Result<A> {
+            let mut storage: sockaddr_storage = unsafe { zeroed() };
+            let mut length = size_of::<sockaddr_storage>() as socklen_t;
+            let ptr: *mut sockaddr_storage = &mut storage;
+            unsafe {
+                let _ = cvt(getname(self.os(), ptr as *mut sockaddr, &mut length))
+                    .context(Operation::Syscall(syscall))?;
+            }
+            A::from_storage(&storage, length)
+        }
+
+        fn drain_queue(&mut self) -> Result<&mut Self> {
+            if cfg!(target_os = "linux") {
+                self.recv_until_empty(MSG_DONTWAIT)
+            } else {
+                let original_flags = unsafe { cvt(fcntl(self.os(), F_GETFL)) }
+                    .context(Operation::Syscall("fcntl"))?;
+                let is_blocking = original_flags & O_NONBLOCK == 0;
+                if is_blocking {
+                    self.set_flags(original_flags | O_NONBLOCK)?
+                        .recv_until_empty(0)?
+                        .set_flags(original_flags)
+                } else {
+                    Ok(self)
+                }
+            }
+        }
+
         fn recv_until_empty(&mut self, flags: i32) -> Result<&mut Self> {
             let mut buf = [0; DRAIN_BUFFER_SIZE];
             loop {
                 match self.recv(&mut buf, flags) {
-                    Err(SystemError(EWOULDBLOCK)) => {
+                    Err(SystemError::Os {
+                        errno: EWOULDBLOCK, ..
+                    }) => {
                         return Ok(self);
                     }
                     // rustc claims this branch is unreachable
                     // because it assumes EWOULDBLOCK == EAGAIN == 11
                     // but that's not always the case
                     #[allow(unreachable_patterns)]
-                    Err(SystemError(EAGAIN)) => {
+                    Err(SystemError::Os { errno: EAGAIN, .. }) => {
                         return Ok(self);
                     }
                     Err(e) => {
@@ -168,12 +415,16 @@ mod private {
             }
         }
     }
+}
 
-    pub trait PrivateSetFilter: PrivateBasicSocket {
-        fn attach_filter(&mut self, filter: impl AttachFilter) -> Result<&mut Self> {
-            filter.attach(self.os()).map(|_| self)
-        }
-    }
+#[cfg(target_os = "linux")]
+unsafe fn accept(fd: RawFd, address: *mut sockaddr, length: *mut socklen_t) -> i32 {
+    libc::accept4(fd, address, length, SOCK_CLOEXEC)
+}
+
+#[cfg(not(target_os = "linux"))]
+unsafe fn accept(fd: RawFd, address: *mut sockaddr, length: *mut socklen_t) -> i32 {
+    libc::accept(fd, address, length)
 }
 
 impl<S: SocketKind> private::PrivateBasicSocket for Socket<S> {
@@ -182,16 +433,49 @@ impl<S: SocketKind> private::PrivateBasicSocket for Socket<S> {
     }
 }
 
+/// true iff the domain, type and protocol of `socket`, and its state, are the ones of `K`
+#[cfg(target_os = "linux")]
+pub(crate) fn is_kind<K: SocketKind>(socket: &Socket<K>) -> Result<bool> {
+    use private::PrivateBasicSocket;
+    // the kernel resolves the default protocol, so 0 matches any protocol
+    let protocol = socket.int_option(SOL_SOCKET, SO_PROTOCOL)?;
+    Ok(socket.int_option(SOL_SOCKET, SO_DOMAIN)? == K::domain()
+        && socket.int_option(SOL_SOCKET, SO_TYPE)? == K::type_()
+        && (K::protocol() == PROTO_NULL || protocol == K::protocol())
+        && K::is_in_state(socket)?)
+}
+
+/// true iff the type of `socket`, the only attribute every target reports, and its state are the
+/// ones of `K`
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn is_kind<K: SocketKind>(socket: &Socket<K>) -> Result<bool> {
+    use private::PrivateBasicSocket;
+    Ok(socket.int_option(SOL_SOCKET, SO_TYPE)? == K::type_() && K::is_in_state(socket)?)
+}
+
+impl<S: Ipv6SocketKind> Socket<S> {
+    /// Sets whether the socket is restricted to IPv6 (`IPV6_V6ONLY`), rather than also
+    /// exchanging IPv4 traffic through IPv4-mapped addresses, must be set before binding
+    pub fn set_v6_only(&mut self, v6_only: bool) -> Result<&mut Self> {
+        self.set_option(Ipv6V6Only::new(v6_only))
+    }
+
+    /// Returns whether the socket is restricted to IPv6 (`IPV6_V6ONLY`)
+    pub fn v6_only(&self) -> Result<bool> {
+        self.option().map(Ipv6V6Only::value)
+    }
+}
+
 /// The most basic socket operations, implemented for all socket kinds
 pub trait BasicSocket: private::PrivateBasicSocket {
     /// `fcntl(..., F_GETFL, ...)`
     fn flags(&self) -> Result<i32> {
-        unsafe { Ok(cvt(fcntl(self.os(), F_GETFL))?) }
+        unsafe { cvt(fcntl(self.os(), F_GETFL)) }.context(Operation::Syscall("fcntl"))
     }
 
     /// `fcntl(..., F_GETFD, ...)`
     fn fd_flags(&self) -> Result<i32> {
-        unsafe { Ok(cvt(fcntl(self.os(), F_GETFD))?) }
+        unsafe { cvt(fcntl(self.os(), F_GETFD)) }.context(Operation::Syscall("fcntl"))
     }
 
     /// set the socket to nonblocking mode
@@ -204,6 +488,17 @@ pub trait BasicSocket: private::PrivateBasicSocket {
         self.set_flags(self.flags()? & !O_NONBLOCK)
     }
 
+    /// set's the socket `FD_CLOEXEC` flag
+    fn set_cloexec(&mut self) -> Result<&mut Self> {
+        self.set_fd_flags(FD_CLOEXEC)
+    }
+}
+
+impl<S: SocketKind> BasicSocket for Socket<S> {}
+
+/// Extends [`BasicSocket`](trait.BasicSocket.html) with methods to receive packets, implemented
+/// for all socket kinds but `tcp(7)` sockets that aren't connected, which have nothing to receive
+pub trait Receive: BasicSocket {
     /// Receives a packet on the socket
     fn receive(&self, buf: &mut [u8], flags: i32) -> Result<usize> {
         self.recv(buf, flags)
@@ -211,28 +506,141 @@ pub trait BasicSocket: private::PrivateBasicSocket {
 
     /// Flushes the socket's receive queue
     fn drain(&mut self) -> Result<&mut Self> {
-        if cfg!(target_os = "linux") {
-            self.recv_until_empty(MSG_DONTWAIT)
-        } else {
-            let original_flags = self.flags()?;
-            let is_blocking = original_flags & O_NONBLOCK == 0;
-            if is_blocking {
-                self.set_flags(original_flags | O_NONBLOCK)?
-                    .recv_until_empty(0)?
-                    .set_flags(original_flags)
-            } else {
-                Ok(self)
-            }
-        }
+        self.drain_queue()
     }
+}
 
-    /// set's the socket `FD_CLOEXEC` flag
-    fn set_cloexec(&mut self) -> Result<&mut Self> {
-        self.set_fd_flags(FD_CLOEXEC)
+/// Extends [`BasicSocket`](trait.BasicSocket.html) with methods to bind the socket to a local
+/// address, implemented for all socket kinds without a lifecycle
+///
+/// `tcp(7)` sockets are bound by consuming methods of [`TcpSocket`] instead, which move the socket
+/// to its `Bound` state.
+///
+/// The socket can only be bound to addresses of its own domain, e.g. a `udp(7)` socket can only be
+/// bound to an IP socket address.
+///
+/// ```compile_fail
+/// # use bs_socket::address::UnixAddress;
+/// # use bs_socket::socket::{Bind, Socket};
+/// # use bs_socket::udp::UdpSocket;
+/// let mut s: Socket<UdpSocket> = Socket::new().unwrap();
+/// // a `udp(7)` socket can't be bound to a `unix(7)` address
+/// s.bind(&UnixAddress::pathname("/tmp/udp.sock").unwrap()).unwrap();
+/// ```
+///
+/// [`TcpSocket`]: ../tcp/struct.TcpSocket.html
+pub trait Bind: BasicSocket {
+    /// The kind of addresses the socket can be bound to
+    type Address: SocketAddress;
+
+    /// Binds the socket to `address`
+    fn bind(&mut self, address: &Self::Address) -> Result<&mut Self> {
+        self.bind_address(address)
+    }
+
+    /// Returns the address the socket is bound to (`getsockname(2)`)
+    fn local_address(&self) -> Result<Self::Address> {
+        self.address(getsockname, "getsockname")
     }
 }
 
-impl<S: SocketKind> BasicSocket for Socket<S> {}
+/// Extends [`Bind`](trait.Bind.html) with methods to send packets to typed addresses, for
+/// connectionless socket kinds
+///
+/// e.g. a `PacketLayer2Socket` sends full ethernet frames to the interface of a `LinkAddress`
+/// ```no_run
+/// # use bs_socket::address::LinkAddress;
+/// # use bs_socket::packet::PacketLayer2Socket;
+/// # use bs_socket::socket::{SendTo, Socket};
+/// # fn main() -> bs_system::Result<()> {
+/// let s: Socket<PacketLayer2Socket> = Socket::new()?;
+/// let mut frame = [0_u8; 60];
+/// frame[..6].copy_from_slice(&[0xff; 6]);
+/// frame[12..14].copy_from_slice(&0x88b5_u16.to_be_bytes());
+/// let _ = s.send_to(&frame, &LinkAddress::new(1, 0x88b5), 0)?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait SendTo: Bind {
+    /// Sends `buf` to `address`, returns the number of bytes sent
+    fn send_to(&self, buf: &[u8], address: &Self::Address, flags: i32) -> Result<usize> {
+        self.send_buffer_to(buf, address, flags)
+    }
+
+    /// Sends `message` (`sendmsg(2)`), returns the number of bytes sent
+    fn send_message(&self, message: &Message<'_, Self::Address>, flags: i32) -> Result<usize> {
+        self.send_message_raw(message, flags)
+    }
+
+    /// Sends `messages` in a single syscall (`sendmmsg(2)`), returns the number of messages sent
+    ///
+    /// fewer messages than given may be sent, in which case the rest should be sent again
+    #[cfg(target_os = "linux")]
+    fn send_messages(&self, messages: &[Message<'_, Self::Address>], flags: i32) -> Result<usize> {
+        self.send_messages_raw(messages, flags)
+    }
+}
+
+/// Extends [`Bind`](trait.Bind.html) with methods to receive packets along with their typed source
+/// addresses, for connectionless socket kinds
+///
+/// e.g. a `PacketLayer2Socket` receives frames along with the `LinkAddress` of the interface they
+/// arrived on, and their packet type
+pub trait ReceiveFrom: Bind {
+    /// Receives a packet into `buf`, returns its length and its source address
+    ///
+    /// the returned length is of the received part of the packet, unless `MSG_TRUNC` is passed in
+    /// `flags`, in which case it's the real length of the packet
+    fn receive_from(&self, buf: &mut [u8], flags: i32) -> Result<(usize, Self::Address)> {
+        self.recv_from(buf, flags)
+    }
+
+    /// Receives a packet scattered to `buffers` (`recvmsg(2)`), along with its source address and
+    /// control messages
+    fn receive_message(
+        &self,
+        buffers: &mut [IoSliceMut<'_>],
+        flags: i32,
+    ) -> Result<ReceivedMessage<Self::Address>> {
+        self.recv_message(buffers, flags)
+    }
+
+    /// Receives a batch of packets into `pool` in a single syscall (`recvmmsg(2)`), returns the
+    /// number of packets received
+    ///
+    /// `timeout` bounds the time spent filling the batch, but it's only checked after each packet
+    /// is received, so a blocking socket still waits for the first packet (see BUGS in
+    /// `recvmmsg(2)`). Pass `MSG_WAITFORONE` in `flags` to return as soon as any packet is
+    /// received, or use a nonblocking socket to return immediately.
+    #[cfg(target_os = "linux")]
+    fn receive_messages(
+        &self,
+        pool: &mut ReceivePool<Self::Address>,
+        flags: i32,
+        timeout: Option<Duration>,
+    ) -> Result<usize> {
+        self.recv_messages(pool, flags, timeout)
+    }
+}
+
+/// Extends [`Bind`](trait.Bind.html) with methods to connect the socket to a remote address, for
+/// socket kinds that support `connect(2)`
+pub trait Connect: Bind {
+    /// Connects the socket to `address`
+    fn connect(&mut self, address: &Self::Address) -> Result<&mut Self> {
+        self.connect_address(address)
+    }
+
+    /// Sends `buf` to the address the socket is connected to, returns the number of bytes sent
+    fn send(&self, buf: &[u8], flags: i32) -> Result<usize> {
+        self.send_buffer(buf, flags)
+    }
+
+    /// Returns the address the socket is connected to (`getpeername(2)`)
+    fn peer_address(&self) -> Result<Self::Address> {
+        self.address(getpeername, "getpeername")
+    }
+}
 
 impl<S: SocketKind> AsRawFd for Socket<S> {
     fn as_raw_fd(&self) -> RawFd {
@@ -242,7 +650,10 @@ impl<S: SocketKind> AsRawFd for Socket<S> {
 
 impl<S: SocketKind> IntoRawFd for Socket<S> {
     fn into_raw_fd(self) -> RawFd {
-        private::PrivateBasicSocket::os(&self)
+        let fd = private::PrivateBasicSocket::os(&self);
+        // the caller now owns the fd, so it mustn't be closed on drop
+        forget(self);
+        fd
     }
 }
 
@@ -252,24 +663,97 @@ impl<S: SocketKind> FromRawFd for Socket<S> {
     }
 }
 
+impl<S: SocketKind> AsFd for Socket<S> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // the fd stays open for as long as the socket is borrowed
+        unsafe { BorrowedFd::borrow_raw(private::PrivateBasicSocket::os(self)) }
+    }
+}
+
+impl<S: SocketKind> From<Socket<S>> for OwnedFd {
+    fn from(socket: Socket<S>) -> Self {
+        unsafe { OwnedFd::from_raw_fd(socket.into_raw_fd()) }
+    }
+}
+
+/// Takes ownership of a socket fd, as long as it's of kind `S`
+///
+/// # Errors
+/// `ENOTSOCK` if `fd` isn't a socket, `EPROTOTYPE` if it isn't of kind `S`, in which case `fd` is
+/// closed
+impl<S: SocketKind> TryFrom<OwnedFd> for Socket<S> {
+    type Error = SystemError;
+
+    fn try_from(fd: OwnedFd) -> Result<Self> {
+        let socket = unsafe { Self::from_raw_fd(fd.into_raw_fd()) };
+        if is_kind(&socket)? {
+            Ok(socket)
+        } else {
+            Err(SystemError::from_raw_os_error(EPROTOTYPE))
+        }
+    }
+}
+
+/// Registers the socket's fd with a `mio::Poll`, which never takes ownership of it
+///
+/// `mio` expects nonblocking sockets, e.g. created with `Socket::nonblocking`, and only reports
+/// readiness once until the socket is read until `EAGAIN`.
+#[cfg(feature = "mio")]
+impl<S: SocketKind> Source for Socket<S> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        SourceFd(&self.as_raw_fd()).deregister(registry)
+    }
+}
+
 /// Extends [`BasicSocket`](trait.BasicSocket.html) with a method to set a packet filter on the
 /// socket
+///
+/// Each socket kind hands its filter packets starting at a specific [`Layer`], and only accepts
+/// filters compiled for that layer, so e.g. ethernet idioms can't be attached to a `udp(7)` socket
+/// ```compile_fail
+/// # use bs_filter::{backend::Classic, idiom::ethernet::ether_type_arp};
+/// # use bs_socket::{socket::{Socket, SetFilter}, udp::UdpSocket};
+/// let mut s: Socket<UdpSocket> = Socket::new().unwrap();
+/// let f = ether_type_arp::<Classic>().compile().unwrap().build().unwrap();
+/// s.set_filter(f).unwrap();
+/// ```
+///
+/// [`Layer`]: ../../bs_filter/layer/trait.Layer.html
 #[cfg(feature = "bs-filter")]
 pub trait SetFilter: BasicSocket {
+    /// The `Layer` at which packets seen by the socket's filter start
+    type Layer: Layer;
+
     /// Sets a new socket filter in the socket, or replaces the existing filter if already set
-    fn attach_filter(&mut self, filter: impl AttachFilter) -> Result<&mut Self> {
+    fn attach_filter(&mut self, filter: impl AttachFilter<Self::Layer>) -> Result<&mut Self> {
         filter.attach(self.os()).map(|_| self)
     }
 
     /// Flushes the socket's incoming stream and sets a new filter
-    fn set_filter(&mut self, filter: impl AttachFilter) -> Result<&mut Self> {
-        let f = Filter::<backend::Classic>::from_iter(backend::Classic::contradiction());
+    fn set_filter(&mut self, filter: impl AttachFilter<Self::Layer>) -> Result<&mut Self> {
+        let f =
+            Filter::<backend::Classic, Self::Layer>::from_iter(backend::Classic::contradiction());
         let drop_filter = f.build()?;
         self.attach_filter(drop_filter)?
-            .drain()?
+            .drain_queue()?
             .attach_filter(filter)
     }
 }
-
-#[cfg(target_os = "linux")]
-impl<S: SocketKind> SetFilter for Socket<S> {}
@@ -1,20 +1,131 @@
-use crate::socket::SocketKind;
+use crate::address::{Inet, Inet6, IpFamily, SocketAddress};
+use crate::message::{Message, ReceivedMessage};
+use crate::socket::private::PrivateBasicSocket;
+#[cfg(all(target_os = "linux", feature = "bs-filter"))]
+use crate::socket::SetFilter;
 use crate::socket::PROTO_NULL;
-use libc::{AF_INET, SOCK_STREAM};
-use std::os::unix::io::RawFd;
+use crate::socket::{Ipv4SocketKind, Ipv6SocketKind, NewSocket, Receive, Socket, SocketKind};
+#[cfg(all(target_os = "linux", feature = "bs-filter"))]
+use bs_filter::layer::Transport;
+use bs_system::{cvt, Context, Operation, Result, SystemError};
+use libc::{getpeername, getsockname};
+use libc::{sockaddr_in, sockaddr_storage, SOL_SOCKET, SO_ACCEPTCONN};
+use libc::{EISCONN, ENOTCONN, SHUT_RD, SHUT_RDWR, SHUT_WR, SOCK_STREAM};
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::IoSliceMut;
+use std::marker::PhantomData;
+use std::net::Shutdown;
+use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
 
-/// `tcp(7)` over `ip(7)`
+mod private {
+    /// What the fd of a `tcp(7)` socket tells of its lifecycle
+    #[derive(Debug, Copy, Clone)]
+    pub struct Lifecycle {
+        pub listening: bool,
+        pub connected: bool,
+        pub bound: bool,
+    }
+
+    pub trait Sealed {
+        /// true iff a socket at `lifecycle` is in this state
+        fn matches(lifecycle: Lifecycle) -> bool;
+    }
+}
+
+/// The state of a [`TcpSocket`](struct.TcpSocket.html) in its lifecycle
+///
+/// Each state only exposes the operations that are sound in it, e.g. only a `Listening` socket
+/// can `accept` new connections, and only a `Connected` socket can `send` data.
+pub trait TcpState: private::Sealed + Copy + fmt::Debug {}
+
+/// A freshly created `TcpSocket`
+#[derive(Debug, Copy, Clone)]
+pub struct Unbound;
+
+/// A `TcpSocket` bound to a local address
+#[derive(Debug, Copy, Clone)]
+pub struct Bound;
+
+/// A `TcpSocket` accepting incoming connections
+#[derive(Debug, Copy, Clone)]
+pub struct Listening;
+
+/// A `TcpSocket` connected to a peer, either by `connect` or by `accept`
 #[derive(Debug, Copy, Clone)]
-pub struct TcpSocket {
+pub struct Connected;
+
+impl private::Sealed for Unbound {
+    fn matches(lifecycle: private::Lifecycle) -> bool {
+        !lifecycle.listening && !lifecycle.connected && !lifecycle.bound
+    }
+}
+
+impl private::Sealed for Bound {
+    fn matches(lifecycle: private::Lifecycle) -> bool {
+        !lifecycle.listening && !lifecycle.connected && lifecycle.bound
+    }
+}
+
+impl private::Sealed for Listening {
+    fn matches(lifecycle: private::Lifecycle) -> bool {
+        lifecycle.listening
+    }
+}
+
+impl private::Sealed for Connected {
+    fn matches(lifecycle: private::Lifecycle) -> bool {
+        lifecycle.connected
+    }
+}
+
+impl TcpState for Unbound {}
+impl TcpState for Bound {}
+impl TcpState for Listening {}
+impl TcpState for Connected {}
+
+/// `tcp(7)` over `ip(7)`, or over `ipv6(7)` for `TcpSocket<State, Inet6>`
+///
+/// `State` tracks the socket's lifecycle, see [`TcpState`](trait.TcpState.html)
+/// ```
+/// # use bs_socket::socket::{Receive, Socket};
+/// # use bs_socket::tcp::TcpSocket;
+/// # fn main() -> bs_system::Result<()> {
+/// let server: Socket<TcpSocket> = Socket::new()?;
+/// let server = server.bind(&"127.0.0.1:0".parse().unwrap())?.listen(5)?;
+///
+/// let client: Socket<TcpSocket> = Socket::new()?;
+/// let client = client.connect(&server.local_address()?)?;
+/// let peer = server.accept()?;
+///
+/// let _ = client.send(b"ping", 0)?;
+/// let mut buffer = [0; 4];
+/// assert_eq!(peer.receive(&mut buffer, 0)?, 4);
+/// assert_eq!(&buffer, b"ping");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct TcpSocket<State: TcpState = Unbound, F: IpFamily = Inet> {
     fd: RawFd,
+    state: PhantomData<(State, F)>,
 }
 
-impl SocketKind for TcpSocket {
+/// `tcp(7)` over `ipv6(7)`
+pub type Tcp6Socket<State = Unbound> = TcpSocket<State, Inet6>;
+
+impl<State: TcpState, F: IpFamily> SocketKind for TcpSocket<State, F> {
+    type Address = F::Address;
+
     fn new(fd: RawFd) -> Self {
-        Self { fd }
+        Self {
+            fd,
+            state: PhantomData,
+        }
     }
     fn domain() -> i32 {
-        AF_INET
+        F::Address::family()
     }
     fn type_() -> i32 {
         SOCK_STREAM
@@ -25,4 +136,212 @@ impl SocketKind for TcpSocket {
     fn os(&self) -> i32 {
         self.fd
     }
+    fn is_in_state(socket: &Socket<Self>) -> Result<bool> {
+        Ok(State::matches(socket.lifecycle()?))
+    }
+}
+
+impl<F: IpFamily> NewSocket for TcpSocket<Unbound, F> {}
+
+impl<State: TcpState> Ipv4SocketKind for TcpSocket<State, Inet> {}
+impl<State: TcpState> Ipv6SocketKind for TcpSocket<State, Inet6> {}
+
+fn transition<From: TcpState, To: TcpState, F: IpFamily>(
+    socket: Socket<TcpSocket<From, F>>,
+) -> Socket<TcpSocket<To, F>> {
+    unsafe { Socket::from_raw_fd(socket.into_raw_fd()) }
+}
+
+/// Moves `socket` to state `To` if `result` succeeded, or hands it back in its current state
+fn attempt<From: TcpState, To: TcpState, F: IpFamily>(
+    socket: Socket<TcpSocket<From, F>>,
+    result: Result<()>,
+) -> Transition<From, To, F> {
+    match result {
+        Ok(()) => Ok(transition(socket)),
+        Err(error) => Err(TransitionError { socket, error }),
+    }
+}
+
+/// The result of moving a `Socket<TcpSocket<From, F>>` to state `To`
+pub type Transition<From, To, F> =
+    std::result::Result<Socket<TcpSocket<To, F>>, TransitionError<From, F>>;
+
+/// A failed state transition of a [`TcpSocket`](struct.TcpSocket.html), which hands the socket
+/// back in its previous state, e.g. to retry binding, or to poll a nonblocking connection
+///
+/// Converts into a `SystemError`, so `?` closes the socket and keeps the error.
+#[derive(Debug)]
+pub struct TransitionError<State: TcpState, F: IpFamily> {
+    socket: Socket<TcpSocket<State, F>>,
+    error: SystemError,
+}
+
+impl<State: TcpState, F: IpFamily> TransitionError<State, F> {
+    /// The reason the transition failed
+    pub fn error(&self) -> &SystemError {
+        &self.error
+    }
+
+    /// The socket, in the state it was in before the transition
+    pub fn into_socket(self) -> Socket<TcpSocket<State, F>> {
+        self.socket
+    }
+
+    /// The socket and the reason the transition failed
+    pub fn into_parts(self) -> (Socket<TcpSocket<State, F>>, SystemError) {
+        (self.socket, self.error)
+    }
+}
+
+impl<State: TcpState, F: IpFamily> fmt::Display for TransitionError<State, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<State: TcpState, F: IpFamily> error::Error for TransitionError<State, F> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl<State: TcpState, F: IpFamily> From<TransitionError<State, F>> for SystemError {
+    fn from(error: TransitionError<State, F>) -> Self {
+        error.error
+    }
+}
+
+impl<State: TcpState, F: IpFamily> From<TransitionError<State, F>> for io::Error {
+    fn from(error: TransitionError<State, F>) -> Self {
+        error.error.into()
+    }
+}
+
+impl<State: TcpState, F: IpFamily> Socket<TcpSocket<State, F>> {
+    /// Returns the address the socket is bound to (`getsockname(2)`)
+    pub fn local_address(&self) -> Result<F::Address> {
+        self.address(getsockname, "getsockname")
+    }
+
+    fn lifecycle(&self) -> Result<private::Lifecycle> {
+        let connected = match self.address::<F::Address>(getpeername, "getpeername") {
+            Ok(_) => true,
+            Err(SystemError::Os {
+                errno: ENOTCONN, ..
+            }) => false,
+            Err(e) => return Err(e),
+        };
+        // `sin_port` and `sin6_port` share their offset, and are 0 until the socket is bound
+        let (storage, _) = self.local_address()?.to_storage();
+        let ptr: *const sockaddr_storage = &storage;
+        let port = unsafe { (*(ptr as *const sockaddr_in)).sin_port };
+        Ok(private::Lifecycle {
+            listening: self.int_option(SOL_SOCKET, SO_ACCEPTCONN)? != 0,
+            connected,
+            bound: port != 0,
+        })
+    }
+
+    fn connect_to(mut self, address: &F::Address) -> Transition<State, Connected, F> {
+        let result = match self.connect_address(address) {
+            // the connection a previous call started is established
+            Ok(_) | Err(SystemError::Os { errno: EISCONN, .. }) => Ok(()),
+            Err(error) => Err(error),
+        };
+        attempt(self, result)
+    }
+}
+
+impl<F: IpFamily> Socket<TcpSocket<Unbound, F>> {
+    /// Binds the socket to `address`
+    pub fn bind(mut self, address: &F::Address) -> Transition<Unbound, Bound, F> {
+        let result = self.bind_address(address).map(|_| ());
+        attempt(self, result)
+    }
+
+    /// Connects the socket to `address`, binding it to an ephemeral local address
+    ///
+    /// A nonblocking socket is handed back with `EINPROGRESS`, and is connected by calling
+    /// `connect` again once it's writable.
+    pub fn connect(self, address: &F::Address) -> Transition<Unbound, Connected, F> {
+        self.connect_to(address)
+    }
+}
+
+impl<F: IpFamily> Socket<TcpSocket<Bound, F>> {
+    /// Starts accepting connections, queueing at most `backlog` pending connections
+    pub fn listen(self, backlog: i32) -> Transition<Bound, Listening, F> {
+        let result = unsafe { cvt(libc::listen(self.os(), backlog)) }
+            .context(Operation::Syscall("listen"))
+            .map(|_| ());
+        attempt(self, result)
+    }
+
+    /// Connects the socket to `address` from its bound address
+    ///
+    /// A nonblocking socket is handed back with `EINPROGRESS`, and is connected by calling
+    /// `connect` again once it's writable.
+    pub fn connect(self, address: &F::Address) -> Transition<Bound, Connected, F> {
+        self.connect_to(address)
+    }
+}
+
+impl<F: IpFamily> Socket<TcpSocket<Listening, F>> {
+    /// Accepts a pending connection, blocking until one arrives if the socket is blocking
+    ///
+    /// The returned `Socket` owns a new file descriptor, with the `O_CLOEXEC` flag set if
+    /// available for the target
+    pub fn accept(&self) -> Result<Socket<TcpSocket<Connected, F>>> {
+        let fd = self.accept_fd()?;
+        Ok(unsafe { Socket::from_raw_fd(fd) })
+    }
+}
+
+impl<F: IpFamily> Receive for Socket<TcpSocket<Connected, F>> {}
+
+impl<F: IpFamily> Socket<TcpSocket<Connected, F>> {
+    /// Returns the address of the socket's peer (`getpeername(2)`)
+    pub fn peer_address(&self) -> Result<F::Address> {
+        self.address(getpeername, "getpeername")
+    }
+
+    /// Sends `buf` to the socket's peer, returns the number of bytes sent
+    pub fn send(&self, buf: &[u8], flags: i32) -> Result<usize> {
+        self.send_buffer(buf, flags)
+    }
+
+    /// Sends the gathered buffers and control messages of `message` to the socket's peer,
+    /// returns the number of bytes sent
+    ///
+    /// the message's address, if set, is ignored
+    pub fn send_message(&self, message: &Message<'_, F::Address>, flags: i32) -> Result<usize> {
+        self.send_message_raw(message, flags)
+    }
+
+    /// Receives data scattered to `buffers` (`recvmsg(2)`), along with control messages
+    pub fn receive_message(
+        &self,
+        buffers: &mut [IoSliceMut<'_>],
+        flags: i32,
+    ) -> Result<ReceivedMessage<F::Address>> {
+        self.recv_message(buffers, flags)
+    }
+
+    /// Shuts down the reading half, the writing half, or both halves of the connection
+    pub fn shutdown(&mut self, how: Shutdown) -> Result<&mut Self> {
+        let how = match how {
+            Shutdown::Read => SHUT_RD,
+            Shutdown::Write => SHUT_WR,
+            Shutdown::Both => SHUT_RDWR,
+        };
+        let _ = unsafe { cvt(libc::shutdown(self.os(), how)) }
+            .context(Operation::Syscall("shutdown"))?;
+        Ok(self)
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "bs-filter"))]
+impl<State: TcpState, F: IpFamily> SetFilter for Socket<TcpSocket<State, F>> {
+    type Layer = Transport;
 }
@@ -1,20 +1,38 @@
-use crate::socket::SocketKind;
+use crate::address::{Inet, Inet6, IpFamily, SocketAddress};
+#[cfg(all(target_os = "linux", feature = "bs-filter"))]
+use crate::socket::SetFilter;
 use crate::socket::PROTO_NULL;
-use libc::{AF_INET, SOCK_DGRAM};
+use crate::socket::{
+    Bind, Connect, Ipv4SocketKind, Ipv6SocketKind, NewSocket, Receive, ReceiveFrom, SendTo,
+};
+use crate::socket::{Socket, SocketKind};
+#[cfg(all(target_os = "linux", feature = "bs-filter"))]
+use bs_filter::layer::Transport;
+use libc::SOCK_DGRAM;
+use std::marker::PhantomData;
 use std::os::unix::io::RawFd;
 
-/// `udp(7)` over `ip(7)`
+/// `udp(7)` over `ip(7)`, or over `ipv6(7)` for `UdpSocket<Inet6>`
 #[derive(Debug, Copy, Clone)]
-pub struct UdpSocket {
+pub struct UdpSocket<F: IpFamily = Inet> {
     fd: RawFd,
+    family: PhantomData<F>,
 }
 
-impl SocketKind for UdpSocket {
+/// `udp(7)` over `ipv6(7)`
+pub type Udp6Socket = UdpSocket<Inet6>;
+
+impl<F: IpFamily> SocketKind for UdpSocket<F> {
+    type Address = F::Address;
+
     fn new(fd: RawFd) -> Self {
-        Self { fd }
+        Self {
+            fd,
+            family: PhantomData,
+        }
     }
     fn domain() -> i32 {
-        AF_INET
+        F::Address::family()
     }
     fn type_() -> i32 {
         SOCK_DGRAM
@@ -26,3 +44,25 @@ impl SocketKind for UdpSocket {
         self.fd
     }
 }
+
+impl<F: IpFamily> NewSocket for UdpSocket<F> {}
+
+impl Ipv4SocketKind for UdpSocket<Inet> {}
+impl Ipv6SocketKind for UdpSocket<Inet6> {}
+
+impl<F: IpFamily> Bind for Socket<UdpSocket<F>> {
+    type Address = F::Address;
+}
+
+impl<F: IpFamily> SendTo for Socket<UdpSocket<F>> {}
+
+impl<F: IpFamily> Receive for Socket<UdpSocket<F>> {}
+
+impl<F: IpFamily> ReceiveFrom for Socket<UdpSocket<F>> {}
+
+impl<F: IpFamily> Connect for Socket<UdpSocket<F>> {}
+
+#[cfg(all(target_os = "linux", feature = "bs-filter"))]
+impl<F: IpFamily> SetFilter for Socket<UdpSocket<F>> {
+    type Layer = Transport;
+}
@@ -0,0 +1,306 @@
+use crate::address::UnixAddress;
+#[cfg(target_os = "linux")]
+use crate::message::Credentials;
+use crate::message::{ControlMessage, Message, ReceivedMessage};
+use crate::socket::private::PrivateBasicSocket;
+use crate::socket::{is_kind, PROTO_NULL};
+use crate::socket::{Bind, Connect, NewSocket, Receive, ReceiveFrom, SendTo, Socket, SocketKind};
+use bs_system::{cvt, Context, Operation, Result, SystemError};
+use cfg_if::cfg_if;
+use libc::{AF_UNIX, ENOMSG, EPROTOTYPE, SOCK_DGRAM, SOCK_SEQPACKET, SOCK_STREAM};
+use std::io::IoSliceMut;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use crate::socket::SOCK_CLOEXEC;
+        use libc::{c_void, socklen_t, ucred, MSG_CMSG_CLOEXEC};
+        use crate::option::PassCredentials;
+        use libc::{SOL_SOCKET, SO_PEERCRED};
+        use std::mem::{size_of, zeroed};
+    }
+}
+
+/// `unix(7)` socket kinds
+#[doc(hidden)]
+pub trait UnixSocketKind: SocketKind<Address = UnixAddress> {}
+
+/// Connection-oriented `unix(7)` socket kinds, which `listen` for and `accept` connections
+#[doc(hidden)]
+pub trait UnixConnectionKind: UnixSocketKind {}
+
+/// `unix(7)` stream socket (`SOCK_STREAM`)
+///
+/// ```
+/// # use bs_socket::socket::{Connect, Receive, Socket};
+/// # use bs_socket::unix::UnixStreamSocket;
+/// # fn main() -> bs_system::Result<()> {
+/// let (client, server): (Socket<UnixStreamSocket>, _) = Socket::pair()?;
+/// let _ = client.send(b"ping", 0)?;
+/// let mut buffer = [0; 4];
+/// assert_eq!(server.receive(&mut buffer, 0)?, 4);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct UnixStreamSocket {
+    fd: RawFd,
+}
+
+/// `unix(7)` datagram socket (`SOCK_DGRAM`)
+#[derive(Debug, Copy, Clone)]
+pub struct UnixDatagramSocket {
+    fd: RawFd,
+}
+
+/// `unix(7)` sequenced packet socket (`SOCK_SEQPACKET`), connection-oriented like
+/// `UnixStreamSocket` while preserving message boundaries like `UnixDatagramSocket`
+#[derive(Debug, Copy, Clone)]
+pub struct UnixSeqpacketSocket {
+    fd: RawFd,
+}
+
+impl SocketKind for UnixStreamSocket {
+    type Address = UnixAddress;
+
+    fn new(fd: RawFd) -> Self {
+        Self { fd }
+    }
+    fn domain() -> i32 {
+        AF_UNIX
+    }
+    fn type_() -> i32 {
+        SOCK_STREAM
+    }
+    fn protocol() -> i32 {
+        PROTO_NULL
+    }
+    fn os(&self) -> i32 {
+        self.fd
+    }
+}
+
+impl SocketKind for UnixDatagramSocket {
+    type Address = UnixAddress;
+
+    fn new(fd: RawFd) -> Self {
+        Self { fd }
+    }
+    fn domain() -> i32 {
+        AF_UNIX
+    }
+    fn type_() -> i32 {
+        SOCK_DGRAM
+    }
+    fn protocol() -> i32 {
+        PROTO_NULL
+    }
+    fn os(&self) -> i32 {
+        self.fd
+    }
+}
+
+impl SocketKind for UnixSeqpacketSocket {
+    type Address = UnixAddress;
+
+    fn new(fd: RawFd) -> Self {
+        Self { fd }
+    }
+    fn domain() -> i32 {
+        AF_UNIX
+    }
+    fn type_() -> i32 {
+        SOCK_SEQPACKET
+    }
+    fn protocol() -> i32 {
+        PROTO_NULL
+    }
+    fn os(&self) -> i32 {
+        self.fd
+    }
+}
+
+impl NewSocket for UnixStreamSocket {}
+impl NewSocket for UnixDatagramSocket {}
+impl NewSocket for UnixSeqpacketSocket {}
+impl UnixSocketKind for UnixStreamSocket {}
+impl UnixSocketKind for UnixDatagramSocket {}
+impl UnixSocketKind for UnixSeqpacketSocket {}
+impl UnixConnectionKind for UnixStreamSocket {}
+impl UnixConnectionKind for UnixSeqpacketSocket {}
+
+impl Bind for Socket<UnixStreamSocket> {
+    type Address = UnixAddress;
+}
+
+impl Receive for Socket<UnixStreamSocket> {}
+
+impl Connect for Socket<UnixStreamSocket> {}
+
+impl Bind for Socket<UnixDatagramSocket> {
+    type Address = UnixAddress;
+}
+
+impl SendTo for Socket<UnixDatagramSocket> {}
+
+impl Receive for Socket<UnixDatagramSocket> {}
+
+impl ReceiveFrom for Socket<UnixDatagramSocket> {}
+
+impl Connect for Socket<UnixDatagramSocket> {}
+
+impl Bind for Socket<UnixSeqpacketSocket> {
+    type Address = UnixAddress;
+}
+
+impl Receive for Socket<UnixSeqpacketSocket> {}
+
+impl Connect for Socket<UnixSeqpacketSocket> {}
+
+/// The octet sent along with a passed socket, as stream sockets don't pass control messages
+/// without any data
+const PASSED_SOCKET_DATA: [u8; 1] = [0];
+
+impl<S: UnixSocketKind + NewSocket> Socket<S> {
+    /// Creates a pair of connected, unnamed `Socket`s (`socketpair(2)`)
+    ///
+    /// sets the O_CLOEXEC creation flag if available for the target
+    pub fn pair() -> Result<(Self, Self)> {
+        let mut fds = [0; 2];
+        unsafe {
+            let _ = cvt(libc::socketpair(
+                S::domain(),
+                S::type_() | pair_flags(),
+                S::protocol(),
+                fds.as_mut_ptr(),
+            ))
+            .context(Operation::Syscall("socketpair"))?;
+            Ok((Self::from_raw_fd(fds[0]), Self::from_raw_fd(fds[1])))
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn pair_flags() -> i32 {
+    SOCK_CLOEXEC
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pair_flags() -> i32 {
+    0
+}
+
+impl<S: UnixSocketKind> Socket<S> {
+    /// Sends `socket` to the peer (`SCM_RIGHTS`), which receives its own file descriptor for it
+    /// with [`receive_socket`](#method.receive_socket)
+    ///
+    /// The socket must be connected. `socket` stays open, and may keep being used by the sender.
+    pub fn send_socket<K: SocketKind>(&self, socket: &Socket<K>) -> Result<()> {
+        let message = Message::<UnixAddress>::new(&[&PASSED_SOCKET_DATA])
+            .with_control(ControlMessage::Rights(vec![socket.as_raw_fd()]));
+        self.send_message_raw(&message, 0).map(|_| ())
+    }
+
+    /// Receives a socket sent by [`send_socket`](#method.send_socket), as a `Socket<K>`
+    ///
+    /// # Errors
+    /// `ENOMSG` if the received message carries no socket, e.g. if the peer shut the connection
+    /// down, `EPROTOTYPE` if the received socket isn't of kind `K`
+    pub fn receive_socket<K: SocketKind>(&self) -> Result<Socket<K>> {
+        let mut data = PASSED_SOCKET_DATA;
+        let received: ReceivedMessage<UnixAddress> =
+            self.recv_message(&mut [IoSliceMut::new(&mut data)], receive_socket_flags())?;
+        let mut fds = received.control().iter().flat_map(|control| match control {
+            ControlMessage::Rights(fds) => fds.clone(),
+            _ => Vec::new(),
+        });
+        let socket = fds.next().map(|fd| unsafe { Socket::<K>::from_raw_fd(fd) });
+        // only a single socket is expected, any other descriptors are closed right away
+        for fd in fds {
+            let _ = unsafe { libc::close(fd) };
+        }
+        let socket = socket.ok_or(SystemError::from_raw_os_error(ENOMSG))?;
+        if is_kind::<K>(&socket)? {
+            Ok(socket)
+        } else {
+            Err(SystemError::from_raw_os_error(EPROTOTYPE))
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn receive_socket_flags() -> i32 {
+    MSG_CMSG_CLOEXEC
+}
+
+#[cfg(not(target_os = "linux"))]
+fn receive_socket_flags() -> i32 {
+    0
+}
+
+#[cfg(target_os = "linux")]
+impl<S: UnixSocketKind> Socket<S> {
+    /// Sets whether received messages carry the credentials of their sender, as a
+    /// `ControlMessage::Credentials` (`SO_PASSCRED`)
+    pub fn set_pass_credentials(&mut self, pass: bool) -> Result<&mut Self> {
+        self.set_option(PassCredentials::new(pass))
+    }
+
+    /// Returns the credentials of the peer at the time it connected, or created the pair
+    /// (`SO_PEERCRED`)
+    pub fn peer_credentials(&self) -> Result<Credentials> {
+        let mut credentials: ucred = unsafe { zeroed() };
+        let mut length = size_of::<ucred>() as socklen_t;
+        let ptr: *mut ucred = &mut credentials;
+        unsafe {
+            let _ = cvt(libc::getsockopt(
+                self.os(),
+                SOL_SOCKET,
+                SO_PEERCRED,
+                ptr as *mut c_void,
+                &mut length,
+            ))
+            .context(Operation::Syscall("getsockopt"))?;
+        }
+        Ok(Credentials::new(
+            credentials.pid,
+            credentials.uid,
+            credentials.gid,
+        ))
+    }
+}
+
+impl<S: UnixConnectionKind> Socket<S> {
+    /// Starts accepting connections, queueing at most `backlog` pending connections
+    pub fn listen(&mut self, backlog: i32) -> Result<&mut Self> {
+        let _ = unsafe { cvt(libc::listen(self.os(), backlog)) }
+            .context(Operation::Syscall("listen"))?;
+        Ok(self)
+    }
+
+    /// Accepts a pending connection, blocking until one arrives if the socket is blocking
+    ///
+    /// The returned `Socket` owns a new file descriptor, with the `O_CLOEXEC` flag set if
+    /// available for the target
+    pub fn accept(&self) -> Result<Self> {
+        let fd = self.accept_fd()?;
+        Ok(unsafe { Socket::from_raw_fd(fd) })
+    }
+
+    /// Sends the gathered buffers and control messages of `message` to the socket's peer,
+    /// returns the number of bytes sent
+    ///
+    /// `message` shouldn't be addressed, as stream sockets reject addressed messages
+    pub fn send_message(&self, message: &Message<'_, UnixAddress>, flags: i32) -> Result<usize> {
+        self.send_message_raw(message, flags)
+    }
+
+    /// Receives data scattered to `buffers` (`recvmsg(2)`), along with control messages
+    pub fn receive_message(
+        &self,
+        buffers: &mut [IoSliceMut<'_>],
+        flags: i32,
+    ) -> Result<ReceivedMessage<UnixAddress>> {
+        self.recv_message(buffers, flags)
+    }
+}
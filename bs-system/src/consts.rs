@@ -34,12 +34,14 @@ pub const BPF_JSET: i32 = 0x40;
 pub const BPF_K: i32 = 0x00;
 pub const BPF_X: i32 = 0x08;
 pub const BPF_A: i32 = 0x10;
+pub const BPF_MAXINSNS: usize = 4096;
 
 pub const OFFSET_ETHER_DST: u32 = 0;
 pub const OFFSET_ETHER_SRC: u32 = 6;
 pub const OFFSET_ETHER_TYPE: u32 = 12;
 pub const SIZE_ETHER_HEADER: u32 = 14;
 
+pub const OFFSET_IP4_FRAGMENT: u32 = 6;
 pub const OFFSET_IP4_TTL: u32 = 8;
 pub const OFFSET_IP4_PROTO: u32 = 9;
 pub const OFFSET_IP4_SRC: u32 = 12;
@@ -50,12 +52,159 @@ pub const OFFSET_IP6_HOP_LIMIT: u32 = 7;
 pub const OFFSET_IP6_SRC: u32 = 8;
 pub const OFFSET_IP6_DST: u32 = 24;
 
+pub const OFFSET_TRANSPORT_SRC_PORT: u32 = 0;
+pub const OFFSET_TRANSPORT_DST_PORT: u32 = 2;
+
+pub const IPPROTO_TCP: u8 = 6;
+pub const IPPROTO_UDP: u8 = 17;
+pub const IPPROTO_SCTP: u8 = 132;
+
+pub const TCP_CA_NAME_MAX: usize = 16;
+
+pub const UDP_CORK: i32 = 1;
+pub const UDP_SEGMENT: i32 = 103;
+pub const UDP_GRO: i32 = 104;
+
+pub const SOL_SCTP: i32 = 132;
+pub const SCTP_SNDRCV: i32 = 1;
+pub const SCTP_EVENTS: i32 = 11;
+pub const SCTP_SOCKOPT_BINDX_ADD: i32 = 100;
+pub const SCTP_SOCKOPT_BINDX_REM: i32 = 101;
+pub const SCTP_SOCKOPT_CONNECTX: i32 = 110;
+pub const MSG_NOTIFICATION: i32 = 0x8000;
+pub const SCTP_UNORDERED: u16 = 1;
+pub const SCTP_ADDR_OVER: u16 = 1 << 1;
+pub const SCTP_ABORT: u16 = 1 << 2;
+pub const SCTP_SACK_IMMEDIATELY: u16 = 1 << 3;
+pub const SCTP_EOF: u16 = 0x200;
+pub const SCTP_ASSOC_CHANGE: u16 = 0x8001;
+pub const SCTP_PEER_ADDR_CHANGE: u16 = 0x8002;
+pub const SCTP_SEND_FAILED: u16 = 0x8003;
+pub const SCTP_REMOTE_ERROR: u16 = 0x8004;
+pub const SCTP_SHUTDOWN_EVENT: u16 = 0x8005;
+pub const SCTP_PARTIAL_DELIVERY_EVENT: u16 = 0x8006;
+pub const SCTP_ADAPTATION_INDICATION: u16 = 0x8007;
+pub const SCTP_AUTHENTICATION_EVENT: u16 = 0x8008;
+pub const SCTP_SENDER_DRY_EVENT: u16 = 0x8009;
+
+pub const SO_ATTACH_FILTER: i32 = 26;
+pub const SO_DETACH_FILTER: i32 = 27;
+pub const SO_LOCK_FILTER: i32 = 44;
+pub const SO_ATTACH_BPF: i32 = 50;
+pub const SO_TIMESTAMPNS: i32 = 35;
+pub const SCM_TIMESTAMPNS: i32 = SO_TIMESTAMPNS;
+
+pub const PACKET_ADD_MEMBERSHIP: i32 = 1;
+pub const PACKET_DROP_MEMBERSHIP: i32 = 2;
+pub const PACKET_MR_MULTICAST: u16 = 0;
+pub const PACKET_MR_PROMISC: u16 = 1;
+pub const PACKET_MR_ALLMULTI: u16 = 2;
+
+pub const PACKET_AUXDATA: i32 = 8;
+pub const TP_STATUS_CSUMNOTREADY: u32 = 1 << 3;
+pub const TP_STATUS_VLAN_VALID: u32 = 1 << 4;
+pub const TP_STATUS_VLAN_TPID_VALID: u32 = 1 << 6;
+pub const TP_STATUS_CSUM_VALID: u32 = 1 << 7;
+
+pub const PACKET_RX_RING: i32 = 5;
+pub const PACKET_STATISTICS: i32 = 6;
+pub const PACKET_VERSION: i32 = 10;
+pub const PACKET_TX_RING: i32 = 13;
+pub const PACKET_RESERVE: i32 = 12;
+pub const PACKET_LOSS: i32 = 14;
+pub const PACKET_TIMESTAMP: i32 = 17;
+pub const PACKET_FANOUT: i32 = 18;
+pub const PACKET_QDISC_BYPASS: i32 = 20;
+pub const PACKET_FANOUT_DATA: i32 = 22;
+pub const PACKET_IGNORE_OUTGOING: i32 = 23;
+pub const PACKET_FANOUT_HASH: u16 = 0;
+pub const PACKET_FANOUT_LB: u16 = 1;
+pub const PACKET_FANOUT_CPU: u16 = 2;
+pub const PACKET_FANOUT_ROLLOVER: u16 = 3;
+pub const PACKET_FANOUT_RND: u16 = 4;
+pub const PACKET_FANOUT_QM: u16 = 5;
+pub const PACKET_FANOUT_CBPF: u16 = 6;
+pub const PACKET_FANOUT_EBPF: u16 = 7;
+pub const PACKET_FANOUT_FLAG_ROLLOVER: u16 = 0x1000;
+pub const PACKET_FANOUT_FLAG_IGNORE_OUTGOING: u16 = 0x4000;
+pub const PACKET_FANOUT_FLAG_DEFRAG: u16 = 0x8000;
+pub const TPACKET_V2: i32 = 1;
+pub const TPACKET_V3: i32 = 2;
+pub const TPACKET_ALIGNMENT: usize = 16;
+pub const TP_FT_REQ_FILL_RXHASH: u32 = 1;
+pub const TP_STATUS_KERNEL: u32 = 0;
+pub const TP_STATUS_USER: u32 = 1;
+pub const TP_STATUS_AVAILABLE: u32 = 0;
+pub const TP_STATUS_SEND_REQUEST: u32 = 1;
+pub const TP_STATUS_SENDING: u32 = 2;
+pub const TP_STATUS_WRONG_FORMAT: u32 = 1 << 2;
+
 pub const ETH_P_IP: u32 = 0x0800;
 pub const ETH_P_ARP: u32 = 0x0806;
 pub const ETH_P_IPV6: u32 = 0x86DD;
 pub const ETH_P_LLDP: u32 = 0x88CC;
 pub const ETH_P_8021Q: u32 = 0x8100;
 
+pub const OFFSET_NLMSG_TYPE: u32 = 4;
+pub const NLMSG_ALIGNTO: usize = 4;
+pub const NLMSG_HDRLEN: usize = 16;
+pub const NLA_HDRLEN: usize = 4;
+pub const NLMSG_NOOP: u16 = 1;
+pub const NLMSG_ERROR: u16 = 2;
+pub const NLMSG_DONE: u16 = 3;
+pub const NLMSG_OVERRUN: u16 = 4;
+pub const NLM_F_REQUEST: u16 = 1;
+pub const NLM_F_MULTI: u16 = 2;
+pub const NLM_F_ACK: u16 = 4;
+pub const NLM_F_ECHO: u16 = 8;
+pub const NLM_F_ROOT: u16 = 0x100;
+pub const NLM_F_MATCH: u16 = 0x200;
+pub const NLM_F_DUMP: u16 = NLM_F_ROOT | NLM_F_MATCH;
+pub const NLM_F_REPLACE: u16 = 0x100;
+pub const NLM_F_EXCL: u16 = 0x200;
+pub const NLM_F_CREATE: u16 = 0x400;
+pub const NLM_F_APPEND: u16 = 0x800;
+pub const NLA_F_NESTED: u16 = 1 << 15;
+pub const NLA_F_NET_BYTEORDER: u16 = 1 << 14;
+pub const NLA_TYPE_MASK: u16 = !(NLA_F_NESTED | NLA_F_NET_BYTEORDER);
+pub const GENL_ID_CTRL: u16 = 16;
+pub const CTRL_CMD_GETFAMILY: u8 = 3;
+pub const CTRL_ATTR_FAMILY_ID: u16 = 1;
+pub const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+pub const SOCK_DIAG_BY_FAMILY: u16 = 20;
+
+pub const PACKET_HOST: u8 = 0;
+pub const PACKET_BROADCAST: u8 = 1;
+pub const PACKET_MULTICAST: u8 = 2;
+pub const PACKET_OTHERHOST: u8 = 3;
+pub const PACKET_OUTGOING: u8 = 4;
+
+/* Classic BPF ancillary data, loaded from negative offsets */
+pub const SKF_AD_OFF: i32 = -0x1000;
+pub const SKF_NET_OFF: i32 = -0x100000;
+pub const SKF_AD_PROTOCOL: i32 = 0;
+pub const SKF_AD_PKTTYPE: i32 = 4;
+pub const SKF_AD_IFINDEX: i32 = 8;
+pub const SKF_AD_MARK: i32 = 20;
+pub const SKF_AD_QUEUE: i32 = 24;
+pub const SKF_AD_RXHASH: i32 = 32;
+pub const SKF_AD_CPU: i32 = 36;
+pub const SKF_AD_RANDOM: i32 = 56;
+
+/* Offsets of `struct __sk_buff` fields, the context of eBPF socket filters */
+pub const OFFSET_SK_BUFF_LEN: i16 = 0;
+pub const OFFSET_SK_BUFF_PKT_TYPE: i16 = 4;
+pub const OFFSET_SK_BUFF_MARK: i16 = 8;
+pub const OFFSET_SK_BUFF_QUEUE_MAPPING: i16 = 12;
+pub const OFFSET_SK_BUFF_PROTOCOL: i16 = 16;
+pub const OFFSET_SK_BUFF_IFINDEX: i16 = 40;
+pub const OFFSET_SK_BUFF_HASH: i16 = 68;
+
+/* eBPF kernel helper functions */
+pub const BPF_FUNC_MAP_LOOKUP_ELEM: i32 = 1;
+pub const BPF_FUNC_GET_PRANDOM_U32: i32 = 7;
+pub const BPF_FUNC_GET_SMP_PROCESSOR_ID: i32 = 8;
+
 /* Extended instruction set based on top of classic BPF */
 pub const BPF_JMP32: i32 = 0x06;
 pub const BPF_ALU64: i32 = 0x07;
@@ -77,3 +226,10 @@ pub const BPF_JSLT: i32 = 0xc0;
 pub const BPF_JSLE: i32 = 0xd0;
 pub const BPF_CALL: i32 = 0x80;
 pub const BPF_EXIT: i32 = 0x90;
+pub const BPF_PSEUDO_MAP_FD: u8 = 1;
+
+/* eBPF maps */
+pub const BPF_MAP_CREATE: i32 = 0;
+pub const BPF_MAP_UPDATE_ELEM: i32 = 2;
+pub const BPF_MAP_TYPE_HASH: u32 = 1;
+pub const BPF_ANY: u64 = 0;
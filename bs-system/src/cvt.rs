@@ -84,7 +84,7 @@ impl_is_minus_one! { i8 i16 i32 i64 isize }
 /// instead of `std::io::Error`
 pub fn cvt<T: IsMinusOne>(t: T) -> Result<T> {
     if t.is_minus_one() {
-        Err(SystemError(errno()))
+        Err(SystemError::from_raw_os_error(errno()))
     } else {
         Ok(t)
     }
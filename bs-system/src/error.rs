@@ -0,0 +1,446 @@
+//! `SystemError`, the error type of every `bs` crate
+//!
+//! Most errors are an `errno` value, returned by a system call or produced by `bs` itself for
+//! the closest equivalent condition, e.g. `EINVAL` for a malformed netlink message. Errors
+//! record the operation which failed when it's known, and building a filter fails with errors
+//! of their own, as they aren't reported through `errno` alone.
+
+use crate::cvt::errno::errno;
+use crate::Name;
+use std::error;
+use std::fmt;
+use std::io;
+
+/// `bs`'s custom `Error` type, returned by `SocketOption::set`/`get`, `Socket` methods, etc.
+///
+/// much like `std::io::Error`, this is mostly just a wrapper for `errno`,
+/// but unlike `std::io::Error`, it can
+/// [actually](https://internals.rust-lang.org/t/insufficient-std-io-error/3597) represent every
+/// relevant `errno` value. Both convert into one another without losing the `errno` value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SystemError {
+    /// An operation failed with `errno`
+    Os {
+        /// The `errno` value
+        errno: i32,
+        /// The failed operation, if known
+        operation: Option<Operation>,
+    },
+
+    /// A filter can't be compiled to its backend's instructions, e.g. as it's too long
+    Compilation(String),
+
+    /// A compiled classic program would be rejected by the kernel, e.g. as it jumps out of
+    /// bounds
+    Validation(String),
+
+    /// The kernel's verifier rejected an extended program
+    Verifier {
+        /// The `errno` value `bpf(2)` failed with
+        errno: i32,
+        /// The verifier's log, only collected by debug builds
+        log: String,
+    },
+}
+
+/// The operation which failed with a `SystemError`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// A system call, e.g. `"bind"`
+    Syscall(&'static str),
+
+    /// `setsockopt(2)` of the option
+    SetOption(Name),
+
+    /// `getsockopt(2)` of the option
+    GetOption(Name),
+
+    /// A stage of building a filter
+    Filter(FilterStage),
+}
+
+/// The stages of building a filter, each of which may fail
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FilterStage {
+    /// Compiling a filter to its backend's instructions
+    Compilation,
+
+    /// Validating a classic program before attaching it
+    Validation,
+
+    /// Loading an extended program, which the kernel's verifier checks
+    Verification,
+}
+
+impl SystemError {
+    /// Creates a `SystemError` from an `errno` value, without an operation
+    pub fn from_raw_os_error(errno: i32) -> Self {
+        SystemError::Os {
+            errno,
+            operation: None,
+        }
+    }
+
+    /// Creates a `SystemError` from the current value of `errno`
+    pub fn last_os_error() -> Self {
+        Self::from_raw_os_error(errno())
+    }
+
+    /// The `errno` value of the error, `None` for filter compilation and validation errors
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match *self {
+            SystemError::Os { errno, .. } | SystemError::Verifier { errno, .. } => Some(errno),
+            SystemError::Compilation(_) | SystemError::Validation(_) => None,
+        }
+    }
+
+    /// The operation which failed, if known
+    pub fn operation(&self) -> Option<Operation> {
+        match *self {
+            SystemError::Os { operation, .. } => operation,
+            SystemError::Compilation(_) => Some(Operation::Filter(FilterStage::Compilation)),
+            SystemError::Validation(_) => Some(Operation::Filter(FilterStage::Validation)),
+            SystemError::Verifier { .. } => Some(Operation::Filter(FilterStage::Verification)),
+        }
+    }
+
+    /// Records `operation` as the failed operation, unless one was already recorded
+    pub fn with_operation(self, operation: Operation) -> Self {
+        match self {
+            SystemError::Os {
+                errno,
+                operation: None,
+            } => SystemError::Os {
+                errno,
+                operation: Some(operation),
+            },
+            error => error,
+        }
+    }
+
+    /// The `io::ErrorKind` of the error's `errno` value, `InvalidInput` for filter compilation
+    /// and validation errors
+    pub fn kind(&self) -> io::ErrorKind {
+        match self.raw_os_error() {
+            Some(errno) => io::Error::from_raw_os_error(errno).kind(),
+            None => io::ErrorKind::InvalidInput,
+        }
+    }
+}
+
+/// Records the failed operation of a `Result`'s error
+pub trait Context {
+    /// Records `operation` as the failed operation, unless one was already recorded
+    fn context(self, operation: Operation) -> Self;
+}
+
+impl<T> Context for Result<T, SystemError> {
+    fn context(self, operation: Operation) -> Self {
+        self.map_err(|error| error.with_operation(operation))
+    }
+}
+
+/// Declares `describe`, mapping each `errno` value to its name and description
+macro_rules! errnos {
+    ($($(#[cfg($cfg:meta)])? $name:ident: $description:literal,)*) => {
+        /// The name and description of `errno`, e.g. `("EINVAL", "Invalid argument")`
+        fn describe(errno: i32) -> Option<(&'static str, &'static str)> {
+            match errno {
+                $(
+                    $(#[cfg($cfg)])?
+                    libc::$name => Some((stringify!($name), $description)),
+                )*
+                _ => None,
+            }
+        }
+    };
+}
+
+// aliases, e.g. `EWOULDBLOCK` of `EAGAIN`, are left out as they'd be unreachable
+errnos! {
+    EPERM: "Operation not permitted",
+    ENOENT: "No such file or directory",
+    ESRCH: "No such process",
+    EINTR: "Interrupted system call",
+    EIO: "Input/output error",
+    ENXIO: "No such device or address",
+    E2BIG: "Argument list too long",
+    ENOEXEC: "Exec format error",
+    EBADF: "Bad file descriptor",
+    ECHILD: "No child processes",
+    EAGAIN: "Resource temporarily unavailable",
+    ENOMEM: "Cannot allocate memory",
+    EACCES: "Permission denied",
+    EFAULT: "Bad address",
+    ENOTBLK: "Block device required",
+    EBUSY: "Device or resource busy",
+    EEXIST: "File exists",
+    EXDEV: "Invalid cross-device link",
+    ENODEV: "No such device",
+    ENOTDIR: "Not a directory",
+    EISDIR: "Is a directory",
+    EINVAL: "Invalid argument",
+    ENFILE: "Too many open files in system",
+    EMFILE: "Too many open files",
+    ENOTTY: "Inappropriate ioctl for device",
+    ETXTBSY: "Text file busy",
+    EFBIG: "File too large",
+    ENOSPC: "No space left on device",
+    ESPIPE: "Illegal seek",
+    EROFS: "Read-only file system",
+    EMLINK: "Too many links",
+    EPIPE: "Broken pipe",
+    EDOM: "Numerical argument out of domain",
+    ERANGE: "Numerical result out of range",
+    EDEADLK: "Resource deadlock avoided",
+    ENAMETOOLONG: "File name too long",
+    ENOLCK: "No locks available",
+    ENOSYS: "Function not implemented",
+    ENOTEMPTY: "Directory not empty",
+    ELOOP: "Too many levels of symbolic links",
+    ENOMSG: "No message of desired type",
+    EIDRM: "Identifier removed",
+    EREMOTE: "Object is remote",
+    EPROTO: "Protocol error",
+    EBADMSG: "Bad message",
+    EOVERFLOW: "Value too large for defined data type",
+    EILSEQ: "Invalid or incomplete multibyte or wide character",
+    EUSERS: "Too many users",
+    ENOTSOCK: "Socket operation on non-socket",
+    EDESTADDRREQ: "Destination address required",
+    EMSGSIZE: "Message too long",
+    EPROTOTYPE: "Protocol wrong type for socket",
+    ENOPROTOOPT: "Protocol not available",
+    EPROTONOSUPPORT: "Protocol not supported",
+    ESOCKTNOSUPPORT: "Socket type not supported",
+    EOPNOTSUPP: "Operation not supported",
+    EPFNOSUPPORT: "Protocol family not supported",
+    EAFNOSUPPORT: "Address family not supported by protocol",
+    EADDRINUSE: "Address already in use",
+    EADDRNOTAVAIL: "Cannot assign requested address",
+    ENETDOWN: "Network is down",
+    ENETUNREACH: "Network is unreachable",
+    ENETRESET: "Network dropped connection on reset",
+    ECONNABORTED: "Software caused connection abort",
+    ECONNRESET: "Connection reset by peer",
+    ENOBUFS: "No buffer space available",
+    EISCONN: "Transport endpoint is already connected",
+    ENOTCONN: "Transport endpoint is not connected",
+    ESHUTDOWN: "Cannot send after transport endpoint shutdown",
+    ETOOMANYREFS: "Too many references: cannot splice",
+    ETIMEDOUT: "Connection timed out",
+    ECONNREFUSED: "Connection refused",
+    EHOSTDOWN: "Host is down",
+    EHOSTUNREACH: "No route to host",
+    EALREADY: "Operation already in progress",
+    EINPROGRESS: "Operation now in progress",
+    ESTALE: "Stale file handle",
+    EDQUOT: "Disk quota exceeded",
+    ECANCELED: "Operation canceled",
+    #[cfg(not(target_os = "linux"))]
+    ENOTSUP: "Operation not supported",
+    #[cfg(target_os = "linux")]
+    ECHRNG: "Channel number out of range",
+    #[cfg(target_os = "linux")]
+    EL2NSYNC: "Level 2 not synchronized",
+    #[cfg(target_os = "linux")]
+    EL3HLT: "Level 3 halted",
+    #[cfg(target_os = "linux")]
+    EL3RST: "Level 3 reset",
+    #[cfg(target_os = "linux")]
+    ELNRNG: "Link number out of range",
+    #[cfg(target_os = "linux")]
+    EUNATCH: "Protocol driver not attached",
+    #[cfg(target_os = "linux")]
+    ENOCSI: "No CSI structure available",
+    #[cfg(target_os = "linux")]
+    EL2HLT: "Level 2 halted",
+    #[cfg(target_os = "linux")]
+    EBADE: "Invalid exchange",
+    #[cfg(target_os = "linux")]
+    EBADR: "Invalid request descriptor",
+    #[cfg(target_os = "linux")]
+    EXFULL: "Exchange full",
+    #[cfg(target_os = "linux")]
+    ENOANO: "No anode",
+    #[cfg(target_os = "linux")]
+    EBADRQC: "Invalid request code",
+    #[cfg(target_os = "linux")]
+    EBADSLT: "Invalid slot",
+    #[cfg(target_os = "linux")]
+    EBFONT: "Bad font file format",
+    #[cfg(target_os = "linux")]
+    ENOSTR: "Device not a stream",
+    #[cfg(target_os = "linux")]
+    ENODATA: "No data available",
+    #[cfg(target_os = "linux")]
+    ETIME: "Timer expired",
+    #[cfg(target_os = "linux")]
+    ENOSR: "Out of streams resources",
+    #[cfg(target_os = "linux")]
+    ENONET: "Machine is not on the network",
+    #[cfg(target_os = "linux")]
+    ENOPKG: "Package not installed",
+    #[cfg(target_os = "linux")]
+    ENOLINK: "Link has been severed",
+    #[cfg(target_os = "linux")]
+    EADV: "Advertise error",
+    #[cfg(target_os = "linux")]
+    ESRMNT: "Srmount error",
+    #[cfg(target_os = "linux")]
+    ECOMM: "Communication error on send",
+    #[cfg(target_os = "linux")]
+    EMULTIHOP: "Multihop attempted",
+    #[cfg(target_os = "linux")]
+    EDOTDOT: "RFS specific error",
+    #[cfg(target_os = "linux")]
+    ENOTUNIQ: "Name not unique on network",
+    #[cfg(target_os = "linux")]
+    EBADFD: "File descriptor in bad state",
+    #[cfg(target_os = "linux")]
+    EREMCHG: "Remote address changed",
+    #[cfg(target_os = "linux")]
+    ELIBACC: "Can not access a needed shared library",
+    #[cfg(target_os = "linux")]
+    ELIBBAD: "Accessing a corrupted shared library",
+    #[cfg(target_os = "linux")]
+    ELIBSCN: ".lib section in a.out corrupted",
+    #[cfg(target_os = "linux")]
+    ELIBMAX: "Attempting to link in too many shared libraries",
+    #[cfg(target_os = "linux")]
+    ELIBEXEC: "Cannot exec a shared library directly",
+    #[cfg(target_os = "linux")]
+    ERESTART: "Interrupted system call should be restarted",
+    #[cfg(target_os = "linux")]
+    ESTRPIPE: "Streams pipe error",
+    #[cfg(target_os = "linux")]
+    EUCLEAN: "Structure needs cleaning",
+    #[cfg(target_os = "linux")]
+    ENOTNAM: "Not a XENIX named type file",
+    #[cfg(target_os = "linux")]
+    ENAVAIL: "No XENIX semaphores available",
+    #[cfg(target_os = "linux")]
+    EISNAM: "Is a named type file",
+    #[cfg(target_os = "linux")]
+    EREMOTEIO: "Remote I/O error",
+    #[cfg(target_os = "linux")]
+    ENOMEDIUM: "No medium found",
+    #[cfg(target_os = "linux")]
+    EMEDIUMTYPE: "Wrong medium type",
+    #[cfg(target_os = "linux")]
+    ENOKEY: "Required key not available",
+    #[cfg(target_os = "linux")]
+    EKEYEXPIRED: "Key has expired",
+    #[cfg(target_os = "linux")]
+    EKEYREVOKED: "Key has been revoked",
+    #[cfg(target_os = "linux")]
+    EKEYREJECTED: "Key was rejected by service",
+    #[cfg(target_os = "linux")]
+    EOWNERDEAD: "Owner died",
+    #[cfg(target_os = "linux")]
+    ENOTRECOVERABLE: "State not recoverable",
+    #[cfg(target_os = "linux")]
+    ERFKILL: "Operation not possible due to RF-kill",
+    #[cfg(target_os = "linux")]
+    EHWPOISON: "Memory page has hardware error",
+}
+
+/// Writes `errno`'s description and name, e.g. `Invalid argument (EINVAL)`
+fn write_errno(f: &mut fmt::Formatter<'_>, errno: i32) -> fmt::Result {
+    match describe(errno) {
+        Some((name, description)) => write!(f, "{} ({})", description, name),
+        None => write!(f, "Unknown error {}", errno),
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Syscall(name) => write!(f, "{}", name),
+            Operation::SetOption(name) => write!(f, "setsockopt({})", name),
+            Operation::GetOption(name) => write!(f, "getsockopt({})", name),
+            Operation::Filter(FilterStage::Compilation) => write!(f, "filter compilation"),
+            Operation::Filter(FilterStage::Validation) => write!(f, "filter validation"),
+            Operation::Filter(FilterStage::Verification) => write!(f, "filter verification"),
+        }
+    }
+}
+
+impl fmt::Display for SystemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(operation) = self.operation() {
+            write!(f, "{}: ", operation)?;
+        }
+        match self {
+            SystemError::Os { errno, .. } => write_errno(f, *errno),
+            SystemError::Compilation(reason) | SystemError::Validation(reason) => {
+                write!(f, "{}", reason)
+            }
+            SystemError::Verifier { errno, log } => {
+                write_errno(f, *errno)?;
+                if !log.is_empty() {
+                    write!(f, "\n{}", log)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for SystemError {}
+
+/// The closest `errno` value to an `io::ErrorKind`, for `io::Error`s without one
+fn errno_of_kind(kind: io::ErrorKind) -> i32 {
+    match kind {
+        io::ErrorKind::NotFound => libc::ENOENT,
+        io::ErrorKind::PermissionDenied => libc::EACCES,
+        io::ErrorKind::ConnectionRefused => libc::ECONNREFUSED,
+        io::ErrorKind::ConnectionReset => libc::ECONNRESET,
+        io::ErrorKind::ConnectionAborted => libc::ECONNABORTED,
+        io::ErrorKind::NotConnected => libc::ENOTCONN,
+        io::ErrorKind::AddrInUse => libc::EADDRINUSE,
+        io::ErrorKind::AddrNotAvailable => libc::EADDRNOTAVAIL,
+        io::ErrorKind::BrokenPipe => libc::EPIPE,
+        io::ErrorKind::AlreadyExists => libc::EEXIST,
+        io::ErrorKind::WouldBlock => libc::EAGAIN,
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => libc::EINVAL,
+        io::ErrorKind::TimedOut => libc::ETIMEDOUT,
+        io::ErrorKind::Interrupted => libc::EINTR,
+        io::ErrorKind::Unsupported => libc::EOPNOTSUPP,
+        io::ErrorKind::OutOfMemory => libc::ENOMEM,
+        _ => libc::EIO,
+    }
+}
+
+/// Keeps the `errno` value, so `raw_os_error` is set for all errors that have one
+///
+/// An `io::Error` can't hold both an `errno` value and a payload, so the operation and the
+/// verifier log are left behind. Errors without an `errno` value are wrapped whole.
+impl From<SystemError> for io::Error {
+    fn from(error: SystemError) -> Self {
+        match error {
+            SystemError::Os { errno, .. } | SystemError::Verifier { errno, .. } => {
+                io::Error::from_raw_os_error(errno)
+            }
+            error => io::Error::new(error.kind(), error),
+        }
+    }
+}
+
+/// Takes the `errno` value, or unwraps a converted `SystemError`, or maps the `io::ErrorKind`
+/// to its closest `errno` value
+impl From<io::Error> for SystemError {
+    fn from(error: io::Error) -> Self {
+        if let Some(errno) = error.raw_os_error() {
+            return Self::from_raw_os_error(errno);
+        }
+        let kind = error.kind();
+        match error
+            .into_inner()
+            .map(|inner| inner.downcast::<SystemError>())
+        {
+            Some(Ok(error)) => *error,
+            _ => Self::from_raw_os_error(errno_of_kind(kind)),
+        }
+    }
+}
@@ -53,72 +53,317 @@
 #[doc(hidden)]
 pub mod consts;
 mod cvt;
+mod error;
+pub mod option;
 
 pub use cvt::cvt;
-use libc::EBADF;
+pub use error::{Context, FilterStage, Operation, SystemError};
+#[cfg(target_os = "linux")]
+use libc::SOL_PACKET;
 use libc::SOL_SOCKET;
 use libc::{c_void, socklen_t};
 use libc::{getsockopt, setsockopt};
+use libc::{IPPROTO_IP, IPPROTO_IPV6, IPPROTO_TCP, IPPROTO_UDP};
 use log::debug;
-use std::convert::TryFrom;
-use std::error;
 use std::fmt;
 use std::fmt::Debug;
-use std::mem::size_of;
+use std::mem::{size_of, MaybeUninit};
 use std::os::unix::io::RawFd;
 
-/// `bs-system`'s custom `Error` type, returned by `SocketOption::set`/`get`.
-///
-/// much like `std::io::Error`, this is mostly just a wrapper for `errno`,
-/// but unlike `std::io::Error`, it can
-/// [actually](https://internals.rust-lang.org/t/insufficient-std-io-error/3597) represent every relevant `errno` value
-#[derive(Debug, PartialEq, Copy, Clone)]
-pub struct SystemError(pub i32);
-
-impl error::Error for SystemError {}
-
-impl fmt::Display for SystemError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.0 {
-            EBADF => write!(f, "Bad file descriptor"),
-            _ => unreachable!(), // TODO - think this through
-        }
-    }
-}
-
-impl From<std::io::Error> for SystemError {
-    fn from(error: std::io::Error) -> Self {
-        SystemError(error.kind() as i32)
-    }
-}
-
 /// `bs-sockopt`'s custom `Result` type, returned by `SocketOption::set`/`get`, etc.
 /// uses `SystemError` as its `Err` variant
 pub type Result<T> = std::result::Result<T, SystemError>;
 
 /// `setsockopt`'s `level` arguments
 #[repr(i32)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Level {
-    /// `SOL_SOCKET`
+    /// `SOL_SOCKET`, see `socket(7)`
     Socket = SOL_SOCKET,
+
+    /// `IPPROTO_IP`, see `ip(7)`
+    Ip = IPPROTO_IP,
+
+    /// `IPPROTO_IPV6`, see `ipv6(7)`
+    Ipv6 = IPPROTO_IPV6,
+
+    /// `IPPROTO_TCP`, see `tcp(7)`
+    Tcp = IPPROTO_TCP,
+
+    /// `IPPROTO_UDP`, see `udp(7)`
+    Udp = IPPROTO_UDP,
+
+    /// `SOL_PACKET`, see `packet(7)`
+    #[cfg(target_os = "linux")]
+    Packet = SOL_PACKET,
 }
 
-/// `setsockopt`'s `optname` arguments
-#[repr(i32)]
-#[derive(Debug, Copy, Clone)]
-pub enum Name {
-    /// `SO_ATTACH_FILTER`
-    AttachFilter = 26,
+/// Declares `Name`, mapping each of its variants to its `Level` and its `optname` value
+macro_rules! names {
+    ($(
+        $level:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                $(#[cfg($cfg:meta)])?
+                $name:ident = $module:ident::$constant:ident,
+            )*
+        }
+    )*) => {
+        /// `setsockopt`'s `optname` arguments
+        ///
+        /// Option names are only unique within their `Level`, e.g. `IP_TTL` and `SO_REUSEADDR`
+        /// share the same value, so each `Name` also knows its `Level`.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub enum Name {
+            $($(
+                $(#[doc = $doc])*
+                $(#[cfg($cfg)])?
+                $name,
+            )*)*
+        }
+
+        impl Name {
+            /// The `Level` the option belongs to
+            pub fn level(self) -> Level {
+                match self {
+                    $($(
+                        $(#[cfg($cfg)])?
+                        Name::$name => Level::$level,
+                    )*)*
+                }
+            }
+
+            /// The `optname` argument for `set/getsockopt(2)`
+            pub fn value(self) -> i32 {
+                match self {
+                    $($(
+                        $(#[cfg($cfg)])?
+                        Name::$name => $module::$constant,
+                    )*)*
+                }
+            }
+        }
+
+        /// Writes the option's C name, e.g. `SO_REUSEADDR`
+        impl fmt::Display for Name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let name = match self {
+                    $($(
+                        $(#[cfg($cfg)])?
+                        Name::$name => stringify!($constant),
+                    )*)*
+                };
+                f.write_str(name)
+            }
+        }
+    };
+}
 
-    /// `SO_ATTACH_BPF`
-    AttachBpf = 50,
+names! {
+    Socket {
+        /// `SO_ATTACH_FILTER`
+        AttachFilter = consts::SO_ATTACH_FILTER,
+        /// `SO_ATTACH_BPF`
+        AttachBpf = consts::SO_ATTACH_BPF,
+        /// `SO_DETACH_FILTER`
+        #[cfg(target_os = "linux")]
+        DetachFilter = consts::SO_DETACH_FILTER,
+        /// `SO_LOCK_FILTER`
+        #[cfg(target_os = "linux")]
+        LockFilter = consts::SO_LOCK_FILTER,
+        /// `SO_ACCEPTCONN`
+        AcceptConnections = libc::SO_ACCEPTCONN,
+        /// `SO_BROADCAST`
+        Broadcast = libc::SO_BROADCAST,
+        /// `SO_DONTROUTE`
+        DontRoute = libc::SO_DONTROUTE,
+        /// `SO_ERROR`
+        Error = libc::SO_ERROR,
+        /// `SO_KEEPALIVE`
+        KeepAlive = libc::SO_KEEPALIVE,
+        /// `SO_LINGER`
+        Linger = libc::SO_LINGER,
+        /// `SO_RCVBUF`
+        ReceiveBuffer = libc::SO_RCVBUF,
+        /// `SO_RCVLOWAT`
+        ReceiveLowWatermark = libc::SO_RCVLOWAT,
+        /// `SO_RCVTIMEO`
+        ReceiveTimeout = libc::SO_RCVTIMEO,
+        /// `SO_REUSEADDR`
+        ReuseAddress = libc::SO_REUSEADDR,
+        /// `SO_REUSEPORT`
+        ReusePort = libc::SO_REUSEPORT,
+        /// `SO_SNDBUF`
+        SendBuffer = libc::SO_SNDBUF,
+        /// `SO_SNDTIMEO`
+        SendTimeout = libc::SO_SNDTIMEO,
+        /// `SO_TYPE`
+        Type = libc::SO_TYPE,
+        /// `SO_BINDTODEVICE`
+        #[cfg(target_os = "linux")]
+        BindToDevice = libc::SO_BINDTODEVICE,
+        /// `SO_BUSY_POLL`
+        #[cfg(target_os = "linux")]
+        BusyPoll = libc::SO_BUSY_POLL,
+        /// `SO_DOMAIN`
+        #[cfg(target_os = "linux")]
+        Domain = libc::SO_DOMAIN,
+        /// `SO_MARK`
+        #[cfg(target_os = "linux")]
+        Mark = libc::SO_MARK,
+        /// `SO_PASSCRED`
+        #[cfg(target_os = "linux")]
+        PassCredentials = libc::SO_PASSCRED,
+        /// `SO_PRIORITY`
+        #[cfg(target_os = "linux")]
+        Priority = libc::SO_PRIORITY,
+        /// `SO_PROTOCOL`
+        #[cfg(target_os = "linux")]
+        Protocol = libc::SO_PROTOCOL,
+        /// `SO_TIMESTAMP`
+        #[cfg(target_os = "linux")]
+        Timestamp = libc::SO_TIMESTAMP,
+        /// `SO_TIMESTAMPNS`
+        #[cfg(target_os = "linux")]
+        TimestampNs = consts::SO_TIMESTAMPNS,
+        /// `SO_TIMESTAMPING`
+        #[cfg(target_os = "linux")]
+        Timestamping = libc::SO_TIMESTAMPING,
+    }
+    Ip {
+        /// `IP_HDRINCL`
+        IpHeaderIncluded = libc::IP_HDRINCL,
+        /// `IP_MULTICAST_LOOP`
+        IpMulticastLoop = libc::IP_MULTICAST_LOOP,
+        /// `IP_MULTICAST_TTL`
+        IpMulticastTtl = libc::IP_MULTICAST_TTL,
+        /// `IP_RECVTOS`
+        IpReceiveTypeOfService = libc::IP_RECVTOS,
+        /// `IP_RECVTTL`
+        IpReceiveTtl = libc::IP_RECVTTL,
+        /// `IP_TOS`
+        IpTypeOfService = libc::IP_TOS,
+        /// `IP_TTL`
+        IpTtl = libc::IP_TTL,
+        /// `IP_BIND_ADDRESS_NO_PORT`
+        #[cfg(target_os = "linux")]
+        IpBindAddressNoPort = libc::IP_BIND_ADDRESS_NO_PORT,
+        /// `IP_FREEBIND`
+        #[cfg(target_os = "linux")]
+        IpFreeBind = libc::IP_FREEBIND,
+        /// `IP_MTU_DISCOVER`
+        #[cfg(target_os = "linux")]
+        IpMtuDiscover = libc::IP_MTU_DISCOVER,
+        /// `IP_PKTINFO`
+        #[cfg(target_os = "linux")]
+        IpPacketInfo = libc::IP_PKTINFO,
+        /// `IP_RECVERR`
+        #[cfg(target_os = "linux")]
+        IpReceiveError = libc::IP_RECVERR,
+        /// `IP_TRANSPARENT`
+        #[cfg(target_os = "linux")]
+        IpTransparent = libc::IP_TRANSPARENT,
+    }
+    Ipv6 {
+        /// `IPV6_MULTICAST_HOPS`
+        Ipv6MulticastHops = libc::IPV6_MULTICAST_HOPS,
+        /// `IPV6_MULTICAST_LOOP`
+        Ipv6MulticastLoop = libc::IPV6_MULTICAST_LOOP,
+        /// `IPV6_RECVHOPLIMIT`
+        Ipv6ReceiveHopLimit = libc::IPV6_RECVHOPLIMIT,
+        /// `IPV6_RECVPKTINFO`
+        Ipv6ReceivePacketInfo = libc::IPV6_RECVPKTINFO,
+        /// `IPV6_RECVTCLASS`
+        Ipv6ReceiveTrafficClass = libc::IPV6_RECVTCLASS,
+        /// `IPV6_TCLASS`
+        Ipv6TrafficClass = libc::IPV6_TCLASS,
+        /// `IPV6_UNICAST_HOPS`
+        Ipv6UnicastHops = libc::IPV6_UNICAST_HOPS,
+        /// `IPV6_V6ONLY`
+        Ipv6V6Only = libc::IPV6_V6ONLY,
+    }
+    Tcp {
+        /// `TCP_KEEPCNT`
+        TcpKeepAliveCount = libc::TCP_KEEPCNT,
+        /// `TCP_KEEPINTVL`
+        TcpKeepAliveInterval = libc::TCP_KEEPINTVL,
+        /// `TCP_MAXSEG`
+        TcpMaxSegment = libc::TCP_MAXSEG,
+        /// `TCP_NODELAY`
+        TcpNoDelay = libc::TCP_NODELAY,
+        /// `TCP_CONGESTION`
+        #[cfg(target_os = "linux")]
+        TcpCongestion = libc::TCP_CONGESTION,
+        /// `TCP_CORK`
+        #[cfg(target_os = "linux")]
+        TcpCork = libc::TCP_CORK,
+        /// `TCP_DEFER_ACCEPT`
+        #[cfg(target_os = "linux")]
+        TcpDeferAccept = libc::TCP_DEFER_ACCEPT,
+        /// `TCP_FASTOPEN`
+        #[cfg(target_os = "linux")]
+        TcpFastOpen = libc::TCP_FASTOPEN,
+        /// `TCP_INFO`
+        #[cfg(target_os = "linux")]
+        TcpInfo = libc::TCP_INFO,
+        /// `TCP_KEEPIDLE`
+        #[cfg(target_os = "linux")]
+        TcpKeepAliveIdle = libc::TCP_KEEPIDLE,
+        /// `TCP_NOTSENT_LOWAT`
+        #[cfg(target_os = "linux")]
+        TcpNotSentLowWatermark = libc::TCP_NOTSENT_LOWAT,
+        /// `TCP_QUICKACK`
+        #[cfg(target_os = "linux")]
+        TcpQuickAck = libc::TCP_QUICKACK,
+        /// `TCP_USER_TIMEOUT`
+        #[cfg(target_os = "linux")]
+        TcpUserTimeout = libc::TCP_USER_TIMEOUT,
+    }
+    Udp {
+        /// `UDP_CORK`
+        #[cfg(target_os = "linux")]
+        UdpCork = consts::UDP_CORK,
+        /// `UDP_GRO`
+        #[cfg(target_os = "linux")]
+        UdpGenericReceiveOffload = consts::UDP_GRO,
+        /// `UDP_SEGMENT`
+        #[cfg(target_os = "linux")]
+        UdpSegment = consts::UDP_SEGMENT,
+    }
+    Packet {
+        /// `PACKET_AUXDATA`
+        #[cfg(target_os = "linux")]
+        PacketAuxData = consts::PACKET_AUXDATA,
+        /// `PACKET_IGNORE_OUTGOING`
+        #[cfg(target_os = "linux")]
+        PacketIgnoreOutgoing = consts::PACKET_IGNORE_OUTGOING,
+        /// `PACKET_LOSS`
+        #[cfg(target_os = "linux")]
+        PacketLoss = consts::PACKET_LOSS,
+        /// `PACKET_QDISC_BYPASS`
+        #[cfg(target_os = "linux")]
+        PacketQdiscBypass = consts::PACKET_QDISC_BYPASS,
+        /// `PACKET_RESERVE`
+        #[cfg(target_os = "linux")]
+        PacketReserve = consts::PACKET_RESERVE,
+        /// `PACKET_STATISTICS`
+        #[cfg(target_os = "linux")]
+        PacketStatistics = consts::PACKET_STATISTICS,
+        /// `PACKET_TIMESTAMP`
+        #[cfg(target_os = "linux")]
+        PacketTimestamp = consts::PACKET_TIMESTAMP,
+        /// `PACKET_VERSION`
+        #[cfg(target_os = "linux")]
+        PacketVersion = consts::PACKET_VERSION,
+    }
 }
 
 /// A viable `optval` argument for `set/getsockopt(2)`
 pub trait SocketOption: Sized + Debug {
-    /// Returns a `Level` to be passed to `set/getsockopt(2)`
-    fn level() -> Level;
+    /// Returns a `Level` to be passed to `set/getsockopt(2)`, the `Level` of `name()` by default
+    fn level() -> Level {
+        Self::name().level()
+    }
 
     /// Returns a `Name` to be passed to `set/getsockopt(2)`
     fn name() -> Name;
@@ -141,45 +386,316 @@ pub trait SetSocketOption: SocketOption {
             cvt(setsockopt(
                 socket,
                 Self::level() as i32,
-                Self::name() as i32,
+                Self::name().value(),
                 ptr as *const c_void,
                 self.optlen(),
             ))
         }
+        .context(Operation::SetOption(Self::name()))
     }
 }
 
-/// Extension trait for a gettable `SocketOption`
-pub trait GetSocketOption: SocketOption + TryFrom<Vec<u8>>
-where
-    Self::Error: Into<SystemError>,
-{
-    /// Calls `getsockopt(2)` to retrieve a `SocktOption` of the given socket.
-    /// From<Vec<u8>> is required because it is currently demanded for the safety of the
-    /// implementation, this restriction may be lifted in a future revision.
-    /// # Errors
-    /// Will rethrow any errors produced by the underlying `getsockopt` call
-    // TODO - test GetSocketOption
-    //
-    fn get(socket: RawFd) -> Result<Self> {
-        let mut optlen = size_of::<Self>();
-        let optlen_ptr: *mut usize = &mut optlen;
-        let mut placeholder = Vec::<u8>::with_capacity(optlen);
+/// The size of the `optval` buffer `GetSocketOption::get` passes to `getsockopt(2)`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Capacity {
+    /// A buffer of the given size in bytes, of which the kernel may fill only a prefix, e.g.
+    /// `TCP_INFO` whose size depends on the kernel's version
+    Fixed(usize),
 
-        (unsafe {
+    /// A buffer sized by a first `getsockopt(2)` call with a zero `optlen`, to which the kernel
+    /// replies with the option's length, e.g. `SO_GET_FILTER`
+    ///
+    /// Both the queried length and the `optlen` of the second call count units of `unit` bytes,
+    /// e.g. instructions for `SO_GET_FILTER`. If the option grows between both calls, the second
+    /// one fails, with `EINVAL` for `SO_GET_FILTER`.
+    Queried {
+        /// The size in bytes of each unit
+        unit: usize,
+    },
+}
+
+/// A `getsockopt(2)` `optval` buffer, of which only the prefix the kernel reports having
+/// written is ever read
+#[derive(Debug)]
+struct OptionBuffer {
+    buffer: Vec<MaybeUninit<u8>>,
+    unit: usize,
+    filled: usize,
+}
+
+impl OptionBuffer {
+    /// A buffer of `length` units of `unit` bytes
+    fn new(length: usize, unit: usize) -> Self {
+        Self {
+            buffer: vec![MaybeUninit::uninit(); length * unit],
+            unit,
+            filled: 0,
+        }
+    }
+
+    /// Calls `getsockopt(2)` to fill the buffer, returns the length the kernel reported, in
+    /// units
+    fn get(&mut self, socket: RawFd, level: Level, name: Name) -> Result<usize> {
+        let mut length = (self.buffer.len() / self.unit) as socklen_t;
+        let _ = unsafe {
             cvt(getsockopt(
                 socket,
-                Self::level() as i32,
-                Self::name() as i32,
-                placeholder.as_mut_ptr() as *mut c_void,
-                optlen_ptr as *mut u32,
+                level as i32,
+                name.value(),
+                self.buffer.as_mut_ptr() as *mut c_void,
+                &mut length,
             ))
-        })
-        .and(Self::try_from(placeholder).map_err(|e| e.into()))
+            .context(Operation::GetOption(name))?
+        };
+        let length = length as usize;
+        self.filled = (length * self.unit).min(self.buffer.len());
+        Ok(length)
+    }
+
+    /// The bytes the kernel wrote
+    fn filled(&self) -> &[u8] {
+        let ptr: *const MaybeUninit<u8> = self.buffer.as_ptr();
+        // `getsockopt(2)` initialized the first `filled` bytes
+        unsafe { std::slice::from_raw_parts(ptr as *const u8, self.filled) }
+    }
+}
+
+/// Extension trait for a gettable `SocketOption`
+pub trait GetSocketOption: SocketOption {
+    /// The size of the buffer `get` passes to `getsockopt(2)`, the size of `Self` by default,
+    /// which only suits options that are plain old data
+    fn capacity() -> Capacity {
+        Capacity::Fixed(size_of::<Self>())
+    }
+
+    /// Creates the option from the bytes `getsockopt(2)` wrote
+    /// # Errors
+    /// `EINVAL` if `bytes` doesn't hold a valid option
+    fn from_bytes(bytes: &[u8]) -> Result<Self>;
+
+    /// Calls `getsockopt(2)` to retrieve a `SocketOption` of the given socket.
+    /// # Errors
+    /// Will rethrow any errors produced by the underlying `getsockopt` calls, or by `from_bytes`
+    fn get(socket: RawFd) -> Result<Self> {
+        debug!("getting option {:?} of socket {:?}", Self::name(), socket);
+
+        let mut buffer = match Self::capacity() {
+            Capacity::Fixed(size) => OptionBuffer::new(size, 1),
+            Capacity::Queried { unit } => {
+                let length = OptionBuffer::new(0, unit).get(socket, Self::level(), Self::name())?;
+                OptionBuffer::new(length, unit)
+            }
+        };
+        let _ = buffer.get(socket, Self::level(), Self::name())?;
+        Self::from_bytes(buffer.filled())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    // TODO - test bs-system
+    use super::option::*;
+    use super::*;
+    use libc::{AF_INET, EINVAL, SOCK_DGRAM, SOCK_STREAM};
+    use std::io;
+    use std::time::Duration;
+
+    /// A socket created with `socket(2)`, closed on drop
+    struct Fd(RawFd);
+
+    impl Fd {
+        fn new(type_: i32) -> Self {
+            Fd(cvt(unsafe { libc::socket(AF_INET, type_, 0) }).unwrap())
+        }
+    }
+
+    impl Drop for Fd {
+        fn drop(&mut self) {
+            let _ = unsafe { libc::close(self.0) };
+        }
+    }
+
+    #[test]
+    fn get_int_option() {
+        let fd = Fd::new(SOCK_DGRAM);
+        assert_eq!(Type::get(fd.0).map(Type::value), Ok(SOCK_DGRAM));
+        assert_eq!(ReuseAddress::get(fd.0).map(ReuseAddress::value), Ok(false));
+        let _ = ReuseAddress::new(true).set(fd.0).unwrap();
+        assert_eq!(ReuseAddress::get(fd.0).map(ReuseAddress::value), Ok(true));
+        // the kernel doubles the set size
+        let _ = ReceiveBuffer::new(4096).set(fd.0).unwrap();
+        assert_eq!(ReceiveBuffer::get(fd.0).map(ReceiveBuffer::value), Ok(8192));
+        let _ = IpTtl::new(7).set(fd.0).unwrap();
+        assert_eq!(IpTtl::get(fd.0).map(IpTtl::value), Ok(7));
+    }
+
+    #[test]
+    fn get_struct_option() {
+        let fd = Fd::new(SOCK_STREAM);
+        assert_eq!(Linger::get(fd.0).map(Linger::timeout), Ok(None));
+        let _ = Linger::new(Some(Duration::from_secs(5))).set(fd.0).unwrap();
+        assert_eq!(
+            Linger::get(fd.0).map(Linger::timeout),
+            Ok(Some(Duration::from_secs(5)))
+        );
+
+        let timeout = Some(Duration::from_millis(1500));
+        let _ = ReceiveTimeout::new(timeout).set(fd.0).unwrap();
+        assert_eq!(
+            ReceiveTimeout::get(fd.0).map(ReceiveTimeout::timeout),
+            Ok(timeout)
+        );
+    }
+
+    #[test]
+    fn option_from_bytes_invalid() {
+        assert_eq!(
+            IpTtl::from_bytes(&[64, 0]),
+            Err(SystemError::from_raw_os_error(EINVAL))
+        );
+        assert_eq!(
+            Linger::from_bytes(&[0; 12]),
+            Err(SystemError::from_raw_os_error(EINVAL))
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn get_tcp_info() {
+        let fd = Fd::new(SOCK_STREAM);
+        let info = TcpInfo::get(fd.0).unwrap();
+        // `TCP_CLOSE`
+        assert_eq!(info.state(), 7);
+        assert_eq!(info.total_retransmits(), 0);
+
+        // older kernels report a prefix of the struct
+        let info = TcpInfo::from_bytes(&[1, 0, 3]).unwrap();
+        assert_eq!(info.state(), 1);
+        assert_eq!(info.retransmits(), 3);
+        assert_eq!(info.send_mss(), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn get_variable_length_option() {
+        let fd = Fd::new(SOCK_DGRAM);
+        assert_eq!(
+            BindToDevice::get(fd.0).map(|o| o.as_str().to_owned()),
+            Ok(String::new())
+        );
+        let _ = BindToDevice::new("lo").set(fd.0).unwrap();
+        assert_eq!(
+            BindToDevice::get(fd.0).map(|o| o.as_str().to_owned()),
+            Ok("lo".to_owned())
+        );
+
+        let fd = Fd::new(SOCK_STREAM);
+        let congestion = TcpCongestion::get(fd.0).unwrap();
+        assert!(!congestion.as_str().is_empty());
+        let _ = congestion.set(fd.0).unwrap();
+        let error = TcpCongestion::new("no such algorithm")
+            .set(fd.0)
+            .unwrap_err();
+        assert_eq!(error.raw_os_error(), Some(libc::ENOENT));
+        assert_eq!(
+            error.operation(),
+            Some(Operation::SetOption(Name::TcpCongestion))
+        );
+    }
+
+    #[test]
+    fn error_display() {
+        assert_eq!(
+            SystemError::from_raw_os_error(libc::EBADF).to_string(),
+            "Bad file descriptor (EBADF)"
+        );
+        assert_eq!(
+            SystemError::from_raw_os_error(4242).to_string(),
+            "Unknown error 4242"
+        );
+        let error = SystemError::from_raw_os_error(libc::EADDRINUSE)
+            .with_operation(Operation::Syscall("bind"));
+        assert_eq!(
+            error.to_string(),
+            "bind: Address already in use (EADDRINUSE)"
+        );
+        let error = SystemError::Verifier {
+            errno: libc::EACCES,
+            log: "R0 !read_ok".to_owned(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "filter verification: Permission denied (EACCES)\nR0 !read_ok"
+        );
+    }
+
+    #[test]
+    fn error_operation() {
+        let fd = Fd::new(SOCK_DGRAM);
+        let error = IpTtl::new(0).set(fd.0).unwrap_err();
+        assert_eq!(error.raw_os_error(), Some(EINVAL));
+        assert_eq!(error.operation(), Some(Operation::SetOption(Name::IpTtl)));
+        // the first recorded operation is kept
+        let error = error.with_operation(Operation::Syscall("bind"));
+        assert_eq!(error.operation(), Some(Operation::SetOption(Name::IpTtl)));
+
+        let error = TcpNoDelay::get(fd.0).unwrap_err();
+        assert_eq!(error.raw_os_error(), Some(libc::EOPNOTSUPP));
+        assert_eq!(
+            error.to_string(),
+            "getsockopt(TCP_NODELAY): Operation not supported (EOPNOTSUPP)"
+        );
+
+        let error = SystemError::Compilation("too long".to_owned());
+        assert_eq!(error.raw_os_error(), None);
+        assert_eq!(
+            error.operation(),
+            Some(Operation::Filter(FilterStage::Compilation))
+        );
+        assert_eq!(error.to_string(), "filter compilation: too long");
+    }
+
+    #[test]
+    fn error_io_conversions() {
+        let error = io::Error::from(SystemError::from_raw_os_error(libc::ECONNREFUSED));
+        assert_eq!(error.raw_os_error(), Some(libc::ECONNREFUSED));
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+        assert_eq!(
+            SystemError::from(error),
+            SystemError::from_raw_os_error(libc::ECONNREFUSED)
+        );
+
+        // `io::Error` has no `ErrorKind` for `EPROTO`, but keeps the value
+        let error = io::Error::from_raw_os_error(libc::EPROTO);
+        assert_eq!(SystemError::from(error).raw_os_error(), Some(libc::EPROTO));
+
+        // errors with context keep their `errno` value
+        let error = io::Error::from(
+            SystemError::from_raw_os_error(libc::EADDRINUSE)
+                .with_operation(Operation::Syscall("bind")),
+        );
+        assert_eq!(error.raw_os_error(), Some(libc::EADDRINUSE));
+        assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+        assert_eq!(
+            SystemError::from(error),
+            SystemError::from_raw_os_error(libc::EADDRINUSE)
+        );
+        let error = io::Error::from(SystemError::Verifier {
+            errno: libc::EACCES,
+            log: "R0 !read_ok".to_owned(),
+        });
+        assert_eq!(error.raw_os_error(), Some(libc::EACCES));
+
+        // errors without an `errno` value survive the round trip
+        let original = SystemError::Validation("empty program".to_owned());
+        let error = io::Error::from(original.clone());
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(SystemError::from(error), original);
+
+        // errors without an `errno` value map their kind
+        let error = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+        assert_eq!(
+            SystemError::from(error).raw_os_error(),
+            Some(libc::ETIMEDOUT)
+        );
+    }
 }
@@ -0,0 +1,845 @@
+//! Typed `SocketOption`s for the `Name`s of every `Level`
+//!
+//! Most options are plain `int`s, typed here as `bool` for flags, `i32` or `u32` otherwise.
+//! Options which the kernel only reports, e.g. `SO_ERROR` or `TCP_INFO`, implement
+//! `GetSocketOption` but not `SetSocketOption`.
+//!
+//! # Example
+//! ```ignore
+//! use bs_system::option::{ReceiveBuffer, ReuseAddress};
+//! use bs_system::SetSocketOption;
+//!
+//! ReuseAddress::new(true).set(fd)?;
+//! ReceiveBuffer::new(1 << 20).set(fd)?;
+//! ```
+
+use crate::SystemError;
+use crate::{cvt, Capacity, Context, GetSocketOption, Name, Operation, Result};
+use crate::{SetSocketOption, SocketOption};
+use libc::{c_int, c_void, setsockopt, socklen_t, suseconds_t, time_t, EINVAL};
+use std::mem::size_of;
+use std::os::unix::io::RawFd;
+use std::ptr::read_unaligned;
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use {crate::consts::TCP_CA_NAME_MAX, libc::IF_NAMESIZE, std::ptr::copy_nonoverlapping};
+
+/// Rust types of `int` options
+trait IntValue: Copy {
+    fn into_int(self) -> c_int;
+    fn from_int(value: c_int) -> Self;
+}
+
+impl IntValue for bool {
+    fn into_int(self) -> c_int {
+        self.into()
+    }
+    fn from_int(value: c_int) -> Self {
+        value != 0
+    }
+}
+
+impl IntValue for i32 {
+    fn into_int(self) -> c_int {
+        self
+    }
+    fn from_int(value: c_int) -> Self {
+        value
+    }
+}
+
+impl IntValue for u32 {
+    fn into_int(self) -> c_int {
+        self as c_int
+    }
+    fn from_int(value: c_int) -> Self {
+        value as u32
+    }
+}
+
+/// Reads a `T` from the `optval` bytes returned by `getsockopt(2)`
+///
+/// `T` must be plain old data, valid for any bit pattern.
+fn read<T: Copy>(bytes: &[u8]) -> Result<T> {
+    if bytes.len() != size_of::<T>() {
+        return Err(SystemError::from_raw_os_error(EINVAL));
+    }
+    let ptr: *const T = bytes.as_ptr().cast();
+    Ok(unsafe { read_unaligned(ptr) })
+}
+
+/// Reads a `T` from a prefix of `T` returned by `getsockopt(2)`, the rest of `T` left as default
+///
+/// Used for structs which grow between kernel versions, of which older kernels only write a
+/// prefix. `T` must be plain old data, valid for any bit pattern.
+#[cfg(target_os = "linux")]
+fn read_prefix<T: Copy + Default>(bytes: &[u8]) -> T {
+    let mut value = T::default();
+    let length = bytes.len().min(size_of::<T>());
+    let ptr: *mut T = &mut value;
+    unsafe { copy_nonoverlapping(bytes.as_ptr(), ptr as *mut u8, length) };
+    value
+}
+
+/// Calls `setsockopt(2)` with `bytes` as the `optval` of the `Name` `name`
+fn set_bytes(socket: RawFd, name: Name, bytes: &[u8]) -> Result<i32> {
+    unsafe {
+        cvt(setsockopt(
+            socket,
+            name.level() as i32,
+            name.value(),
+            bytes.as_ptr() as *const c_void,
+            bytes.len() as socklen_t,
+        ))
+    }
+    .context(Operation::SetOption(name))
+}
+
+/// Declares an `int` option, typed as `$type`, for the `Name` of the same name
+macro_rules! int_option {
+    (@common $(#[doc = $doc:literal])* $(#[cfg($cfg:meta)])? $option:ident: $type:ty) => {
+        $(#[doc = $doc])*
+        $(#[cfg($cfg)])?
+        #[repr(transparent)]
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub struct $option(c_int);
+
+        $(#[cfg($cfg)])?
+        impl $option {
+            /// Creates the option with the given value
+            pub fn new(value: $type) -> Self {
+                Self(value.into_int())
+            }
+
+            /// The option's value
+            pub fn value(self) -> $type {
+                <$type>::from_int(self.0)
+            }
+        }
+
+        $(#[cfg($cfg)])?
+        impl SocketOption for $option {
+            fn name() -> Name {
+                Name::$option
+            }
+            fn optlen(&self) -> socklen_t {
+                size_of::<c_int>() as socklen_t
+            }
+        }
+
+        $(#[cfg($cfg)])?
+        impl GetSocketOption for $option {
+            fn from_bytes(bytes: &[u8]) -> Result<Self> {
+                read(bytes).map(Self)
+            }
+        }
+    };
+    (read_only $(#[doc = $doc:literal])* $(#[cfg($cfg:meta)])? $option:ident: $type:ty) => {
+        int_option!(@common $(#[doc = $doc])* $(#[cfg($cfg)])? $option: $type);
+    };
+    ($(#[doc = $doc:literal])* $(#[cfg($cfg:meta)])? $option:ident: $type:ty) => {
+        int_option!(@common $(#[doc = $doc])* $(#[cfg($cfg)])? $option: $type);
+
+        $(#[cfg($cfg)])?
+        impl SetSocketOption for $option {}
+    };
+}
+
+int_option!(read_only
+    /// `SO_ACCEPTCONN`, whether the socket is listening for connections
+    AcceptConnections: bool
+);
+int_option!(
+    /// `SO_BROADCAST`, whether datagrams may be sent to broadcast addresses
+    Broadcast: bool
+);
+int_option!(
+    /// `SO_DONTROUTE`, whether sent packets bypass routing, reaching directly connected hosts
+    /// only
+    DontRoute: bool
+);
+int_option!(read_only
+    /// `SO_ERROR`, the socket's pending error, cleared by reading it
+    Error: i32
+);
+int_option!(
+    /// `SO_KEEPALIVE`, whether keep-alive probes are sent on connection-oriented sockets
+    KeepAlive: bool
+);
+int_option!(
+    /// `SO_RCVBUF`, the receive buffer size in bytes
+    ///
+    /// The kernel doubles the set value to account for its own bookkeeping, and reports the
+    /// doubled value.
+    ReceiveBuffer: i32
+);
+int_option!(
+    /// `SO_RCVLOWAT`, the minimal number of bytes a receive call waits for
+    ReceiveLowWatermark: i32
+);
+int_option!(
+    /// `SO_REUSEADDR`, whether the socket may bind to a local address still in use by a socket
+    /// which isn't listening
+    ReuseAddress: bool
+);
+int_option!(
+    /// `SO_REUSEPORT`, whether sockets of the same user may bind to the same address, with the
+    /// kernel balancing incoming traffic between them
+    ReusePort: bool
+);
+int_option!(
+    /// `SO_SNDBUF`, the send buffer size in bytes, doubled by the kernel like `ReceiveBuffer`
+    SendBuffer: i32
+);
+int_option!(read_only
+    /// `SO_TYPE`, the socket's type, e.g. `SOCK_STREAM`
+    Type: i32
+);
+int_option!(
+    /// `SO_LOCK_FILTER`, whether the socket's filter is locked, so it can't be replaced or
+    /// detached anymore
+    ///
+    /// Once locked, the filter can't be unlocked.
+    #[cfg(target_os = "linux")]
+    LockFilter: bool
+);
+int_option!(
+    /// `SO_BUSY_POLL`, the time in microseconds to busy poll the device queue for when no
+    /// packets are received
+    #[cfg(target_os = "linux")]
+    BusyPoll: i32
+);
+int_option!(read_only
+    /// `SO_DOMAIN`, the socket's domain, e.g. `AF_INET`
+    #[cfg(target_os = "linux")]
+    Domain: i32
+);
+int_option!(
+    /// `SO_MARK`, the mark of sent packets, used by routing and netfilter
+    ///
+    /// Setting it requires `CAP_NET_ADMIN`.
+    #[cfg(target_os = "linux")]
+    Mark: u32
+);
+int_option!(
+    /// `SO_PASSCRED`, whether received messages carry the credentials of their sender
+    #[cfg(target_os = "linux")]
+    PassCredentials: bool
+);
+int_option!(
+    /// `SO_PRIORITY`, the protocol-defined priority of sent packets, selecting the device queue
+    #[cfg(target_os = "linux")]
+    Priority: i32
+);
+int_option!(read_only
+    /// `SO_PROTOCOL`, the socket's protocol, e.g. `IPPROTO_TCP`
+    #[cfg(target_os = "linux")]
+    Protocol: i32
+);
+int_option!(
+    /// `SO_TIMESTAMP`, whether received packets carry their reception time, in microseconds
+    #[cfg(target_os = "linux")]
+    Timestamp: bool
+);
+int_option!(
+    /// `SO_TIMESTAMPNS`, whether received packets carry their reception time, in nanoseconds
+    #[cfg(target_os = "linux")]
+    TimestampNs: bool
+);
+int_option!(
+    /// `SO_TIMESTAMPING`, the `SOF_TIMESTAMPING_*` flags selecting which software and hardware
+    /// timestamps are generated and reported
+    #[cfg(target_os = "linux")]
+    Timestamping: u32
+);
+
+int_option!(
+    /// `IP_HDRINCL`, whether sent packets include their IP header
+    IpHeaderIncluded: bool
+);
+int_option!(
+    /// `IP_MULTICAST_LOOP`, whether sent multicast packets are looped back to local sockets
+    IpMulticastLoop: bool
+);
+int_option!(
+    /// `IP_MULTICAST_TTL`, the TTL of sent multicast packets
+    IpMulticastTtl: i32
+);
+int_option!(
+    /// `IP_RECVTOS`, whether received packets carry their TOS field
+    IpReceiveTypeOfService: bool
+);
+int_option!(
+    /// `IP_RECVTTL`, whether received packets carry their TTL
+    IpReceiveTtl: bool
+);
+int_option!(
+    /// `IP_TOS`, the TOS field of sent packets
+    IpTypeOfService: i32
+);
+int_option!(
+    /// `IP_TTL`, the TTL of sent unicast packets
+    IpTtl: i32
+);
+int_option!(
+    /// `IP_BIND_ADDRESS_NO_PORT`, whether binding to port 0 defers allocating the port until
+    /// the socket connects
+    #[cfg(target_os = "linux")]
+    IpBindAddressNoPort: bool
+);
+int_option!(
+    /// `IP_FREEBIND`, whether the socket may bind to addresses which aren't local (yet)
+    #[cfg(target_os = "linux")]
+    IpFreeBind: bool
+);
+int_option!(
+    /// `IP_MTU_DISCOVER`, the path MTU discovery mode, one of the `IP_PMTUDISC_*` values
+    #[cfg(target_os = "linux")]
+    IpMtuDiscover: i32
+);
+int_option!(
+    /// `IP_PKTINFO`, whether received packets carry their destination address and interface
+    #[cfg(target_os = "linux")]
+    IpPacketInfo: bool
+);
+int_option!(
+    /// `IP_RECVERR`, whether extended errors are queued to the socket's error queue
+    #[cfg(target_os = "linux")]
+    IpReceiveError: bool
+);
+int_option!(
+    /// `IP_TRANSPARENT`, whether the socket may act as a transparent proxy, binding to and
+    /// receiving for foreign addresses
+    ///
+    /// Setting it requires `CAP_NET_ADMIN`.
+    #[cfg(target_os = "linux")]
+    IpTransparent: bool
+);
+
+int_option!(
+    /// `IPV6_MULTICAST_HOPS`, the hop limit of sent multicast packets, -1 for the route's
+    /// default
+    Ipv6MulticastHops: i32
+);
+int_option!(
+    /// `IPV6_MULTICAST_LOOP`, whether sent multicast packets are looped back to local sockets
+    Ipv6MulticastLoop: bool
+);
+int_option!(
+    /// `IPV6_RECVHOPLIMIT`, whether received packets carry their hop limit
+    Ipv6ReceiveHopLimit: bool
+);
+int_option!(
+    /// `IPV6_RECVPKTINFO`, whether received packets carry their destination address and
+    /// interface
+    Ipv6ReceivePacketInfo: bool
+);
+int_option!(
+    /// `IPV6_RECVTCLASS`, whether received packets carry their traffic class
+    Ipv6ReceiveTrafficClass: bool
+);
+int_option!(
+    /// `IPV6_TCLASS`, the traffic class of sent packets, -1 for the kernel's default
+    Ipv6TrafficClass: i32
+);
+int_option!(
+    /// `IPV6_UNICAST_HOPS`, the hop limit of sent unicast packets, -1 for the route's default
+    Ipv6UnicastHops: i32
+);
+int_option!(
+    /// `IPV6_V6ONLY`, whether the socket is restricted to IPv6, rather than also exchanging
+    /// IPv4 traffic through IPv4-mapped addresses
+    Ipv6V6Only: bool
+);
+
+int_option!(
+    /// `TCP_KEEPCNT`, the number of unanswered keep-alive probes before the connection is
+    /// dropped
+    TcpKeepAliveCount: i32
+);
+int_option!(
+    /// `TCP_KEEPINTVL`, the time in seconds between keep-alive probes
+    TcpKeepAliveInterval: i32
+);
+int_option!(
+    /// `TCP_MAXSEG`, the maximal segment size of outgoing segments
+    TcpMaxSegment: i32
+);
+int_option!(
+    /// `TCP_NODELAY`, whether small segments are sent right away, rather than coalesced by
+    /// Nagle's algorithm
+    TcpNoDelay: bool
+);
+int_option!(
+    /// `TCP_CORK`, whether partial segments are held back until the option is cleared, or for
+    /// at most 200 milliseconds
+    #[cfg(target_os = "linux")]
+    TcpCork: bool
+);
+int_option!(
+    /// `TCP_DEFER_ACCEPT`, the time in seconds a listening socket waits for data before
+    /// accepting a connection
+    #[cfg(target_os = "linux")]
+    TcpDeferAccept: i32
+);
+int_option!(
+    /// `TCP_FASTOPEN`, the queue length of pending TCP Fast Open requests of a listening socket
+    #[cfg(target_os = "linux")]
+    TcpFastOpen: i32
+);
+int_option!(
+    /// `TCP_KEEPIDLE`, the idle time in seconds before keep-alive probes are sent
+    #[cfg(target_os = "linux")]
+    TcpKeepAliveIdle: i32
+);
+int_option!(
+    /// `TCP_NOTSENT_LOWAT`, the number of unsent bytes above which the socket isn't writable
+    #[cfg(target_os = "linux")]
+    TcpNotSentLowWatermark: u32
+);
+int_option!(
+    /// `TCP_QUICKACK`, whether acknowledgements are sent right away rather than delayed
+    ///
+    /// The kernel may clear the option on its own, so it must be set again as needed.
+    #[cfg(target_os = "linux")]
+    TcpQuickAck: bool
+);
+int_option!(
+    /// `TCP_USER_TIMEOUT`, the time in milliseconds sent data may stay unacknowledged before
+    /// the connection is dropped, 0 for the system's default
+    #[cfg(target_os = "linux")]
+    TcpUserTimeout: u32
+);
+
+int_option!(
+    /// `UDP_CORK`, whether sent data is accumulated into a single datagram until the option is
+    /// cleared
+    #[cfg(target_os = "linux")]
+    UdpCork: bool
+);
+int_option!(
+    /// `UDP_GRO`, whether received datagrams may be coalesced (UDP GRO)
+    #[cfg(target_os = "linux")]
+    UdpGenericReceiveOffload: bool
+);
+int_option!(
+    /// `UDP_SEGMENT`, the size of the datagrams sent payloads are split to (UDP GSO), 0 to
+    /// disable segmentation
+    #[cfg(target_os = "linux")]
+    UdpSegment: i32
+);
+
+int_option!(
+    /// `PACKET_AUXDATA`, whether received packets carry their `struct tpacket_auxdata`
+    #[cfg(target_os = "linux")]
+    PacketAuxData: bool
+);
+int_option!(
+    /// `PACKET_IGNORE_OUTGOING`, whether packets sent by the host are left out
+    #[cfg(target_os = "linux")]
+    PacketIgnoreOutgoing: bool
+);
+int_option!(
+    /// `PACKET_LOSS`, whether malformed packets are dropped from the transmit ring rather than
+    /// stopping its transmission
+    #[cfg(target_os = "linux")]
+    PacketLoss: bool
+);
+int_option!(
+    /// `PACKET_QDISC_BYPASS`, whether sent packets bypass the kernel's queueing disciplines
+    #[cfg(target_os = "linux")]
+    PacketQdiscBypass: bool
+);
+int_option!(
+    /// `PACKET_RESERVE`, the headroom in bytes reserved before each packet of the receive ring
+    #[cfg(target_os = "linux")]
+    PacketReserve: u32
+);
+int_option!(
+    /// `PACKET_TIMESTAMP`, the `SOF_TIMESTAMPING_*` flags selecting the timestamps of the
+    /// rings' packets
+    #[cfg(target_os = "linux")]
+    PacketTimestamp: u32
+);
+int_option!(
+    /// `PACKET_VERSION`, the version of the rings' frame headers, one of the `TPACKET_V*`
+    /// values
+    #[cfg(target_os = "linux")]
+    PacketVersion: i32
+);
+
+/// `SO_DETACH_FILTER`, detaches the socket's filter
+#[cfg(target_os = "linux")]
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DetachFilter(c_int);
+
+#[cfg(target_os = "linux")]
+impl SocketOption for DetachFilter {
+    fn name() -> Name {
+        Name::DetachFilter
+    }
+    fn optlen(&self) -> socklen_t {
+        size_of::<c_int>() as socklen_t
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl SetSocketOption for DetachFilter {}
+
+/// `SO_LINGER`, how long closing a connected socket waits for its unsent data to be sent
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Linger {
+    on: c_int,
+    seconds: c_int,
+}
+
+impl Linger {
+    /// Lingers for up to `timeout`, whole seconds only, or doesn't linger for `None`
+    ///
+    /// A zero `timeout` resets connections on close, rather than shutting them down gracefully.
+    pub fn new(timeout: Option<Duration>) -> Self {
+        match timeout {
+            Some(timeout) => Self {
+                on: 1,
+                seconds: timeout.as_secs().min(i32::MAX as u64) as c_int,
+            },
+            None => Self { on: 0, seconds: 0 },
+        }
+    }
+
+    /// The time closing the socket lingers for, `None` if it doesn't linger
+    pub fn timeout(self) -> Option<Duration> {
+        if self.on == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.seconds as u64))
+        }
+    }
+}
+
+impl SocketOption for Linger {
+    fn name() -> Name {
+        Name::Linger
+    }
+    fn optlen(&self) -> socklen_t {
+        size_of::<Self>() as socklen_t
+    }
+}
+
+impl SetSocketOption for Linger {}
+
+impl GetSocketOption for Linger {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        read(bytes)
+    }
+}
+
+/// `TCP_INFO`'s `struct tcp_info`, the state and statistics of a `tcp(7)` connection
+///
+/// Only the fields every supported kernel reports are mirrored, as the struct keeps growing.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TcpInfo {
+    state: u8,
+    congestion_state: u8,
+    retransmits: u8,
+    probes: u8,
+    backoff: u8,
+    options: u8,
+    window_scales: u8,
+    flags: u8,
+    retransmission_timeout: u32,
+    delayed_ack_timeout: u32,
+    send_mss: u32,
+    receive_mss: u32,
+    unacknowledged: u32,
+    selectively_acknowledged: u32,
+    lost: u32,
+    retransmitted: u32,
+    forward_acknowledged: u32,
+    last_data_sent: u32,
+    last_ack_sent: u32,
+    last_data_received: u32,
+    last_ack_received: u32,
+    path_mtu: u32,
+    receive_slow_start_threshold: u32,
+    round_trip_time: u32,
+    round_trip_time_variance: u32,
+    send_slow_start_threshold: u32,
+    send_congestion_window: u32,
+    advertised_mss: u32,
+    reordering: u32,
+    receive_round_trip_time: u32,
+    receive_space: u32,
+    total_retransmits: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl TcpInfo {
+    /// The connection's state, one of the `TCP_ESTABLISHED`..`TCP_CLOSING` values
+    pub fn state(&self) -> u8 {
+        self.state
+    }
+
+    /// The number of unrecovered retransmission timeouts
+    pub fn retransmits(&self) -> u8 {
+        self.retransmits
+    }
+
+    /// The retransmission timeout
+    pub fn retransmission_timeout(&self) -> Duration {
+        Duration::from_micros(self.retransmission_timeout.into())
+    }
+
+    /// The maximal segment size for sending
+    pub fn send_mss(&self) -> u32 {
+        self.send_mss
+    }
+
+    /// The maximal segment size for receiving
+    pub fn receive_mss(&self) -> u32 {
+        self.receive_mss
+    }
+
+    /// The number of sent segments not acknowledged yet
+    pub fn unacknowledged(&self) -> u32 {
+        self.unacknowledged
+    }
+
+    /// The number of sent segments considered lost
+    pub fn lost(&self) -> u32 {
+        self.lost
+    }
+
+    /// The path MTU
+    pub fn path_mtu(&self) -> u32 {
+        self.path_mtu
+    }
+
+    /// The smoothed round trip time
+    pub fn round_trip_time(&self) -> Duration {
+        Duration::from_micros(self.round_trip_time.into())
+    }
+
+    /// The round trip time's mean deviation
+    pub fn round_trip_time_variance(&self) -> Duration {
+        Duration::from_micros(self.round_trip_time_variance.into())
+    }
+
+    /// The congestion window, in segments
+    pub fn send_congestion_window(&self) -> u32 {
+        self.send_congestion_window
+    }
+
+    /// The number of segments retransmitted over the connection's lifetime
+    pub fn total_retransmits(&self) -> u32 {
+        self.total_retransmits
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl SocketOption for TcpInfo {
+    fn name() -> Name {
+        Name::TcpInfo
+    }
+    fn optlen(&self) -> socklen_t {
+        size_of::<Self>() as socklen_t
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl GetSocketOption for TcpInfo {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(read_prefix(bytes))
+    }
+}
+
+/// `PACKET_STATISTICS`'s `struct tpacket_stats`, the packet counters of a `packet(7)` socket
+///
+/// Reading the counters resets them.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PacketStatistics {
+    packets: u32,
+    drops: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl PacketStatistics {
+    /// The number of packets received, including dropped ones
+    pub fn packets(&self) -> u32 {
+        self.packets
+    }
+
+    /// The number of packets dropped, as the receive buffer or ring was full
+    pub fn drops(&self) -> u32 {
+        self.drops
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl SocketOption for PacketStatistics {
+    fn name() -> Name {
+        Name::PacketStatistics
+    }
+    fn optlen(&self) -> socklen_t {
+        size_of::<Self>() as socklen_t
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl GetSocketOption for PacketStatistics {
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        read(bytes)
+    }
+}
+
+/// `struct timeval`
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct TimeValue {
+    seconds: time_t,
+    microseconds: suseconds_t,
+}
+
+impl TimeValue {
+    fn new(timeout: Option<Duration>) -> Self {
+        let timeout = timeout.unwrap_or_default();
+        Self {
+            seconds: timeout.as_secs() as time_t,
+            microseconds: timeout.subsec_micros() as suseconds_t,
+        }
+    }
+
+    fn timeout(self) -> Option<Duration> {
+        match (self.seconds, self.microseconds) {
+            (0, 0) => None,
+            (seconds, microseconds) => Some(
+                Duration::from_secs(seconds as u64) + Duration::from_micros(microseconds as u64),
+            ),
+        }
+    }
+}
+
+/// Declares a `struct timeval` option for the `Name` of the same name
+macro_rules! timeout_option {
+    ($(#[doc = $doc:literal])* $option:ident) => {
+        $(#[doc = $doc])*
+        #[repr(transparent)]
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub struct $option(TimeValue);
+
+        impl $option {
+            /// Times out after `timeout`, microseconds precision, or never for `None`
+            ///
+            /// A zero `timeout` never times out either.
+            pub fn new(timeout: Option<Duration>) -> Self {
+                Self(TimeValue::new(timeout))
+            }
+
+            /// The timeout, `None` if the socket never times out
+            pub fn timeout(self) -> Option<Duration> {
+                self.0.timeout()
+            }
+        }
+
+        impl SocketOption for $option {
+            fn name() -> Name {
+                Name::$option
+            }
+            fn optlen(&self) -> socklen_t {
+                size_of::<TimeValue>() as socklen_t
+            }
+        }
+
+        impl SetSocketOption for $option {}
+
+        impl GetSocketOption for $option {
+            fn from_bytes(bytes: &[u8]) -> Result<Self> {
+                read(bytes).map(Self)
+            }
+        }
+    };
+}
+
+timeout_option!(
+    /// `SO_RCVTIMEO`, how long a blocking receive call waits before failing with `EAGAIN`
+    ReceiveTimeout
+);
+timeout_option!(
+    /// `SO_SNDTIMEO`, how long a blocking send call waits before failing with `EAGAIN`
+    SendTimeout
+);
+
+/// Declares an option holding a name of at most `$capacity` bytes, including the terminating
+/// NUL, e.g. an interface name, for the `Name` of the same name
+macro_rules! string_option {
+    ($(#[doc = $doc:literal])* $option:ident($capacity:expr)) => {
+        $(#[doc = $doc])*
+        #[cfg(target_os = "linux")]
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub struct $option(String);
+
+        #[cfg(target_os = "linux")]
+        impl $option {
+            /// Creates the option with the given name
+            pub fn new(name: impl Into<String>) -> Self {
+                Self(name.into())
+            }
+
+            /// The option's name
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        impl SocketOption for $option {
+            fn name() -> Name {
+                Name::$option
+            }
+            fn optlen(&self) -> socklen_t {
+                self.0.len() as socklen_t
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        impl SetSocketOption for $option {
+            fn set(&self, socket: RawFd) -> Result<i32> {
+                set_bytes(socket, Self::name(), self.0.as_bytes())
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        impl GetSocketOption for $option {
+            fn capacity() -> Capacity {
+                Capacity::Fixed($capacity)
+            }
+
+            /// The kernel reports names NUL-terminated
+            fn from_bytes(bytes: &[u8]) -> Result<Self> {
+                let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+                String::from_utf8(bytes[..end].to_vec())
+                    .map(Self)
+                    .map_err(|_| SystemError::from_raw_os_error(EINVAL))
+            }
+        }
+    };
+}
+
+string_option!(
+    /// `SO_BINDTODEVICE`, the name of the interface the socket is bound to, so it only
+    /// exchanges packets through it
+    ///
+    /// An empty name removes the binding. Setting it requires `CAP_NET_RAW`, unless the socket
+    /// isn't bound to an interface yet.
+    BindToDevice(IF_NAMESIZE)
+);
+string_option!(
+    /// `TCP_CONGESTION`, the name of the congestion control algorithm, e.g. `cubic`
+    TcpCongestion(TCP_CA_NAME_MAX)
+);
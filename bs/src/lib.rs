@@ -6,7 +6,7 @@
 //! ```
 //! # use bs_system::Result;
 //! # use bs_system::SystemError as Error;
-//! # use bs_socket::socket::{BasicSocket, SetFilter};
+//! # use bs_socket::socket::{Receive, SetFilter};
 //! # use std::net::IpAddr;
 //! # use eui48::MacAddress;
 //! use bs::{
@@ -30,13 +30,13 @@
 //!
 //! fn raw_ethernet_only_loves_one_one_one_one(buffer: &mut [u8]) -> Result<()> {
 //!
-//!     let vip = "1.1.1.1".parse().map_err(|_| Error(PARSE_ERROR))?;
-//!     let my_gateway = "00:11:22:33:44:55".parse().map_err(|_| Error(PARSE_ERROR))?;
+//!     let vip = "1.1.1.1".parse().map_err(|_| Error::from_raw_os_error(PARSE_ERROR))?;
+//!     let my_gateway = "00:11:22:33:44:55".parse().map_err(|_| Error::from_raw_os_error(PARSE_ERROR))?;
 //!
 //!     let mut s: Socket<PacketLayer2Socket> = Socket::new()?;
 //!
 //!     s.set_filter(
-//!         ( ip_src::<Classic>(vip) & ether_src(my_gateway) )
+//!         ( ip_src::<Classic, _>(vip) & ether_src(my_gateway) )
 //!             .compile()?
 //!             .build()?
 //!     )?;
@@ -85,15 +85,21 @@
 pub mod filter {
     pub use bs_filter::backend;
     pub use bs_filter::idiom;
+    pub use bs_filter::layer;
 }
 
 /// Main sockets API
 ///
 /// see `bs-socket` for more information
 pub mod socket {
+    pub use bs_socket::address;
+    pub use bs_socket::message;
+    pub use bs_socket::option;
     #[cfg(target_os = "linux")]
     pub use bs_socket::packet;
     pub use bs_socket::raw;
+    #[cfg(target_os = "linux")]
+    pub use bs_socket::ring;
     pub use bs_socket::socket;
     pub use bs_socket::tcp;
     pub use bs_socket::udp;
@@ -157,7 +163,7 @@ mod tests {
         init();
         let mut s: Socket<packet::PacketLayer2Socket> = Socket::new().unwrap();
         let _ = s.set_filter(
-            idiom::ip::ip_host::<backend::Classic>("1.1.1.1".parse().unwrap())
+            idiom::ip::ip_host::<backend::Classic, _>("1.1.1.1".parse().unwrap())
                 .compile()
                 .unwrap()
                 .build()
@@ -165,7 +171,7 @@ mod tests {
         );
 
         let _ = s.set_filter(
-            idiom::ip::ip_host::<backend::Classic>("::1".parse().unwrap())
+            idiom::ip::ip_host::<backend::Classic, _>("::1".parse().unwrap())
                 .compile()
                 .unwrap()
                 .build()
@@ -176,13 +182,139 @@ mod tests {
         //      let _ = s.recv(&mut buf, 0);
     }
 
+    #[cfg(feature = "bs-filter")]
+    #[test]
+    #[allow(unused_results)]
+    fn packet_socket_meta() {
+        use bs_system::consts::{ETH_P_IP, PACKET_OUTGOING};
+        use std::time::Duration;
+
+        fn receiver(
+            p: bs_filter::Predicate<backend::Classic, layer::Ethernet>,
+        ) -> Socket<packet::PacketLayer2Socket> {
+            Socket::builder()
+                .filter(p.compile().unwrap().build().unwrap())
+                .option(option::ReceiveTimeout::new(Some(Duration::from_millis(
+                    100,
+                ))))
+                .build()
+                .unwrap()
+        }
+
+        init();
+        let mut s: Socket<packet::PacketLayer2Socket> = Socket::new().unwrap();
+        let p = (idiom::meta::ifindex::<backend::Classic, _>(1) & idiom::meta::pkt_type_outgoing())
+            | (idiom::meta::mark(0x10) & idiom::meta::sample(1, 100));
+        let f = p.compile().unwrap().build().unwrap();
+        let _ = s.set_filter(f).unwrap();
+
+        // the filters have to read the right ancillary data for the right packets to get through
+        let lo = packet::interface_index("lo").unwrap();
+        let outgoing = receiver(
+            idiom::meta::ifindex(lo as u32)
+                & idiom::meta::pkt_type_outgoing()
+                & idiom::meta::protocol(ETH_P_IP as u16),
+        );
+        let marked = receiver(idiom::meta::mark(0x10));
+
+        let mut server: Socket<udp::UdpSocket> = Socket::new().unwrap();
+        server.bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let server_address = server.local_address().unwrap();
+        let mut client: Socket<udp::UdpSocket> = Socket::new().unwrap();
+        client.send_to(b"plain", &server_address, 0).unwrap();
+        client.set_option(option::Mark::new(0x10)).unwrap();
+        client.send_to(b"marked", &server_address, 0).unwrap();
+
+        // the looped back copies come in as PACKET_HOST frames, which only the mark lets through
+        let mut sent = 0;
+        let mut buffer = [0; 2048];
+        while let Ok((length, address)) = outgoing.receive_from(&mut buffer, 0) {
+            assert_eq!(address.interface_index(), lo);
+            assert_eq!(address.packet_type(), PACKET_OUTGOING);
+            assert_eq!(address.protocol(), ETH_P_IP as u16);
+            if buffer[..length].ends_with(b"plain") || buffer[..length].ends_with(b"marked") {
+                sent += 1;
+            }
+        }
+        assert_eq!(sent, 2);
+
+        let mut frames = 0;
+        while let Ok(length) = marked.receive(&mut buffer, 0) {
+            assert!(buffer[..length].ends_with(b"marked"));
+            frames += 1;
+        }
+        assert!(frames > 0);
+    }
+
+    #[cfg(feature = "ebpf")]
+    #[test]
+    fn packet_socket_ebpf_meta() {
+        init();
+        let mut s: Socket<packet::PacketLayer2Socket> = Socket::new().unwrap();
+        let p = (idiom::meta::ifindex::<backend::Extended, _>(1)
+            & idiom::meta::protocol(0x0800)
+            & idiom::meta::cpu(0))
+            | (idiom::meta::mark(0x10) & idiom::meta::sample(1, 100));
+        let f = p.compile().unwrap().build().unwrap();
+        let _ = s.set_filter(f).unwrap();
+    }
+
+    #[cfg(feature = "bs-filter")]
+    #[test]
+    fn udp_socket_field() {
+        init();
+        let mut s: Socket<udp::UdpSocket> = Socket::new().unwrap();
+        // source port is unprivileged, destination port is privileged, UDP length is even
+        let p = idiom::field::field_u16::<backend::Classic, _>(0).in_range(1024..)
+            & idiom::field::field_u16(2).lesser_than(1024)
+            & idiom::field::field_u16(4).mask(1).equals(0);
+        let f = p.compile().unwrap().build().unwrap();
+        let _ = s.set_filter(f).unwrap();
+    }
+
+    #[cfg(feature = "ebpf")]
+    #[test]
+    fn udp_socket_ebpf_field() {
+        init();
+        let mut s: Socket<udp::UdpSocket> = Socket::new().unwrap();
+        let p = idiom::field::field_u16::<backend::Extended, _>(0).in_range(1024..)
+            & idiom::field::field_u16(2).lesser_than(1024)
+            & idiom::field::field_u16(4).mask(1).equals(0);
+        let f = p.compile().unwrap().build().unwrap();
+        let _ = s.set_filter(f).unwrap();
+    }
+
+    #[cfg(feature = "bs-filter")]
+    #[test]
+    fn packet_socket_ip4_src_in() {
+        init();
+        let mut s: Socket<packet::PacketLayer2Socket> = Socket::new().unwrap();
+        let ips: Vec<_> = (0..500_u32).map(|i| (0x0a00_0000 + i).into()).collect();
+        let p = idiom::ip::ip4_src_in::<backend::Classic, _>(&ips)
+            & idiom::transport::port_in(&[53, 123, 443]);
+        let f = p.compile().unwrap().build().unwrap();
+        let _ = s.set_filter(f).unwrap();
+    }
+
+    #[cfg(feature = "ebpf")]
+    #[test]
+    fn packet_socket_ebpf_ip4_src_in() {
+        init();
+        let mut s: Socket<packet::PacketLayer2Socket> = Socket::new().unwrap();
+        let ips: Vec<_> = (0..500_u32).map(|i| (0x0a00_0000 + i).into()).collect();
+        let p = idiom::ip::ip4_src_in::<backend::Extended, _>(&ips)
+            & idiom::transport::port_in(&[53, 123, 443]);
+        let f = p.compile().unwrap().build().unwrap();
+        let _ = s.set_filter(f).unwrap();
+    }
+
     #[cfg(feature = "ebpf")]
     #[test]
     fn packet_socket_ebpf_ip_host() {
         init();
         let mut s: Socket<packet::PacketLayer2Socket> = Socket::new().unwrap();
         let _ = s.set_filter(
-            idiom::ip::ip_host::<backend::Extended>("1.1.1.1".parse().unwrap())
+            idiom::ip::ip_host::<backend::Extended, _>("1.1.1.1".parse().unwrap())
                 .compile()
                 .unwrap()
                 .build()